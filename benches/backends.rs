@@ -0,0 +1,123 @@
+// Times loop-heavy programs on each backend, then a short script run many times, compiled
+// afresh each time and compiled once up front. Run with 'cargo bench'.
+
+use std::time::{Duration, Instant};
+
+use lula::{interpreter::Backend, statement::Statement, Interpreter, Lexer, Parser, Program};
+
+const RUNS: u32 = 5;
+
+const PROGRAMS: &[(&str, &str)] = &[
+    (
+        "counting loop",
+        "let total = 0\nlet i = 0\nwhile i < 200000 {\n    total = total + i % 7\n    i = i + 1\n}\ntotal",
+    ),
+    (
+        "nested for loops",
+        "let hits = 0\nfor i in 0..300 {\n    for j in 0..300 {\n        if (i + j) % 3 == 0 {\n            hits = hits + 1\n        }\n    }\n}\nhits",
+    ),
+    (
+        "recursive calls",
+        "func fib(n) {\n    if n < 2 {\n        return n\n    }\n    return fib(n - 1) + fib(n - 2)\n}\nfib(20)",
+    ),
+    (
+        "map building",
+        "let counts = {}\nfor i in 0..20000 {\n    counts[str(i % 100)] = i\n}\nlet sum = 0\nfor key in counts {\n    sum = sum + counts[key]\n}\nsum",
+    ),
+];
+
+// A script like a host might run on every request or frame, which does little enough work
+// that lexing and parsing it is a large part of each run
+const HOOK: &str = "let request = {path: \"/users/42\", method: \"GET\"}\nlet parts = request[\"path\"].split(\"/\")\nlet allowed = request[\"method\"] == \"GET\" or request[\"method\"] == \"HEAD\"\nallowed and len(parts) == 3 ? parts[2] : nil";
+
+const HOOK_RUNS: u32 = 10_000;
+
+fn parse(source: &str) -> Vec<Statement> {
+    Parser::new(Lexer::new(source.to_owned()))
+        .collect_statements()
+        .unwrap()
+}
+
+// Takes the fastest of several runs, which is the least disturbed by other processes
+fn time(statements: &[Statement], backend: Backend) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_backend(backend);
+
+            let start = Instant::now();
+            interpreter.run_statements(statements).unwrap();
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+// Times running the hook repeatedly in one interpreter, compiling it on each run unless
+// 'reuse' is set
+fn time_hook(backend: Backend, reuse: bool) -> Duration {
+    let program = Program::compile(HOOK).unwrap();
+
+    (0..RUNS)
+        .map(|_| {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_backend(backend);
+
+            let start = Instant::now();
+
+            for _ in 0..HOOK_RUNS {
+                if reuse {
+                    interpreter.run(&program).unwrap();
+                } else {
+                    interpreter.run(&Program::compile(HOOK).unwrap()).unwrap();
+                }
+            }
+
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn main() {
+    println!(
+        "{:<20} {:>12} {:>12} {:>9}",
+        "program", "tree", "vm", "speedup"
+    );
+
+    for (name, source) in PROGRAMS {
+        let statements = parse(source);
+
+        let tree = time(&statements, Backend::Tree);
+        let vm = time(&statements, Backend::Vm);
+
+        println!(
+            "{:<20} {:>10.2}ms {:>10.2}ms {:>8.2}x",
+            name,
+            tree.as_secs_f64() * 1000.0,
+            vm.as_secs_f64() * 1000.0,
+            tree.as_secs_f64() / vm.as_secs_f64()
+        );
+    }
+
+    println!(
+        "\n{:<20} {:>12} {:>12} {:>9}",
+        format!("hook x{}", HOOK_RUNS),
+        "compiled",
+        "reused",
+        "speedup"
+    );
+
+    for (name, backend) in [("tree", Backend::Tree), ("vm", Backend::Vm)] {
+        let compiled = time_hook(backend, false);
+        let reused = time_hook(backend, true);
+
+        println!(
+            "{:<20} {:>10.2}ms {:>10.2}ms {:>8.2}x",
+            name,
+            compiled.as_secs_f64() * 1000.0,
+            reused.as_secs_f64() * 1000.0,
+            compiled.as_secs_f64() / reused.as_secs_f64()
+        );
+    }
+}
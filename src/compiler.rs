@@ -0,0 +1,812 @@
+use std::rc::Rc;
+
+use crate::error::LulaError;
+use crate::expr::Expr;
+use crate::messages::Message;
+use crate::statement::Statement;
+use crate::token::{Name, Position, Token, TokenKind};
+use crate::value::Value;
+
+// A single bytecode instruction. Operands index into the pools of the chunk holding the
+// instruction, except for jumps, which name the instruction to continue at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Constant(usize),
+    Nil,
+    Pop,
+
+    // Variables are looked up by name through the same scopes the tree-walker uses.
+    // Declarations also name the position a clash with a constant is reported at
+    GetVar(usize),
+    SetVar(usize),
+    DefineVar(usize, usize),
+
+    // Variables known to be globals are found through the chunk's global pool instead,
+    // so that their slots only need looking up once. The token is reported if the global
+    // doesn't exist or is constant
+    GetGlobal(usize, usize),
+    SetGlobal(usize, usize),
+    PushScope,
+    PopScope,
+
+    // Locals no closure can capture live in stack slots instead, counted from the start of
+    // the running frame
+    GetLocal(usize),
+    SetLocal(usize),
+    PopLocals(usize),
+
+    Unary(usize),
+    Binary(usize),
+    Power(usize),
+    Print,
+
+    // Fails with the error for the placeholder at the given token
+    Placeholder(usize),
+
+    // Counts a statement as it starts running
+    Statement,
+
+    // Conditional jumps also name the position of their condition, which strict semantics
+    // report non-booleans at
+    Jump(usize),
+    JumpIfFalse(usize, usize),
+    // Short-circuit jumps, which keep the deciding operand when they jump and pop it
+    // otherwise
+    AndJump(usize, usize),
+    OrJump(usize, usize),
+
+    // Checks the callee before its arguments are evaluated, then calls it once they are
+    CheckCall(usize, usize),
+    Call(usize, usize),
+    // Swaps the receiver for the builtin it calls, followed by the receiver as its first
+    // argument
+    Method(usize, usize, usize),
+    Closure(usize),
+    Return,
+
+    List(usize),
+    MapKey(usize),
+    Map(usize),
+    Index(usize),
+    Slice(usize, usize),
+    SetIndex(usize),
+
+    // Starts an iteration of the loop whose keyword is the given token, counting it
+    Iteration(usize),
+
+    // For loops keep their iterators on a stack of their own
+    Iterate(usize),
+    IterateRange(usize),
+    Next(usize),
+    PopIterator,
+}
+
+// A function's parameters and body, turned into a function value each time its
+// declaration runs
+#[derive(Debug)]
+pub struct Prototype {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Rc<Vec<Statement>>,
+}
+
+// Compiled code for a program or function body along with the pools its instructions
+// refer to. Tokens are kept for the names and positions runtime errors are reported with
+#[derive(Debug, Default)]
+pub struct Chunk {
+    // Whether a function's arguments are passed in stack slots rather than defined in a
+    // scope
+    pub local_params: bool,
+
+    pub code: Vec<Op>,
+    pub constants: Vec<Value>,
+    pub tokens: Vec<Token>,
+    pub positions: Vec<Position>,
+    pub names: Vec<String>,
+    pub functions: Vec<Prototype>,
+
+    // Names of the globals the chunk uses, each listed once
+    pub globals: Vec<String>,
+}
+
+enum Variable {
+    Local(usize),
+    Global(usize, usize),
+    Named(usize),
+}
+
+struct Loop {
+    // Scope depth and number of locals outside the loop body, which 'break' and
+    // 'continue' unwind to
+    scope_depth: usize,
+    local_count: usize,
+    continue_target: usize,
+    breaks: Vec<usize>,
+}
+
+struct Compiler {
+    chunk: Chunk,
+    scope_depth: usize,
+    loops: Vec<Loop>,
+
+    // Names of the locals in each stack slot, and whether declarations currently go in
+    // slots. Slots are only used inside code that creates no closures, so that shadowing
+    // can be settled at compile time
+    locals: Vec<String>,
+    in_slots: bool,
+
+    // Whether names that aren't locals are globals when no scope has been entered, which
+    // holds for programs and for functions that keep their locals in slots and were
+    // declared at the top level
+    sees_globals: bool,
+}
+
+// Compiles a program, which evaluates to the value of its last statement if that is an
+// expression and to nil otherwise
+pub fn compile(statements: &[Statement]) -> Result<Chunk, LulaError> {
+    let mut compiler = Compiler::new();
+    compiler.sees_globals = true;
+
+    if let Some((last, rest)) = statements.split_last() {
+        compiler.statements(rest)?;
+
+        match last {
+            Statement::Expr(expr) => {
+                compiler.emit(Op::Statement);
+                compiler.expr(expr)?;
+            }
+            _ => {
+                compiler.statement(last)?;
+                compiler.emit(Op::Nil);
+            }
+        }
+    } else {
+        compiler.emit(Op::Nil);
+    }
+
+    compiler.emit(Op::Return);
+    Ok(compiler.chunk)
+}
+
+// Compiles a function body, which returns nil if it runs off its end. 'top_level' tells
+// whether the function's closure is the global scope
+pub fn compile_function(
+    params: &[String],
+    body: &[Statement],
+    top_level: bool,
+) -> Result<Chunk, LulaError> {
+    let mut compiler = Compiler::new();
+
+    if !creates_closures(body) {
+        compiler.chunk.local_params = true;
+        compiler.locals = params.to_vec();
+        compiler.in_slots = true;
+        compiler.sees_globals = top_level;
+    }
+
+    compiler.statements(body)?;
+
+    compiler.emit(Op::Nil);
+    compiler.emit(Op::Return);
+    Ok(compiler.chunk)
+}
+
+impl Compiler {
+    fn new() -> Compiler {
+        Compiler {
+            chunk: Chunk::default(),
+            scope_depth: 0,
+            loops: Vec::new(),
+
+            locals: Vec::new(),
+            in_slots: false,
+            sees_globals: false,
+        }
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.code.len() - 1
+    }
+
+    // Points the jump at the given instruction to the next instruction emitted
+    fn patch(&mut self, jump: usize) {
+        let target = self.chunk.code.len();
+
+        match &mut self.chunk.code[jump] {
+            Op::Jump(to)
+            | Op::JumpIfFalse(to, _)
+            | Op::AndJump(to, _)
+            | Op::OrJump(to, _)
+            | Op::Next(to) => *to = target,
+            _ => {}
+        }
+    }
+
+    fn constant(&mut self, value: Value) -> usize {
+        self.chunk.constants.push(value);
+        self.chunk.constants.len() - 1
+    }
+
+    fn token(&mut self, token: &Token) -> usize {
+        self.chunk.tokens.push(token.clone());
+        self.chunk.tokens.len() - 1
+    }
+
+    fn position(&mut self, position: Position) -> usize {
+        self.chunk.positions.push(position);
+        self.chunk.positions.len() - 1
+    }
+
+    fn name(&mut self, name: &str) -> usize {
+        self.chunk.names.push(name.to_owned());
+        self.chunk.names.len() - 1
+    }
+
+    // Finds a global in the pool if another instruction already uses it
+    fn global(&mut self, name: &str) -> usize {
+        match self.chunk.globals.iter().position(|global| global == name) {
+            Some(global) => global,
+            None => {
+                self.chunk.globals.push(name.to_owned());
+                self.chunk.globals.len() - 1
+            }
+        }
+    }
+
+    fn function(&mut self, name: &str, params: &[Name], body: &Rc<Vec<Statement>>) -> usize {
+        self.chunk.functions.push(Prototype {
+            name: name.to_owned(),
+            params: Name::identifiers(params),
+            body: Rc::clone(body),
+        });
+        self.chunk.functions.len() - 1
+    }
+
+    fn push_scope(&mut self) {
+        self.emit(Op::PushScope);
+        self.scope_depth += 1;
+    }
+
+    fn pop_scope(&mut self) {
+        self.emit(Op::PopScope);
+        self.scope_depth -= 1;
+    }
+
+    // Works out where a variable lives: in the slot of the innermost local with its name,
+    // in a global, or somewhere only a lookup by name can find
+    fn resolve(&mut self, name: &Token) -> Result<Variable, LulaError> {
+        let identifier = Expr::identifier_name(name)?;
+
+        if let Some(slot) = self.locals.iter().rposition(|local| local == identifier) {
+            return Ok(Variable::Local(slot));
+        }
+
+        if self.sees_globals && self.scope_depth == 0 {
+            let global = self.global(identifier);
+            return Ok(Variable::Global(global, self.token(name)));
+        }
+
+        Ok(Variable::Named(self.token(name)))
+    }
+
+    // Drops the locals declared since there were the given number of them
+    fn pop_locals(&mut self, local_count: usize) {
+        let count = self.locals.len() - local_count;
+
+        if count > 0 {
+            self.emit(Op::PopLocals(count));
+            self.locals.truncate(local_count);
+        }
+    }
+
+    // Runs 'body' with declarations going into stack slots, dropping them afterwards
+    fn with_slots<F>(&mut self, body: F) -> Result<(), LulaError>
+    where
+        F: FnOnce(&mut Compiler) -> Result<(), LulaError>,
+    {
+        let in_slots = std::mem::replace(&mut self.in_slots, true);
+        let local_count = self.locals.len();
+
+        body(self)?;
+
+        self.pop_locals(local_count);
+        self.in_slots = in_slots;
+
+        Ok(())
+    }
+
+    // A block's scope can be left out if nothing is declared directly inside it, since
+    // lookups would only ever pass through it
+    fn needs_scope(block: &[Statement]) -> bool {
+        block
+            .iter()
+            .any(|statement| matches!(statement, Statement::VarDecl(..) | Statement::Func(..)))
+    }
+
+    fn scoped(&mut self, block: &[Statement]) -> Result<(), LulaError> {
+        if self.in_slots || !creates_closures(block) {
+            return self.with_slots(|compiler| compiler.statements(block));
+        }
+
+        if !Compiler::needs_scope(block) {
+            return self.statements(block);
+        }
+
+        self.push_scope();
+        self.statements(block)?;
+        self.pop_scope();
+
+        Ok(())
+    }
+
+    // Leaves every scope and local entered since the innermost loop began
+    fn unwind_loop(&mut self) {
+        let (loop_depth, local_count) = self
+            .loops
+            .last()
+            .map_or((0, 0), |lp| (lp.scope_depth, lp.local_count));
+
+        if self.locals.len() > local_count {
+            self.emit(Op::PopLocals(self.locals.len() - local_count));
+        }
+
+        for _ in loop_depth..self.scope_depth {
+            self.emit(Op::PopScope);
+        }
+    }
+
+    // Compiles a loop body, which gets a fresh scope each iteration. For loops declare
+    // their loop variable in it from the value left by 'Next'
+    fn loop_body(
+        &mut self,
+        keyword: &Token,
+        continue_target: usize,
+        body: &[Statement],
+        variable: Option<&str>,
+    ) -> Result<Vec<usize>, LulaError> {
+        let position = keyword.position;
+        let keyword = self.token(keyword);
+        self.emit(Op::Iteration(keyword));
+
+        self.loops.push(Loop {
+            scope_depth: self.scope_depth,
+            local_count: self.locals.len(),
+            continue_target,
+            breaks: Vec::new(),
+        });
+
+        if self.in_slots || !creates_closures(body) {
+            self.with_slots(|compiler| {
+                compiler.locals.extend(variable.map(str::to_owned));
+                compiler.statements(body)
+            })?;
+        } else if let Some(variable) = variable {
+            let variable = self.name(variable);
+            let position = self.position(position);
+
+            self.push_scope();
+            self.emit(Op::DefineVar(variable, position));
+            self.statements(body)?;
+            self.pop_scope();
+        } else {
+            self.scoped(body)?;
+        }
+
+        self.emit(Op::Jump(continue_target));
+
+        Ok(self.loops.pop().map_or_else(Vec::new, |lp| lp.breaks))
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> Result<(), LulaError> {
+        for statement in statements {
+            self.statement(statement)?;
+        }
+
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), LulaError> {
+        self.emit(Op::Statement);
+
+        match statement {
+            Statement::Print(expr) => {
+                self.expr(expr)?;
+                self.emit(Op::Print);
+            }
+
+            Statement::VarDecl(name, initializer) => {
+                match initializer {
+                    Some(expr) => self.expr(expr)?,
+                    None => {
+                        self.emit(Op::Nil);
+                    }
+                }
+
+                // A local's value is simply left in its slot
+                if self.in_slots {
+                    self.locals.push(name.identifier.clone());
+                } else {
+                    let position = self.position(name.position);
+                    let name = self.name(&name.identifier);
+                    self.emit(Op::DefineVar(name, position));
+                }
+            }
+
+            Statement::Expr(expr) => {
+                self.expr(expr)?;
+                self.emit(Op::Pop);
+            }
+
+            Statement::If(keyword, condition, then_block, elif_blocks, else_block) => {
+                let branches = std::iter::once((keyword, condition, then_block)).chain(
+                    elif_blocks
+                        .iter()
+                        .map(|(keyword, cond, block)| (keyword, cond, block)),
+                );
+                let mut exits = Vec::new();
+
+                for (keyword, cond, block) in branches {
+                    self.expr(cond)?;
+                    let position = self.position(cond.position_or(keyword.position));
+                    let skip = self.emit(Op::JumpIfFalse(0, position));
+
+                    self.scoped(block)?;
+                    exits.push(self.emit(Op::Jump(0)));
+                    self.patch(skip);
+                }
+
+                if let Some(block) = else_block {
+                    self.scoped(block)?;
+                }
+
+                for exit in exits {
+                    self.patch(exit);
+                }
+            }
+
+            Statement::Block(block) => self.scoped(block)?,
+
+            Statement::Loop(keyword, body) => {
+                let start = self.chunk.code.len();
+                let breaks = self.loop_body(keyword, start, body, None)?;
+
+                for jump in breaks {
+                    self.patch(jump);
+                }
+            }
+
+            Statement::While(keyword, condition, body) => {
+                let start = self.chunk.code.len();
+                self.expr(condition)?;
+                let position = self.position(condition.position_or(keyword.position));
+                let exit = self.emit(Op::JumpIfFalse(0, position));
+
+                let breaks = self.loop_body(keyword, start, body, None)?;
+
+                self.patch(exit);
+                for jump in breaks {
+                    self.patch(jump);
+                }
+            }
+
+            Statement::For(keyword, name, iterable, body) => {
+                match iterable {
+                    Expr::Range(start, op, end) => {
+                        self.expr(start)?;
+                        self.expr(end)?;
+
+                        let op = self.token(op);
+                        self.emit(Op::IterateRange(op));
+                    }
+
+                    _ => {
+                        self.expr(iterable)?;
+
+                        let name = self.token(name);
+                        self.emit(Op::Iterate(name));
+                    }
+                }
+
+                let start = self.emit(Op::Next(0));
+                let variable = Expr::identifier_name(name)?;
+                let breaks = self.loop_body(keyword, start, body, Some(variable))?;
+
+                // Breaking out of the loop still drops its iterator
+                self.patch(start);
+                for jump in breaks {
+                    self.patch(jump);
+                }
+                self.emit(Op::PopIterator);
+            }
+
+            Statement::Break => {
+                self.unwind_loop();
+                let jump = self.emit(Op::Jump(0));
+
+                if let Some(lp) = self.loops.last_mut() {
+                    lp.breaks.push(jump);
+                }
+            }
+
+            Statement::Continue => {
+                self.unwind_loop();
+
+                let target = self.loops.last().map_or(0, |lp| lp.continue_target);
+                self.emit(Op::Jump(target));
+            }
+
+            Statement::Func(name, params, body) => {
+                let func = self.function(&name.identifier, params, body);
+                self.emit(Op::Closure(func));
+
+                let position = self.position(name.position);
+                let name = self.name(&name.identifier);
+                self.emit(Op::DefineVar(name, position));
+            }
+
+            Statement::Return(value) => {
+                match value {
+                    Some(expr) => self.expr(expr)?,
+                    None => {
+                        self.emit(Op::Nil);
+                    }
+                }
+
+                self.emit(Op::Return);
+            }
+
+            Statement::Error(tokens) => return Err(Statement::unparsed(tokens)),
+        }
+
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<(), LulaError> {
+        match expr {
+            Expr::Literal(lit) => {
+                let constant = self.constant(Expr::evaluate_literal(lit)?);
+                self.emit(Op::Constant(constant));
+            }
+
+            Expr::Variable(name) => {
+                let op = match self.resolve(name)? {
+                    Variable::Local(slot) => Op::GetLocal(slot),
+                    Variable::Global(global, name) => Op::GetGlobal(global, name),
+                    Variable::Named(name) => Op::GetVar(name),
+                };
+                self.emit(op);
+            }
+
+            Expr::Assign(name, value) => {
+                self.expr(value)?;
+
+                let op = match self.resolve(name)? {
+                    Variable::Local(slot) => Op::SetLocal(slot),
+                    Variable::Global(global, name) => Op::SetGlobal(global, name),
+                    Variable::Named(name) => Op::SetVar(name),
+                };
+                self.emit(op);
+            }
+
+            Expr::Unary(op, operand) => {
+                self.expr(operand)?;
+
+                let op = self.token(op);
+                self.emit(Op::Unary(op));
+            }
+
+            Expr::Binary(lhs, op, rhs) => {
+                self.expr(lhs)?;
+                self.expr(rhs)?;
+
+                let is_power = op.kind == TokenKind::StarStar;
+                let op = self.token(op);
+
+                if is_power {
+                    self.emit(Op::Power(op));
+                } else {
+                    self.emit(Op::Binary(op));
+                }
+            }
+
+            Expr::Logical(lhs, op, rhs) => {
+                self.expr(lhs)?;
+
+                let position = self.position(lhs.position_or(op.position));
+                let jump = match op.kind {
+                    TokenKind::And => Op::AndJump(0, position),
+                    TokenKind::Or => Op::OrJump(0, position),
+                    _ => {
+                        return Err(LulaError::internal(
+                            Message::new("unexpected-logical").debug_arg(&op.kind),
+                            op.position,
+                        ))
+                    }
+                };
+                let jump = self.emit(jump);
+
+                self.expr(rhs)?;
+                self.patch(jump);
+            }
+
+            Expr::Conditional(condition, question, then_value, else_value) => {
+                self.expr(condition)?;
+                let position = self.position(condition.position_or(question.position));
+                let else_jump = self.emit(Op::JumpIfFalse(0, position));
+
+                self.expr(then_value)?;
+                let end_jump = self.emit(Op::Jump(0));
+
+                self.patch(else_jump);
+                self.expr(else_value)?;
+                self.patch(end_jump);
+            }
+
+            Expr::Grouping(_, inner, _) => self.expr(inner)?,
+
+            Expr::Call(callee, paren, args) => {
+                self.expr(callee)?;
+
+                let paren = self.token(paren);
+                self.emit(Op::CheckCall(paren, args.len()));
+
+                for arg in args {
+                    self.expr(arg)?;
+                }
+
+                self.emit(Op::Call(paren, args.len()));
+            }
+
+            Expr::Method(receiver, name, paren, args) => {
+                self.expr(receiver)?;
+
+                let name = self.token(name);
+                let paren = self.token(paren);
+                self.emit(Op::Method(name, paren, args.len()));
+
+                for arg in args {
+                    self.expr(arg)?;
+                }
+
+                self.emit(Op::Call(paren, args.len() + 1));
+            }
+
+            Expr::List(items) => {
+                for item in items {
+                    self.expr(item)?;
+                }
+
+                self.emit(Op::List(items.len()));
+            }
+
+            Expr::Map(entries) => {
+                for (key, colon, value) in entries {
+                    self.expr(key)?;
+
+                    let colon = self.token(colon);
+                    self.emit(Op::MapKey(colon));
+
+                    self.expr(value)?;
+                }
+
+                self.emit(Op::Map(entries.len()));
+            }
+
+            Expr::Index(target, bracket, index) => {
+                self.expr(target)?;
+                let bracket = self.token(bracket);
+
+                match &**index {
+                    Expr::Range(start, op, end) => {
+                        self.expr(start)?;
+                        self.expr(end)?;
+
+                        let op = self.token(op);
+                        self.emit(Op::Slice(bracket, op));
+                    }
+
+                    _ => {
+                        self.expr(index)?;
+                        self.emit(Op::Index(bracket));
+                    }
+                }
+            }
+
+            Expr::IndexAssign(target, bracket, index, value) => {
+                self.expr(target)?;
+                self.expr(index)?;
+                self.expr(value)?;
+
+                let bracket = self.token(bracket);
+                self.emit(Op::SetIndex(bracket));
+            }
+
+            Expr::Func(params, body) => {
+                let func = self.function("anonymous", params, body);
+                self.emit(Op::Closure(func));
+            }
+
+            // Ranges are only produced by the parser as the iterable of a for loop
+            Expr::Range(_, op, _) => {
+                return Err(LulaError::internal(
+                    Message::new("range-outside-loop"),
+                    op.position,
+                ))
+            }
+
+            Expr::Error(tokens) => return Err(Statement::unparsed(tokens)),
+
+            Expr::Placeholder(tok) => {
+                let tok = self.token(tok);
+                self.emit(Op::Placeholder(tok));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Whether any function is created inside the statements, which could then capture the
+// scopes they run in
+fn creates_closures(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::Func(..) => true,
+        Statement::Break | Statement::Continue | Statement::Error(..) => false,
+
+        Statement::Print(expr) | Statement::Expr(expr) => expr_creates_closures(expr),
+        Statement::VarDecl(_, value) | Statement::Return(value) => {
+            value.as_ref().is_some_and(expr_creates_closures)
+        }
+
+        Statement::If(_, condition, then_block, elif_blocks, else_block) => {
+            expr_creates_closures(condition)
+                || creates_closures(then_block)
+                || elif_blocks
+                    .iter()
+                    .any(|(_, cond, block)| expr_creates_closures(cond) || creates_closures(block))
+                || else_block.as_deref().is_some_and(creates_closures)
+        }
+
+        Statement::Block(block) | Statement::Loop(_, block) => creates_closures(block),
+        Statement::While(_, expr, block) | Statement::For(_, _, expr, block) => {
+            expr_creates_closures(expr) || creates_closures(block)
+        }
+    })
+}
+
+fn expr_creates_closures(expr: &Expr) -> bool {
+    match expr {
+        Expr::Func(..) => true,
+        Expr::Literal(..) | Expr::Variable(..) | Expr::Error(..) | Expr::Placeholder(..) => false,
+
+        Expr::Assign(_, inner) | Expr::Unary(_, inner) | Expr::Grouping(_, inner, _) => {
+            expr_creates_closures(inner)
+        }
+
+        Expr::Binary(lhs, _, rhs)
+        | Expr::Logical(lhs, _, rhs)
+        | Expr::Range(lhs, _, rhs)
+        | Expr::Index(lhs, _, rhs) => expr_creates_closures(lhs) || expr_creates_closures(rhs),
+
+        Expr::Call(callee, _, args) | Expr::Method(callee, _, _, args) => {
+            expr_creates_closures(callee) || args.iter().any(expr_creates_closures)
+        }
+
+        Expr::List(items) => items.iter().any(expr_creates_closures),
+        Expr::Map(entries) => entries
+            .iter()
+            .any(|(key, _, value)| expr_creates_closures(key) || expr_creates_closures(value)),
+
+        Expr::IndexAssign(target, _, index, value) => {
+            expr_creates_closures(target)
+                || expr_creates_closures(index)
+                || expr_creates_closures(value)
+        }
+        Expr::Conditional(condition, _, then_value, else_value) => {
+            expr_creates_closures(condition)
+                || expr_creates_closures(then_value)
+                || expr_creates_closures(else_value)
+        }
+    }
+}
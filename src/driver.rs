@@ -0,0 +1,578 @@
+use std::{
+    cell::Cell,
+    fs,
+    fs::File,
+    io::Read,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use lula::{
+    dump::program_node,
+    hash::hash_program,
+    interpreter::Backend,
+    json::quote,
+    lexer::source_tokens,
+    messages::language,
+    optimize::optimize,
+    semantics::Semantics,
+    spell::{check_spelling, Wordlist},
+    statement::Statement,
+    steps::DEFAULT_STEP_WARNING,
+    value::Value,
+    Interpreter, LulaError, Parser,
+};
+
+#[cfg(feature = "tooling")]
+use lula::{
+    graph::{call_graph, file_graph},
+    metrics::{measure, Thresholds},
+    refactor::{extract_function, rename, RefactorError},
+    resolver::resolve,
+};
+
+// Name of the file holding a project's own words for the spelling lint, looked for in the
+// checked file's directory and each directory above it
+const PROJECT_WORDLIST: &str = "lula-words.txt";
+
+// How often watch mode checks whether the source file has changed
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+// What a command has reported and run, for the summary shown when it finishes
+#[derive(Debug, Clone, Copy, Default)]
+struct Summary {
+    errors: usize,
+    warnings: usize,
+
+    // How many statements the program ran and how long it took, if the command ran one
+    run: Option<(u64, Duration)>,
+}
+
+thread_local! {
+    static SUMMARY: Cell<Summary> = Cell::new(Summary::default());
+}
+
+fn record<F: FnOnce(&mut Summary)>(update: F) {
+    SUMMARY.with(|summary| {
+        let mut current = summary.get();
+        update(&mut current);
+        summary.set(current);
+    });
+}
+
+fn plural(count: u64, noun: &str) -> String {
+    if count == 1 {
+        format!("{} {}", count, noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+// Prints a line summing up the diagnostics a command reported, and the program it ran if
+// there was one
+pub fn print_summary() {
+    let summary = SUMMARY.with(Cell::get);
+    let mut line = format!(
+        "finished: {}, {}",
+        plural(summary.errors as u64, "error"),
+        plural(summary.warnings as u64, "warning")
+    );
+
+    if let Some((statements, elapsed)) = summary.run {
+        line += &format!(
+            ", ran {} in {:.1}ms",
+            plural(statements, "statement"),
+            elapsed.as_secs_f64() * 1000.0
+        );
+    }
+
+    eprintln!("{}", line);
+}
+
+// Exit codes follow the BSD sysexits convention
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunError {
+    // The interpreter was invoked incorrectly
+    Usage,
+    // The source file could not be opened or read
+    Io,
+    // The source failed to lex or parse
+    Syntax,
+    // An error occurred while running the program
+    Runtime,
+}
+
+impl RunError {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            RunError::Usage => 64,
+            RunError::Syntax => 65,
+            RunError::Runtime => 70,
+            RunError::Io => 74,
+        }
+    }
+}
+
+// One of the files making up a program
+#[derive(Clone)]
+pub struct SourceFile {
+    pub path: String,
+    pub source: String,
+}
+
+fn read_source(in_file_path: &str) -> Result<String, RunError> {
+    // Check if input file uses the '.lla' file extension
+    if !in_file_path.ends_with(".lla") {
+        eprintln!("Fatal error: Input file does not use the '.lla' file extension");
+        return Err(RunError::Usage);
+    }
+
+    // Attempt to open input file
+    let mut in_file = match File::open(in_file_path) {
+        Ok(file) => file,
+        Err(..) => {
+            eprintln!(
+                "Fatal error: Failed to open file '{}'\n~ are you sure this file exists?",
+                in_file_path
+            );
+            return Err(RunError::Io);
+        }
+    };
+
+    // Read file contents into string
+    let mut in_file_contents = String::new();
+
+    if in_file.read_to_string(&mut in_file_contents).is_err() {
+        eprintln!("Fatal error: Failed to read file contents into string");
+        return Err(RunError::Io);
+    }
+
+    Ok(in_file_contents)
+}
+
+fn read_file(in_file_path: &str) -> Result<SourceFile, RunError> {
+    Ok(SourceFile {
+        path: in_file_path.to_owned(),
+        source: read_source(in_file_path)?,
+    })
+}
+
+// How a program should be run
+#[derive(Debug, Clone, Copy)]
+pub struct RunOptions {
+    pub backend: Backend,
+
+    // Whether constant subexpressions are folded before the program runs
+    pub optimize: bool,
+
+    // Loop iterations after which the program is warned it may be stuck, if any
+    pub step_warning: Option<u64>,
+
+    // What values can be used as conditions
+    pub semantics: Semantics,
+
+    // Whether to leave out the summary shown when a command finishes
+    pub quiet: bool,
+
+    // Prints the parsed program in this format instead of running it, if set
+    pub dump_ast: Option<AstFormat>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            backend: Backend::Tree,
+            optimize: true,
+            step_warning: Some(DEFAULT_STEP_WARNING),
+            semantics: Semantics::Lenient,
+            quiet: false,
+            dump_ast: None,
+        }
+    }
+}
+
+// How '--dump-ast' prints a program's syntax tree
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AstFormat {
+    // One node per line, indented by depth
+    Tree,
+    Sexp,
+    Json,
+}
+
+// Runs several files as a single program, in the order given
+pub fn run_files(
+    in_file_paths: &[String],
+    script_args: &[String],
+    options: RunOptions,
+) -> Result<(), RunError> {
+    run_program(&read_files(in_file_paths)?, script_args, options)
+}
+
+fn read_files(in_file_paths: &[String]) -> Result<Vec<SourceFile>, RunError> {
+    in_file_paths.iter().map(|path| read_file(path)).collect()
+}
+
+// Lexes and parses files into a single program without running it, returning every error
+// found
+fn parse_files(files: &[SourceFile]) -> Result<Vec<Statement>, Vec<LulaError>> {
+    let sources = files.iter().map(|file| file.source.clone()).collect();
+
+    let mut parser = Parser::new(source_tokens(sources));
+    parser.collect_statements()
+}
+
+// Parses files, rendering any errors found
+fn parse_or_report(files: &[SourceFile]) -> Result<Vec<Statement>, RunError> {
+    parse_files(files).map_err(|errors| {
+        report_errors(files, &errors);
+        RunError::Syntax
+    })
+}
+
+// Reports errors in a file without running it, along with likely typos in its strings and
+// comments if 'spell' is set. Spelling warnings don't make the check fail
+pub fn check_file(in_file_path: &str, spell: bool) -> Result<(), RunError> {
+    let file = read_file(in_file_path)?;
+    parse_or_report(std::slice::from_ref(&file))?;
+
+    if spell {
+        let warnings = spelling_warnings(&file, &project_wordlist(in_file_path));
+        report_errors(std::slice::from_ref(&file), &warnings);
+    }
+
+    Ok(())
+}
+
+// The built-in wordlist plus the nearest project wordlist above a source file, if any
+fn project_wordlist(in_file_path: &str) -> Wordlist {
+    let mut wordlist = Wordlist::default();
+    let path = Path::new(in_file_path);
+
+    let project_words = path
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join(PROJECT_WORDLIST))
+        .find_map(|path| fs::read_to_string(path).ok());
+
+    if let Some(words) = project_words {
+        wordlist.add_words(&words);
+    }
+
+    wordlist
+}
+
+// Spelling warnings for a file that is known to lex
+fn spelling_warnings(file: &SourceFile, wordlist: &Wordlist) -> Vec<LulaError> {
+    check_spelling(&file.source, 0, wordlist).unwrap_or_default()
+}
+
+// Applies a refactoring to a file's source, then writes the result back to the file
+#[cfg(feature = "tooling")]
+fn refactor_file<F>(in_file_path: &str, refactoring: F) -> Result<(), RunError>
+where
+    F: FnOnce(&str) -> Result<String, RefactorError>,
+{
+    let file = read_file(in_file_path)?;
+
+    let refactored = match refactoring(&file.source) {
+        Ok(refactored) => refactored,
+        Err(RefactorError::Syntax(errors)) => {
+            report_errors(&[file], &errors);
+            return Err(RunError::Syntax);
+        }
+        Err(RefactorError::Rejected(reason)) => {
+            eprintln!("Fatal error: {}", reason);
+            return Err(RunError::Usage);
+        }
+    };
+
+    fs::write(in_file_path, refactored).map_err(|_| {
+        eprintln!("Fatal error: Failed to write file '{}'", in_file_path);
+        RunError::Io
+    })
+}
+
+#[cfg(feature = "tooling")]
+pub fn rename_in_file(
+    in_file_path: &str,
+    line: usize,
+    column: usize,
+    new_name: &str,
+) -> Result<(), RunError> {
+    refactor_file(in_file_path, |source| {
+        rename(source, line, column, new_name)
+    })
+}
+
+#[cfg(feature = "tooling")]
+pub fn extract_in_file(
+    in_file_path: &str,
+    first_line: usize,
+    last_line: usize,
+    name: &str,
+) -> Result<(), RunError> {
+    refactor_file(in_file_path, |source| {
+        extract_function(source, first_line, last_line, name)
+    })
+}
+
+#[cfg(feature = "tooling")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphKind {
+    // Which functions call which
+    Calls,
+
+    // Which files use variables declared in which others
+    Files,
+}
+
+// Prints a graph of a program's structure, as DOT for Graphviz or as JSON
+#[cfg(feature = "tooling")]
+pub fn graph_files(in_file_paths: &[String], kind: GraphKind, dot: bool) -> Result<(), RunError> {
+    let files = read_files(in_file_paths)?;
+    let statements = parse_or_report(&files)?;
+    let resolution = resolve(&statements);
+
+    let (graph, name) = match kind {
+        GraphKind::Calls => (call_graph(&statements, &resolution), "calls"),
+        GraphKind::Files => (file_graph(&resolution, in_file_paths), "files"),
+    };
+
+    if dot {
+        print!("{}", graph.to_dot(name));
+    } else {
+        print!("{}", graph.to_json());
+    }
+
+    Ok(())
+}
+
+// Prints complexity, length and nesting depth for each function in a file, then warns about
+// anything over the thresholds and any duplicated blocks
+#[cfg(feature = "tooling")]
+pub fn metrics_file(in_file_path: &str, thresholds: &Thresholds) -> Result<(), RunError> {
+    let file = read_file(in_file_path)?;
+    let statements = parse_or_report(std::slice::from_ref(&file))?;
+    let metrics = measure(&statements, thresholds.duplicate_statements);
+
+    let width = metrics
+        .functions
+        .iter()
+        .map(|function| function.name.chars().count())
+        .chain(Some("function".len()))
+        .max()
+        .unwrap_or(0);
+
+    println!(
+        "{:<width$}  {:>5}  {:>10}  {:>10}  {:>7}",
+        "function",
+        "line",
+        "complexity",
+        "statements",
+        "nesting",
+        width = width
+    );
+
+    for function in &metrics.functions {
+        println!(
+            "{:<width$}  {:>5}  {:>10}  {:>10}  {:>7}",
+            function.name,
+            function.position.0 + 1,
+            function.complexity,
+            function.statements,
+            function.nesting,
+            width = width
+        );
+    }
+
+    for (position, warning) in metrics.warnings(thresholds) {
+        eprintln!(
+            "Warning in file '{}', {}:\n    {}.",
+            in_file_path, position, warning
+        );
+        record(|summary| summary.warnings += 1);
+    }
+
+    Ok(())
+}
+
+// Prints the explanation of an error code, such as 'E0302', or of one of its messages, such
+// as 'E0302.undeclared-assignment'
+pub fn explain(key: &str) -> Result<(), RunError> {
+    match language().explanation(key) {
+        Some(explanation) => {
+            println!("{}", explanation);
+            Ok(())
+        }
+
+        None => {
+            eprintln!("Fatal error: No explanation for '{}'", key);
+            Err(RunError::Usage)
+        }
+    }
+}
+
+// Prints the structural hash of a file, which only changes when its syntax tree does
+pub fn hash_file(in_file_path: &str) -> Result<(), RunError> {
+    let statements = parse_or_report(&[read_file(in_file_path)?])?;
+
+    println!("{:016x}", hash_program(&statements));
+    Ok(())
+}
+
+// Rechecks a source file whenever it changes, rewriting a 'diagnostics.json' file beside it
+// each time so that editors without language server support can poll it. Spelling warnings
+// are included if 'spell' is set. Runs until the process is killed
+pub fn watch_file(in_file_path: &str, spell: bool) -> Result<(), RunError> {
+    let out_path = Path::new(in_file_path).with_file_name("diagnostics.json");
+    let mut file = read_file(in_file_path)?;
+    let mut last_modified = None;
+    let mut last_hash = None;
+
+    loop {
+        // Files being saved can briefly disappear, so skip rather than fail on errors here
+        let modified = fs::metadata(in_file_path).and_then(|m| m.modified()).ok();
+
+        if modified.is_some() && modified != last_modified {
+            if let Ok(contents) = fs::read_to_string(in_file_path) {
+                file.source = contents;
+            }
+
+            let result = parse_files(std::slice::from_ref(&file));
+            let hash = result
+                .as_ref()
+                .ok()
+                .map(|statements| hash_program(statements));
+
+            // A clean program whose structure is unchanged, say after an edit to a comment,
+            // is still clean, so there is nothing new to write. Comments and strings are
+            // spell checked though, so any edit may change the warnings
+            if spell || hash.is_none() || hash != last_hash {
+                let mut errors = result.err().unwrap_or_default();
+                let error_count = errors.len();
+
+                if spell && error_count == 0 {
+                    errors = spelling_warnings(&file, &project_wordlist(in_file_path));
+                }
+
+                write_diagnostics(in_file_path, &out_path, &errors)?;
+
+                eprintln!(
+                    "Checked '{}': {} error(s), {} warning(s)",
+                    in_file_path,
+                    error_count,
+                    errors.len() - error_count
+                );
+            }
+
+            last_modified = modified;
+            last_hash = hash;
+        }
+
+        thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+fn write_diagnostics(
+    in_file_path: &str,
+    out_path: &Path,
+    errors: &[LulaError],
+) -> Result<(), RunError> {
+    let diagnostics: Vec<String> = errors.iter().map(LulaError::to_json).collect();
+    let json = format!(
+        "{{\"file\": {}, \"diagnostics\": [{}]}}\n",
+        quote(in_file_path),
+        diagnostics.join(", ")
+    );
+
+    // Write to a temporary file first so that pollers never see a partially written file
+    let tmp_path = out_path.with_extension("json.tmp");
+
+    if fs::write(&tmp_path, json)
+        .and_then(|_| fs::rename(&tmp_path, out_path))
+        .is_err()
+    {
+        eprintln!(
+            "Fatal error: Failed to write diagnostics to '{}'",
+            out_path.display()
+        );
+        return Err(RunError::Io);
+    }
+
+    Ok(())
+}
+
+fn report_errors(files: &[SourceFile], errors: &[LulaError]) {
+    for err in errors {
+        report_error(files, err);
+    }
+}
+
+fn report_error(files: &[SourceFile], err: &LulaError) {
+    // Render the error against the file its position is in
+    let source_id = err.diagnostic().position.2;
+
+    if let Some(file) = files.get(source_id) {
+        err.display(&file.path, &file.source);
+    }
+
+    record(|summary| match err.severity() {
+        "warning" => summary.warnings += 1,
+        _ => summary.errors += 1,
+    });
+}
+
+pub fn run_program(
+    files: &[SourceFile],
+    script_args: &[String],
+    options: RunOptions,
+) -> Result<(), RunError> {
+    // Lex and parse file contents into a vector of statements
+    let statements = parse_or_report(files)?;
+
+    // The tree is dumped as parsed, so that folding can't hide what the parser produced
+    if let Some(format) = options.dump_ast {
+        let node = program_node(&statements);
+
+        match format {
+            AstFormat::Tree => print!("{}", node.to_indented()),
+            AstFormat::Sexp => println!("{}", node.to_sexp()),
+            AstFormat::Json => println!("{}", node.to_json()),
+        }
+
+        return Ok(());
+    }
+
+    let statements = if options.optimize {
+        optimize(statements)
+    } else {
+        statements
+    };
+
+    // Interpret statements sequentially, stopping at the first runtime error
+    let mut interpreter = Interpreter::new();
+    interpreter.set_backend(options.backend);
+    interpreter.set_step_warning(options.step_warning);
+    interpreter.set_semantics(options.semantics);
+
+    // Warnings are shown as soon as they are raised, since the program may never finish
+    let warning_files = files.to_vec();
+    interpreter.set_warning_handler(move |warning| report_error(&warning_files, warning));
+
+    let script_args = script_args.iter().cloned().map(Value::String).collect();
+    interpreter.define("args", Value::list(script_args));
+
+    let start = Instant::now();
+    let result = interpreter.run_statements(&statements);
+    let run = (interpreter.statements_run(), start.elapsed());
+    record(|summary| summary.run = Some(run));
+
+    result.map_err(|err| {
+        report_error(files, &err);
+        RunError::Runtime
+    })?;
+
+    Ok(())
+}
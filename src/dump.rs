@@ -0,0 +1,200 @@
+use crate::expr::Expr;
+use crate::json::quote;
+use crate::statement::Statement;
+use crate::token::{Literal, Name, Token, TokenKind};
+
+// A syntax tree stripped down for display, with each node a label over its children.
+// Operators are labelled with their token kinds, as they are in diagnostics, literals with
+// their values and variables with their names
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub label: String,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    fn new(label: &str, children: Vec<Node>) -> Node {
+        Node {
+            label: label.to_owned(),
+            children,
+        }
+    }
+
+    fn leaf(label: &str) -> Node {
+        Node::new(label, Vec::new())
+    }
+
+    // One node per line, indented by two spaces for each level below the root
+    pub fn to_indented(&self) -> String {
+        let mut text = String::new();
+        self.write_indented(0, &mut text);
+        text
+    }
+
+    fn write_indented(&self, depth: usize, text: &mut String) {
+        text.push_str(&format!("{}{}\n", "  ".repeat(depth), self.label));
+
+        for child in &self.children {
+            child.write_indented(depth + 1, text);
+        }
+    }
+
+    // Leaves are written bare, and any other node as its label and children in parentheses
+    pub fn to_sexp(&self) -> String {
+        if self.children.is_empty() {
+            return self.label.clone();
+        }
+
+        let children: Vec<String> = self.children.iter().map(Node::to_sexp).collect();
+        format!("({} {})", self.label, children.join(" "))
+    }
+
+    pub fn to_json(&self) -> String {
+        let children: Vec<String> = self.children.iter().map(Node::to_json).collect();
+
+        format!(
+            "{{\"label\": {}, \"children\": [{}]}}",
+            quote(&self.label),
+            children.join(", ")
+        )
+    }
+}
+
+// Builds the display tree of a program, rooted at a node labelled 'program'
+pub fn program_node(statements: &[Statement]) -> Node {
+    block_node("program", statements)
+}
+
+fn block_node(label: &str, block: &[Statement]) -> Node {
+    Node::new(label, block.iter().map(statement_node).collect())
+}
+
+fn params_node(params: &[Name]) -> Node {
+    Node::new(
+        "params",
+        params
+            .iter()
+            .map(|param| Node::leaf(&param.identifier))
+            .collect(),
+    )
+}
+
+fn statement_node(statement: &Statement) -> Node {
+    match statement {
+        Statement::Print(expr) => Node::new("print", vec![expr_node(expr)]),
+        Statement::VarDecl(name, initializer) => {
+            let mut children = vec![Node::leaf(&name.identifier)];
+            children.extend(initializer.iter().map(expr_node));
+            Node::new("let", children)
+        }
+        Statement::Expr(expr) => expr_node(expr),
+
+        Statement::If(_, condition, then_block, elif_blocks, else_block) => {
+            let mut children = vec![expr_node(condition), block_node("then", then_block)];
+
+            for (_, condition, block) in elif_blocks {
+                children.push(Node::new(
+                    "elif",
+                    vec![expr_node(condition), block_node("then", block)],
+                ));
+            }
+
+            children.extend(else_block.iter().map(|block| block_node("else", block)));
+            Node::new("if", children)
+        }
+        Statement::Block(block) => block_node("block", block),
+
+        Statement::Loop(_, body) => Node::new("loop", vec![block_node("do", body)]),
+        Statement::While(_, condition, body) => {
+            Node::new("while", vec![expr_node(condition), block_node("do", body)])
+        }
+        Statement::For(_, name, iterable, body) => Node::new(
+            "for",
+            vec![
+                token_node(name),
+                expr_node(iterable),
+                block_node("do", body),
+            ],
+        ),
+        Statement::Break => Node::leaf("break"),
+        Statement::Continue => Node::leaf("continue"),
+
+        Statement::Func(name, params, body) => Node::new(
+            "func",
+            vec![
+                Node::leaf(&name.identifier),
+                params_node(params),
+                block_node("body", body),
+            ],
+        ),
+        Statement::Return(value) => Node::new("return", value.iter().map(expr_node).collect()),
+
+        Statement::Error(..) => Node::leaf("error"),
+    }
+}
+
+// Literal tokens show their value, with strings quoted so that their contents can't be
+// mistaken for the rest of the tree, and other tokens their kind
+fn token_node(token: &Token) -> Node {
+    match &token.kind {
+        TokenKind::Literal(Literal::String(val)) => Node::leaf(&quote(val)),
+        TokenKind::Literal(lit) => Node::leaf(&lit.to_string()),
+        kind => Node::leaf(&format!("{:?}", kind)),
+    }
+}
+
+fn expr_node(expr: &Expr) -> Node {
+    match expr {
+        Expr::Literal(token) | Expr::Variable(token) => token_node(token),
+        Expr::Assign(name, value) => Node::new("assign", vec![token_node(name), expr_node(value)]),
+        Expr::Unary(op, operand) => Node::new(&format!("{:?}", op.kind), vec![expr_node(operand)]),
+        Expr::Binary(lhs, op, rhs) | Expr::Logical(lhs, op, rhs) => Node::new(
+            &format!("{:?}", op.kind),
+            vec![expr_node(lhs), expr_node(rhs)],
+        ),
+        Expr::Conditional(condition, _, then_value, else_value) => Node::new(
+            "conditional",
+            vec![
+                expr_node(condition),
+                expr_node(then_value),
+                expr_node(else_value),
+            ],
+        ),
+        Expr::Grouping(_, inner, _) => Node::new("group", vec![expr_node(inner)]),
+        Expr::Range(start, _, end) => Node::new("range", vec![expr_node(start), expr_node(end)]),
+
+        Expr::Call(callee, _, args) => {
+            let mut children = vec![expr_node(callee)];
+            children.extend(args.iter().map(expr_node));
+            Node::new("call", children)
+        }
+        Expr::Method(receiver, name, _, args) => {
+            let mut children = vec![expr_node(receiver), token_node(name)];
+            children.extend(args.iter().map(expr_node));
+            Node::new("method", children)
+        }
+
+        Expr::List(items) => Node::new("list", items.iter().map(expr_node).collect()),
+        Expr::Map(entries) => Node::new(
+            "map",
+            entries
+                .iter()
+                .map(|(key, _, value)| Node::new("entry", vec![expr_node(key), expr_node(value)]))
+                .collect(),
+        ),
+        Expr::Index(target, _, index) => {
+            Node::new("index", vec![expr_node(target), expr_node(index)])
+        }
+        Expr::IndexAssign(target, _, index, value) => Node::new(
+            "assign-index",
+            vec![expr_node(target), expr_node(index), expr_node(value)],
+        ),
+
+        Expr::Func(params, body) => {
+            Node::new("func", vec![params_node(params), block_node("body", body)])
+        }
+
+        Expr::Placeholder(..) => Node::leaf("???"),
+        Expr::Error(..) => Node::leaf("error"),
+    }
+}
@@ -0,0 +1,229 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::semantics::Semantics;
+use crate::steps::StepCounter;
+use crate::value::Value;
+
+// A single lexical scope. Scopes are shared so that closures can keep the scope they
+// were defined in alive after it has been exited
+#[derive(Debug, Default)]
+pub struct Scope {
+    // Each name's slot in 'variables'. Variables are never removed, so a slot stays the
+    // same for as long as the scope lives
+    slots: HashMap<String, usize>,
+    variables: Vec<Variable>,
+    parent: Option<Rc<RefCell<Scope>>>,
+}
+
+#[derive(Debug)]
+struct Variable {
+    value: Value,
+
+    // Constants can't be assigned to or declared again in their scope. Only globals the
+    // host defines are ever constant
+    constant: bool,
+}
+
+// What became of an assignment to a variable
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Assignment {
+    Assigned,
+    Undeclared,
+    Constant,
+}
+
+impl Scope {
+    fn new(parent: Option<Rc<RefCell<Scope>>>) -> Rc<RefCell<Scope>> {
+        Rc::new(RefCell::new(Scope {
+            slots: HashMap::new(),
+            variables: Vec::new(),
+            parent,
+        }))
+    }
+
+    // Declaring a name again replaces its value but keeps its slot
+    fn define(&mut self, name: String, value: Value) -> usize {
+        match self.slots.get(&name) {
+            Some(&slot) => {
+                self.variables[slot].value = value;
+                slot
+            }
+            None => {
+                self.variables.push(Variable {
+                    value,
+                    constant: false,
+                });
+                self.slots.insert(name, self.variables.len() - 1);
+                self.variables.len() - 1
+            }
+        }
+    }
+
+    fn assign_slot(&mut self, slot: usize, value: Value) -> Assignment {
+        let variable = &mut self.variables[slot];
+
+        if variable.constant {
+            return Assignment::Constant;
+        }
+
+        variable.value = value;
+        Assignment::Assigned
+    }
+
+    // Assigns to the innermost variable with the name, so a constant can still be shadowed
+    fn assign(&mut self, name: &str, value: Value) -> Assignment {
+        if let Some(&slot) = self.slots.get(name) {
+            return self.assign_slot(slot, value);
+        }
+
+        match &self.parent {
+            Some(parent) => parent.borrow_mut().assign(name, value),
+            None => Assignment::Undeclared,
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<Value> {
+        match self.slots.get(name) {
+            Some(&slot) => Some(self.variables[slot].value.clone()),
+            None => self.parent.as_ref()?.borrow().get(name),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Environment {
+    // Innermost scope; the global scope sits at the root of its parent chain
+    scope: Rc<RefCell<Scope>>,
+    global: Rc<RefCell<Scope>>,
+    call_depth: usize,
+    steps: StepCounter,
+    semantics: Semantics,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::new()
+    }
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        let global = Scope::new(None);
+
+        Environment {
+            scope: Rc::clone(&global),
+            global,
+            call_depth: 0,
+            steps: StepCounter::default(),
+            semantics: Semantics::default(),
+        }
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scope = Scope::new(Some(Rc::clone(&self.scope)));
+    }
+
+    pub fn pop_scope(&mut self) {
+        // Never pop the global scope
+        let parent = self.scope.borrow().parent.clone();
+
+        if let Some(parent) = parent {
+            self.scope = parent;
+        }
+    }
+
+    // Returns the current scope so that a closure can hold on to it
+    pub fn capture(&self) -> Rc<RefCell<Scope>> {
+        Rc::clone(&self.scope)
+    }
+
+    // Returns to a scope captured earlier, dropping any scopes entered since
+    pub fn restore(&mut self, scope: Rc<RefCell<Scope>>) {
+        self.scope = scope;
+    }
+
+    pub fn call_depth(&self) -> usize {
+        self.call_depth
+    }
+
+    pub fn steps(&mut self) -> &mut StepCounter {
+        &mut self.steps
+    }
+
+    pub fn semantics(&self) -> Semantics {
+        self.semantics
+    }
+
+    pub fn set_semantics(&mut self, semantics: Semantics) {
+        self.semantics = semantics;
+    }
+
+    // Switches to a fresh scope inside the callee's captured scope. The returned caller
+    // scope must be handed back to 'exit_call'
+    pub fn enter_call(&mut self, closure: &Rc<RefCell<Scope>>) -> Rc<RefCell<Scope>> {
+        self.call_depth += 1;
+        std::mem::replace(&mut self.scope, Scope::new(Some(Rc::clone(closure))))
+    }
+
+    pub fn exit_call(&mut self, caller_scope: Rc<RefCell<Scope>>) {
+        self.scope = caller_scope;
+        self.call_depth -= 1;
+    }
+
+    pub fn define(&mut self, name: String, value: Value) {
+        self.scope.borrow_mut().define(name, value);
+    }
+
+    // Defines a global that programs can't change, whatever scope is current
+    pub fn define_constant(&mut self, name: String, value: Value) {
+        let mut global = self.global.borrow_mut();
+
+        let slot = global.define(name, value);
+        global.variables[slot].constant = true;
+    }
+
+    // Makes every global defined so far a constant. Programs can still declare globals of
+    // their own afterwards
+    pub fn freeze_globals(&mut self) {
+        for variable in &mut self.global.borrow_mut().variables {
+            variable.constant = true;
+        }
+    }
+
+    // Whether declaring a variable in the current scope would replace a constant
+    pub fn is_constant(&self, name: &str) -> bool {
+        let scope = self.scope.borrow();
+
+        scope
+            .slots
+            .get(name)
+            .is_some_and(|&slot| scope.variables[slot].constant)
+    }
+
+    pub fn assign(&mut self, name: &str, value: Value) -> Assignment {
+        self.scope.borrow_mut().assign(name, value)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.scope.borrow().get(name)
+    }
+
+    // Finds the slot of a global, so that it can be read and assigned without looking up
+    // its name again
+    pub fn global_slot(&self, name: &str) -> Option<usize> {
+        self.global.borrow().slots.get(name).copied()
+    }
+
+    pub fn get_global(&self, slot: usize) -> Value {
+        self.global.borrow().variables[slot].value.clone()
+    }
+
+    pub fn assign_global(&mut self, slot: usize, value: Value) -> Assignment {
+        self.global.borrow_mut().assign_slot(slot, value)
+    }
+
+    // Whether the scope is the global one, which is at the root of every other
+    pub fn is_global(&self, scope: &Rc<RefCell<Scope>>) -> bool {
+        Rc::ptr_eq(scope, &self.global)
+    }
+}
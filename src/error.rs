@@ -1,8 +1,245 @@
+use crate::json::quote;
+use crate::messages::{explain_errors, fill, language, Language, Message};
 use crate::token::Position;
+use std::fmt::{self, Display};
 
-pub fn display_general_error<S>(subject: &str, message: S, position: Position)
-where
-    S: Into<String>,
-{
-    eprintln!("{} error, {}:\n    {}.", subject, position, message.into());
+pub const BUG_REPORT_URL: &str = "https://github.com/Bryce101189/lula-lang/issues";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuntimeErrorKind {
+    // Operation applied to values of the wrong type
+    Type,
+    // Use of an undeclared variable
+    Name,
+    // Call depth limit exceeded
+    Recursion,
+    // Index outside the bounds of a list
+    Index,
+    // Placeholder for code that hasn't been written yet
+    Unimplemented,
+    // Integer division or remainder by zero
+    Arithmetic,
+}
+
+impl Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            RuntimeErrorKind::Type => "Type",
+            RuntimeErrorKind::Name => "Name",
+            RuntimeErrorKind::Recursion => "Recursion",
+            RuntimeErrorKind::Index => "Index",
+            RuntimeErrorKind::Unimplemented => "Unimplemented",
+            RuntimeErrorKind::Arithmetic => "Arithmetic",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: Message,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LulaError {
+    LexError(Diagnostic),
+    ParseError(Diagnostic),
+    RuntimeError(RuntimeErrorKind, Diagnostic),
+
+    // An interpreter invariant was violated; always a bug in Lula itself
+    InternalError(Diagnostic),
+
+    // A likely typo in a string or comment, reported by the opt-in spelling lint. Warnings
+    // never stop a program from running
+    SpellingWarning(Diagnostic),
+
+    // A loop that has run so many times it may be stuck, reported while the program runs
+    LoopWarning(Diagnostic),
+}
+
+impl LulaError {
+    pub fn lex(message: Message, position: Position) -> LulaError {
+        LulaError::LexError(Diagnostic { message, position })
+    }
+
+    pub fn parse(message: Message, position: Position) -> LulaError {
+        LulaError::ParseError(Diagnostic { message, position })
+    }
+
+    pub fn runtime(kind: RuntimeErrorKind, message: Message, position: Position) -> LulaError {
+        LulaError::RuntimeError(kind, Diagnostic { message, position })
+    }
+
+    pub fn internal(message: Message, position: Position) -> LulaError {
+        LulaError::InternalError(Diagnostic { message, position })
+    }
+
+    pub fn spelling(message: Message, position: Position) -> LulaError {
+        LulaError::SpellingWarning(Diagnostic { message, position })
+    }
+
+    pub fn loop_warning(message: Message, position: Position) -> LulaError {
+        LulaError::LoopWarning(Diagnostic { message, position })
+    }
+
+    pub fn diagnostic(&self) -> &Diagnostic {
+        match self {
+            LulaError::LexError(diag)
+            | LulaError::ParseError(diag)
+            | LulaError::RuntimeError(_, diag)
+            | LulaError::InternalError(diag)
+            | LulaError::SpellingWarning(diag)
+            | LulaError::LoopWarning(diag) => diag,
+        }
+    }
+
+    // Stable identifier for each class of error, so tools can match on errors without
+    // depending on the wording of their messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            LulaError::LexError(..) => "E0100",
+            LulaError::ParseError(..) => "E0200",
+            LulaError::RuntimeError(RuntimeErrorKind::Type, ..) => "E0301",
+            LulaError::RuntimeError(RuntimeErrorKind::Name, ..) => "E0302",
+            LulaError::RuntimeError(RuntimeErrorKind::Recursion, ..) => "E0303",
+            LulaError::RuntimeError(RuntimeErrorKind::Index, ..) => "E0304",
+            LulaError::RuntimeError(RuntimeErrorKind::Unimplemented, ..) => "E0305",
+            LulaError::RuntimeError(RuntimeErrorKind::Arithmetic, ..) => "E0306",
+            LulaError::InternalError(..) => "E0900",
+            LulaError::SpellingWarning(..) => "W0100",
+            LulaError::LoopWarning(..) => "W0200",
+        }
+    }
+
+    pub fn severity(&self) -> &'static str {
+        match self {
+            LulaError::SpellingWarning(..) | LulaError::LoopWarning(..) => "warning",
+            _ => "error",
+        }
+    }
+
+    // English name for the class of error. JSON output keeps this untranslated so that tools
+    // can rely on it
+    fn subject(&self) -> String {
+        match self {
+            LulaError::LexError(..) => String::from("Lexing"),
+            LulaError::ParseError(..) => String::from("Parsing"),
+            LulaError::RuntimeError(kind, ..) => kind.to_string(),
+            LulaError::InternalError(..) => String::from("Internal compiler"),
+            LulaError::SpellingWarning(..) => String::from("Spelling"),
+            LulaError::LoopWarning(..) => String::from("Long-running loop"),
+        }
+    }
+
+    // The error's message, in the language diagnostics are being shown in
+    pub fn message(&self) -> String {
+        self.message_in(language())
+    }
+
+    pub fn message_in(&self, language: Language) -> String {
+        self.diagnostic().message.render(self.code(), language)
+    }
+
+    // Renders the error as a JSON object for editor tooling. Lines and columns start at 1,
+    // matching rendered diagnostics
+    pub fn to_json(&self) -> String {
+        let diag = self.diagnostic();
+
+        format!(
+            concat!(
+                "{{\"severity\": {}, \"code\": {}, \"kind\": {}, ",
+                "\"message\": {}, \"line\": {}, \"column\": {}}}"
+            ),
+            quote(self.severity()),
+            quote(self.code()),
+            quote(&self.subject()),
+            quote(&self.message()),
+            diag.position.0 + 1,
+            diag.position.1 + 1
+        )
+    }
+
+    pub fn display(&self, source_path: &str, source: &str) {
+        let language = language();
+        let diag = self.diagnostic();
+
+        let template = |key: &str| language.template(key).unwrap_or_default();
+        let position = fill(
+            template("frame.position"),
+            &[
+                (diag.position.0 + 1).to_string(),
+                (diag.position.1 + 1).to_string(),
+            ],
+        );
+        let heading = fill(
+            template(&format!("frame.{}", self.severity())),
+            &[
+                template(self.code()).to_owned(),
+                self.code().to_owned(),
+                source_path.to_owned(),
+                position,
+            ],
+        );
+
+        eprintln!("{}\n    {}.", heading, self.message_in(language));
+
+        if let Some(snippet) = render_snippet(source, diag.position) {
+            eprint!("{}", snippet);
+        }
+
+        if let LulaError::InternalError(..) = self {
+            eprintln!(
+                "{}",
+                fill(template("frame.bug-report"), &[BUG_REPORT_URL.to_owned()])
+            );
+        }
+
+        if explain_errors() {
+            let key = format!("{}.{}", self.code(), diag.message.key);
+
+            if let Some(explanation) = language.explanation(&key) {
+                for line in explanation.lines() {
+                    eprintln!("{}", format!("~ {}", line).trim_end());
+                }
+            }
+        }
+    }
+}
+
+// Renders the source line an error occurred on with the error position underlined, or
+// nothing if the position lies past the end of the source
+fn render_snippet(source: &str, position: Position) -> Option<String> {
+    let Position(line, column, _) = position;
+    let text = source.lines().nth(line)?;
+
+    let line_number = (line + 1).to_string();
+    let gutter = " ".repeat(line_number.len());
+
+    // Copy tabs into the padding so the underline lines up however wide tabs are rendered
+    let mut padding: String = text
+        .chars()
+        .take(column)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    padding.push_str(&" ".repeat(column.saturating_sub(text.chars().count())));
+
+    // Underline the whole word under the error position, or a single character otherwise
+    let width = text
+        .chars()
+        .skip(column)
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .count()
+        .max(1);
+
+    Some(format!(
+        "{} |\n{} | {}\n{} | {}^{}\n",
+        gutter,
+        line_number,
+        text,
+        gutter,
+        padding,
+        "~".repeat(width - 1)
+    ))
 }
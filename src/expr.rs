@@ -1,296 +1,839 @@
-use crate::error::display_general_error;
-use crate::token::{Literal, Token, TokenKind};
+use crate::environment::{Assignment, Environment};
+use crate::error::{LulaError, RuntimeErrorKind};
+use crate::function::{Function, MAX_CALL_DEPTH};
+use crate::messages::Message;
+use crate::native::{find_native, NativeFn};
+use crate::number::Number;
+use crate::semantics::to_number;
+use crate::statement::Statement;
+use crate::token::{Literal, Name, Position, Token, TokenKind};
+use crate::value::Value;
+use std::{cmp::Ordering, collections::HashMap, rc::Rc};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Hash)]
 pub enum Expr {
-    Literal(Literal),
+    Literal(Token),
+    Variable(Token),
+    Assign(Token, Box<Expr>),
     Unary(Token, Box<Expr>),
     Binary(Box<Expr>, Token, Box<Expr>),
+    Logical(Box<Expr>, Token, Box<Expr>),
     Grouping(Token, Box<Expr>, Token),
+    Range(Box<Expr>, Token, Box<Expr>),
+    Call(Box<Expr>, Token, Vec<Expr>),
+    Method(Box<Expr>, Token, Token, Vec<Expr>),
+    List(Vec<Expr>),
+    Map(Vec<(Expr, Token, Expr)>),
+    Index(Box<Expr>, Token, Box<Expr>),
+    IndexAssign(Box<Expr>, Token, Box<Expr>, Box<Expr>),
+    Func(Vec<Name>, Rc<Vec<Statement>>),
+
+    // Stands in for a value that failed to parse, keeping its tokens
+    Error(Vec<Token>),
+
+    Conditional(Box<Expr>, Token, Box<Expr>, Box<Expr>),
+
+    // Stands in for code yet to be written, failing only if it is reached
+    Placeholder(Token),
 }
 
 impl Expr {
-    pub fn evaluate(&self) -> Option<Literal> {
+    pub fn evaluate(&self, env: &mut Environment) -> Result<Value, LulaError> {
         match self {
-            Expr::Literal(..) => self.evaluate_literal(),
-            Expr::Unary(..) => self.evaluate_unary(),
-            Expr::Binary(..) => self.evaluate_binary(),
-            Expr::Grouping(..) => self.evaluate_grouping(),
+            Expr::Literal(lit) => Expr::evaluate_literal(lit),
+            Expr::Variable(name) => Expr::evaluate_variable(name, env),
+            Expr::Assign(name, value) => Expr::evaluate_assign(name, value, env),
+            Expr::Unary(op, expr) => Expr::evaluate_unary(op, expr, env),
+            Expr::Binary(lhs, op, rhs) => Expr::evaluate_binary(lhs, op, rhs, env),
+            Expr::Logical(lhs, op, rhs) => Expr::evaluate_logical(lhs, op, rhs, env),
+            Expr::Conditional(condition, question, then_value, else_value) => {
+                // Only the chosen value is evaluated
+                let value = condition.evaluate(env)?;
+
+                if env
+                    .semantics()
+                    .test(&value, || condition.position_or(question.position))?
+                {
+                    then_value.evaluate(env)
+                } else {
+                    else_value.evaluate(env)
+                }
+            }
+            Expr::Grouping(_, expr, _) => expr.evaluate(env),
+            Expr::Call(callee, paren, args) => Expr::evaluate_call(callee, paren, args, env),
+            Expr::Method(receiver, name, paren, args) => {
+                Expr::evaluate_method(receiver, name, paren, args, env)
+            }
+            Expr::List(items) => Expr::evaluate_list(items, env),
+            Expr::Map(entries) => Expr::evaluate_map(entries, env),
+            Expr::Index(target, bracket, index) => {
+                Expr::evaluate_index(target, bracket, index, env)
+            }
+            Expr::IndexAssign(target, bracket, index, value) => {
+                Expr::evaluate_index_assign(target, bracket, index, value, env)
+            }
+
+            Expr::Func(params, body) => Ok(Value::Function(Function {
+                name: String::from("anonymous"),
+                params: Name::identifiers(params),
+                body: Rc::clone(body),
+                closure: env.capture(),
+            })),
+
+            // Ranges are only produced by the parser as the iterable of a for loop
+            Expr::Range(_, op, _) => Err(LulaError::internal(
+                Message::new("range-outside-loop"),
+                op.position,
+            )),
+
+            Expr::Error(tokens) => Err(Statement::unparsed(tokens)),
+            Expr::Placeholder(tok) => Err(Expr::placeholder_error(tok)),
         }
     }
 
-    fn evaluate_literal(&self) -> Option<Literal> {
+    // Where an expression starts, if it has any tokens to tell. Lists, maps and functions
+    // that are empty don't
+    pub(crate) fn position(&self) -> Option<Position> {
         match self {
-            Expr::Literal(l) => Some(l.clone()),
+            Expr::Literal(token)
+            | Expr::Variable(token)
+            | Expr::Assign(token, _)
+            | Expr::Placeholder(token) => Some(token.position),
+            Expr::Unary(op, _) => Some(op.position),
+            Expr::Grouping(open, ..) => Some(open.position),
+
+            Expr::Binary(lhs, ..)
+            | Expr::Logical(lhs, ..)
+            | Expr::Range(lhs, ..)
+            | Expr::Index(lhs, ..)
+            | Expr::IndexAssign(lhs, ..)
+            | Expr::Conditional(lhs, ..)
+            | Expr::Call(lhs, ..)
+            | Expr::Method(lhs, ..) => lhs.position(),
+
+            Expr::List(items) => items.iter().find_map(Expr::position),
+            Expr::Map(entries) => entries.iter().find_map(|(key, ..)| key.position()),
+            Expr::Func(params, body) => params
+                .first()
+                .map(|param| param.position)
+                .or_else(|| body.iter().find_map(Statement::position)),
+            Expr::Error(tokens) => tokens.first().map(|tok| tok.position),
+        }
+    }
+
+    pub(crate) fn position_or(&self, fallback: Position) -> Position {
+        self.position().unwrap_or(fallback)
+    }
+
+    // Evaluates both bounds of a range expression, which must be numbers
+    pub fn evaluate_range(
+        start: &Expr,
+        op: &Token,
+        end: &Expr,
+        env: &mut Environment,
+    ) -> Result<(Number, Number), LulaError> {
+        let start = start.evaluate(env)?;
+        let end = end.evaluate(env)?;
+
+        Expr::range_bounds(start, end, op)
+    }
+
+    pub(crate) fn range_bounds(
+        start: Value,
+        end: Value,
+        op: &Token,
+    ) -> Result<(Number, Number), LulaError> {
+        match (to_number(&start), to_number(&end)) {
+            (Some(start_val), Some(end_val)) => Ok((start_val, end_val)),
+            _ => Err(LulaError::runtime(
+                RuntimeErrorKind::Type,
+                Message::new("range-bounds").debug_arg(start).debug_arg(end),
+                op.position,
+            )),
+        }
+    }
+
+    pub(crate) fn identifier_name(name: &Token) -> Result<&str, LulaError> {
+        match &name.kind {
+            TokenKind::Literal(Literal::Identifier(val)) => Ok(val),
+            _ => Err(LulaError::internal(
+                Message::new("expected-identifier").debug_arg(&name.kind),
+                name.position,
+            )),
+        }
+    }
+
+    pub(crate) fn evaluate_literal(lit: &Token) -> Result<Value, LulaError> {
+        let val = match &lit.kind {
+            TokenKind::Literal(val) => Value::from_literal(val),
             _ => None,
+        };
+
+        val.ok_or_else(|| {
+            LulaError::internal(
+                Message::new("expected-literal").debug_arg(&lit.kind),
+                lit.position,
+            )
+        })
+    }
+
+    pub(crate) fn evaluate_variable(
+        name: &Token,
+        env: &mut Environment,
+    ) -> Result<Value, LulaError> {
+        let identifier = Expr::identifier_name(name)?;
+
+        match env.get(identifier) {
+            Some(val) => Ok(val),
+            None => Err(LulaError::runtime(
+                RuntimeErrorKind::Name,
+                Message::new("undefined-variable").arg(identifier),
+                name.position,
+            )),
         }
     }
 
-    fn evaluate_unary(&self) -> Option<Literal> {
-        let (op, expr) = match self {
-            Expr::Unary(o, e) => (o, e),
-            _ => unreachable!(),
+    fn evaluate_assign(
+        name: &Token,
+        value: &Expr,
+        env: &mut Environment,
+    ) -> Result<Value, LulaError> {
+        let val = value.evaluate(env)?;
+        Expr::assign_variable(name, val, env)
+    }
+
+    pub(crate) fn assign_variable(
+        name: &Token,
+        val: Value,
+        env: &mut Environment,
+    ) -> Result<Value, LulaError> {
+        let identifier = Expr::identifier_name(name)?;
+
+        // Assignment evaluates to the assigned value so that it can be chained
+        let message = match env.assign(identifier, val.clone()) {
+            Assignment::Assigned => return Ok(val),
+            Assignment::Undeclared => "undeclared-assignment",
+            Assignment::Constant => "constant-assignment",
         };
 
-        // Evaluate inner expresion
-        let lit = match expr.evaluate() {
-            Some(e) => e,
-            None => return None,
+        Err(LulaError::runtime(
+            RuntimeErrorKind::Name,
+            Message::new(message).arg(identifier),
+            name.position,
+        ))
+    }
+
+    fn evaluate_call(
+        callee: &Expr,
+        paren: &Token,
+        args: &[Expr],
+        env: &mut Environment,
+    ) -> Result<Value, LulaError> {
+        let callee = callee.evaluate(env)?;
+        Expr::check_call(&callee, args.len(), paren, env.call_depth())?;
+
+        let mut arg_vals = Vec::new();
+
+        for arg in args {
+            arg_vals.push(arg.evaluate(env)?);
+        }
+
+        match &callee {
+            Value::NativeFn(native) => native.call(&arg_vals, paren.position),
+            Value::Function(func) => func.call(arg_vals, env),
+            _ => Err(LulaError::internal(
+                Message::new("value-not-callable").debug_arg(callee),
+                paren.position,
+            )),
+        }
+    }
+
+    // Checks that a value can be called with the given number of arguments, before any of
+    // them are evaluated
+    pub(crate) fn check_call(
+        callee: &Value,
+        arg_count: usize,
+        paren: &Token,
+        call_depth: usize,
+    ) -> Result<(), LulaError> {
+        let (name, arity) = match callee {
+            Value::Function(func) => (func.name.as_str(), func.arity()),
+            Value::NativeFn(native) => (native.name, native.arity),
+            val => {
+                return Err(LulaError::runtime(
+                    RuntimeErrorKind::Type,
+                    Message::new("not-callable").debug_arg(val),
+                    paren.position,
+                ));
+            }
+        };
+
+        if arg_count != arity {
+            return Err(LulaError::runtime(
+                RuntimeErrorKind::Type,
+                Message::new("argument-count")
+                    .arg(name)
+                    .arg(arity)
+                    .arg(arg_count),
+                paren.position,
+            ));
+        }
+
+        if call_depth >= MAX_CALL_DEPTH {
+            return Err(LulaError::runtime(
+                RuntimeErrorKind::Recursion,
+                Message::new("call-depth").arg(MAX_CALL_DEPTH).arg(name),
+                paren.position,
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn evaluate_method(
+        receiver: &Expr,
+        name: &Token,
+        paren: &Token,
+        args: &[Expr],
+        env: &mut Environment,
+    ) -> Result<Value, LulaError> {
+        let mut arg_vals = vec![receiver.evaluate(env)?];
+        let native = Expr::method_native(&arg_vals[0], name, paren, args.len())?;
+
+        for arg in args {
+            arg_vals.push(arg.evaluate(env)?);
+        }
+
+        native.call(&arg_vals, paren.position)
+    }
+
+    // Finds the builtin a method call refers to, checking it takes the given number of
+    // arguments besides the receiver
+    pub(crate) fn method_native(
+        receiver: &Value,
+        name: &Token,
+        paren: &Token,
+        arg_count: usize,
+    ) -> Result<NativeFn, LulaError> {
+        let identifier = Expr::identifier_name(name)?;
+
+        // Methods always refer to builtins, even if a global of the same name shadows one
+        let native = match find_native(identifier) {
+            Some(native) => native,
+            None => {
+                return Err(LulaError::runtime(
+                    RuntimeErrorKind::Name,
+                    Message::new("unknown-method")
+                        .arg(identifier)
+                        .debug_arg(receiver),
+                    name.position,
+                ));
+            }
         };
 
+        // The receiver counts as the native's first argument
+        if arg_count + 1 != native.arity {
+            return Err(LulaError::runtime(
+                RuntimeErrorKind::Type,
+                Message::new("method-argument-count")
+                    .arg(native.name)
+                    .arg(native.arity.saturating_sub(1))
+                    .arg(arg_count),
+                paren.position,
+            ));
+        }
+
+        Ok(native)
+    }
+
+    fn evaluate_list(items: &[Expr], env: &mut Environment) -> Result<Value, LulaError> {
+        let mut vals = Vec::new();
+
+        for item in items {
+            vals.push(item.evaluate(env)?);
+        }
+
+        Ok(Value::list(vals))
+    }
+
+    fn evaluate_map(
+        entries: &[(Expr, Token, Expr)],
+        env: &mut Environment,
+    ) -> Result<Value, LulaError> {
+        let mut vals = HashMap::new();
+
+        for (key, colon, value) in entries {
+            let key = Expr::map_key(key.evaluate(env)?, colon)?;
+            vals.insert(key, value.evaluate(env)?);
+        }
+
+        Ok(Value::map(vals))
+    }
+
+    fn index_type_error(target: &Value, bracket: &Token) -> LulaError {
+        LulaError::runtime(
+            RuntimeErrorKind::Type,
+            Message::new("not-indexable").debug_arg(target),
+            bracket.position,
+        )
+    }
+
+    // Indices must be integers, even if a float holds a whole number
+    fn whole_index(index: Value, bracket: &Token) -> Result<i64, LulaError> {
+        match index {
+            Value::Int(val) => Ok(val),
+            val => Err(LulaError::runtime(
+                RuntimeErrorKind::Type,
+                Message::new("whole-index").debug_arg(val),
+                bracket.position,
+            )),
+        }
+    }
+
+    // Converts an index into a position within a list or string of the given length
+    fn element_index(
+        index: Value,
+        len: usize,
+        kind: &'static str,
+        bracket: &Token,
+    ) -> Result<usize, LulaError> {
+        let index = Expr::whole_index(index, bracket)?;
+
+        if index < 0 || index >= len as i64 {
+            return Err(LulaError::runtime(
+                RuntimeErrorKind::Index,
+                Message::new("index-bounds").arg(index).word(kind).arg(len),
+                bracket.position,
+            ));
+        }
+
+        Ok(index as usize)
+    }
+
+    fn slice_bounds(
+        start: Number,
+        end: Number,
+        len: usize,
+        kind: &'static str,
+        bracket: &Token,
+    ) -> Result<(usize, usize), LulaError> {
+        let start = Expr::whole_index(start.into(), bracket)?;
+        let end = Expr::whole_index(end.into(), bracket)?;
+
+        if start < 0 || start > end || end > len as i64 {
+            return Err(LulaError::runtime(
+                RuntimeErrorKind::Index,
+                Message::new("slice-bounds")
+                    .arg(start)
+                    .arg(end)
+                    .word(kind)
+                    .arg(len),
+                bracket.position,
+            ));
+        }
+
+        Ok((start as usize, end as usize))
+    }
+
+    pub(crate) fn map_key(key: Value, tok: &Token) -> Result<String, LulaError> {
+        match key {
+            Value::String(val) => Ok(val),
+            val => Err(LulaError::runtime(
+                RuntimeErrorKind::Type,
+                Message::new("map-key").debug_arg(val),
+                tok.position,
+            )),
+        }
+    }
+
+    fn evaluate_index(
+        target: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        env: &mut Environment,
+    ) -> Result<Value, LulaError> {
+        if let Expr::Range(start, op, end) = index {
+            return Expr::evaluate_slice(target, bracket, start, op, end, env);
+        }
+
+        let target = target.evaluate(env)?;
+        let index = index.evaluate(env)?;
+
+        Expr::index_value(&target, bracket, index)
+    }
+
+    pub(crate) fn index_value(
+        target: &Value,
+        bracket: &Token,
+        index: Value,
+    ) -> Result<Value, LulaError> {
+        match target {
+            Value::List(items) => {
+                let items = items.borrow();
+                let i = Expr::element_index(index, items.len(), "word.list", bracket)?;
+
+                Ok(items[i].clone())
+            }
+
+            // Strings are indexed by character rather than by byte
+            Value::String(val) => {
+                let i = Expr::element_index(index, val.chars().count(), "word.string", bracket)?;
+                Ok(Value::String(val.chars().skip(i).take(1).collect()))
+            }
+
+            // Looking up a missing key gives nil rather than an error
+            Value::Map(entries) => {
+                let key = Expr::map_key(index, bracket)?;
+                Ok(entries.borrow().get(&key).cloned().unwrap_or(Value::Nil))
+            }
+
+            _ => Err(Expr::index_type_error(target, bracket)),
+        }
+    }
+
+    // Copies part of a list or string, from the start of a range up to but not including
+    // its end
+    fn evaluate_slice(
+        target: &Expr,
+        bracket: &Token,
+        start: &Expr,
+        op: &Token,
+        end: &Expr,
+        env: &mut Environment,
+    ) -> Result<Value, LulaError> {
+        let target = target.evaluate(env)?;
+        let (start, end) = Expr::evaluate_range(start, op, end, env)?;
+
+        Expr::slice_value(&target, bracket, start, end)
+    }
+
+    pub(crate) fn slice_value(
+        target: &Value,
+        bracket: &Token,
+        start: Number,
+        end: Number,
+    ) -> Result<Value, LulaError> {
+        match target {
+            Value::List(items) => {
+                let items = items.borrow();
+                let (start, end) =
+                    Expr::slice_bounds(start, end, items.len(), "word.list", bracket)?;
+
+                Ok(Value::list(items[start..end].to_vec()))
+            }
+
+            Value::String(val) => {
+                let len = val.chars().count();
+                let (start, end) = Expr::slice_bounds(start, end, len, "word.string", bracket)?;
+
+                Ok(Value::String(
+                    val.chars().skip(start).take(end - start).collect(),
+                ))
+            }
+
+            _ => Err(Expr::index_type_error(target, bracket)),
+        }
+    }
+
+    fn evaluate_index_assign(
+        target: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+        env: &mut Environment,
+    ) -> Result<Value, LulaError> {
+        let target = target.evaluate(env)?;
+        let index = index.evaluate(env)?;
+        let val = value.evaluate(env)?;
+
+        // Check bounds only once the value is known, in case evaluating it changed the list
+        Expr::assign_index(&target, bracket, index, val.clone())?;
+        Ok(val)
+    }
+
+    pub(crate) fn assign_index(
+        target: &Value,
+        bracket: &Token,
+        index: Value,
+        val: Value,
+    ) -> Result<(), LulaError> {
+        match target {
+            Value::List(items) => {
+                let mut items = items.borrow_mut();
+                let i = Expr::element_index(index, items.len(), "word.list", bracket)?;
+                items[i] = val;
+            }
+
+            Value::Map(entries) => {
+                let key = Expr::map_key(index, bracket)?;
+                entries.borrow_mut().insert(key, val);
+            }
+
+            _ => return Err(Expr::index_type_error(target, bracket)),
+        }
+
+        Ok(())
+    }
+
+    fn binary_type_error(op: &Token, left: &Value, right: &Value) -> LulaError {
+        LulaError::runtime(
+            RuntimeErrorKind::Type,
+            Message::new("binary-mismatch")
+                .debug_arg(&op.kind)
+                .debug_arg(left)
+                .debug_arg(right),
+            op.position,
+        )
+    }
+
+    fn evaluate_unary(op: &Token, expr: &Expr, env: &mut Environment) -> Result<Value, LulaError> {
+        // Evaluate inner expresion
+        let operand = expr.evaluate(env)?;
+        Expr::apply_unary(op, operand)
+    }
+
+    pub(crate) fn apply_unary(op: &Token, operand: Value) -> Result<Value, LulaError> {
         // Apply operations
         match op.kind {
-            TokenKind::Minus => match lit {
-                Literal::Number(val) => Some(Literal::Number(-val)),
-                _ => {
-                    display_general_error(
-                        "Type",
-                        format!("Could not apply operation {:?} on type {:?}", op.kind, lit),
-                        op.position,
-                    );
-                    None
-                }
+            TokenKind::Minus => match to_number(&operand) {
+                Some(val) => Ok(Value::from(-val)),
+                None => Err(LulaError::runtime(
+                    RuntimeErrorKind::Type,
+                    Message::new("unary-mismatch")
+                        .debug_arg(&op.kind)
+                        .debug_arg(operand),
+                    op.position,
+                )),
             },
-            TokenKind::Bang => match lit {
-                Literal::Bool(val) => Some(Literal::Bool(!val)),
-                _ => {
-                    display_general_error(
-                        "Type",
-                        format!("Could not apply operation {:?} on type {:?}", op.kind, lit),
-                        op.position,
-                    );
-                    None
-                }
+            TokenKind::Bang => match operand {
+                Value::Bool(val) => Ok(Value::Bool(!val)),
+                _ => Err(LulaError::runtime(
+                    RuntimeErrorKind::Type,
+                    Message::new("unary-mismatch")
+                        .debug_arg(&op.kind)
+                        .debug_arg(operand),
+                    op.position,
+                )),
             },
-            _ => unreachable!(),
+            TokenKind::Tilde => match operand {
+                Value::Int(val) => Ok(Value::Int(!val)),
+                _ => Err(LulaError::runtime(
+                    RuntimeErrorKind::Type,
+                    Message::new("unary-mismatch")
+                        .debug_arg(&op.kind)
+                        .debug_arg(operand),
+                    op.position,
+                )),
+            },
+            _ => Err(LulaError::internal(
+                Message::new("unexpected-unary").debug_arg(&op.kind),
+                op.position,
+            )),
         }
     }
 
-    fn evaluate_binary(&self) -> Option<Literal> {
-        let (lhs, op, rhs) = match self {
-            Expr::Binary(l, o, r) => (l, o, r),
-            _ => unreachable!(),
-        };
+    fn evaluate_logical(
+        lhs: &Expr,
+        op: &Token,
+        rhs: &Expr,
+        env: &mut Environment,
+    ) -> Result<Value, LulaError> {
+        let left = lhs.evaluate(env)?;
+        let holds = env
+            .semantics()
+            .test(&left, || lhs.position_or(op.position))?;
+
+        // Only evaluate the right operand if the left one doesn't already decide the result,
+        // which is then whichever operand was evaluated last
+        match op.kind {
+            TokenKind::And if !holds => Ok(left),
+            TokenKind::Or if holds => Ok(left),
+            TokenKind::And | TokenKind::Or => rhs.evaluate(env),
+            _ => Err(LulaError::internal(
+                Message::new("unexpected-logical").debug_arg(&op.kind),
+                op.position,
+            )),
+        }
+    }
 
+    fn evaluate_binary(
+        lhs: &Expr,
+        op: &Token,
+        rhs: &Expr,
+        env: &mut Environment,
+    ) -> Result<Value, LulaError> {
         // Evaluate outer expressions
-        let left_lit = match lhs.evaluate() {
-            Some(e) => e,
-            None => return None,
-        };
+        let left = lhs.evaluate(env)?;
+        let right = rhs.evaluate(env)?;
+
+        Expr::apply_binary(op, left, right)
+    }
+
+    // Applies an arithmetic operator to two numbers. Integers give integers, unless the result
+    // isn't one, as when it overflows, in which case the operation is done on floats like it
+    // is when either number is a float
+    fn apply_arithmetic(
+        op: &Token,
+        left: &Value,
+        right: &Value,
+        int_op: fn(i64, i64) -> Option<i64>,
+        float_op: fn(f64, f64) -> f64,
+    ) -> Result<Value, LulaError> {
+        match (to_number(left), to_number(right)) {
+            (Some(Number::Int(left_val)), Some(Number::Int(right_val))) => {
+                Ok(int_op(left_val, right_val).map_or_else(
+                    || Value::Float(float_op(left_val as f64, right_val as f64)),
+                    Value::Int,
+                ))
+            }
+            (Some(left_val), Some(right_val)) => Ok(Value::Float(float_op(
+                left_val.to_f64(),
+                right_val.to_f64(),
+            ))),
+            _ => Err(Expr::binary_type_error(op, left, right)),
+        }
+    }
 
-        let right_lit = match rhs.evaluate() {
-            Some(e) => e,
-            None => return None,
+    pub(crate) fn placeholder_error(tok: &Token) -> LulaError {
+        LulaError::runtime(
+            RuntimeErrorKind::Unimplemented,
+            Message::new("placeholder"),
+            tok.position,
+        )
+    }
+
+    // Integer powers stay integers unless the exponent is negative or the result overflows
+    pub(crate) fn apply_power(op: &Token, left: Value, right: Value) -> Result<Value, LulaError> {
+        let int_pow = |base: i64, exp: i64| {
+            if (0..=i64::from(u32::MAX)).contains(&exp) {
+                base.checked_pow(exp as u32)
+            } else {
+                None
+            }
         };
 
+        Expr::apply_arithmetic(op, &left, &right, int_pow, f64::powf)
+    }
+
+    // Applies a bitwise operator to two integers, as 64-bit two's complement. Floats are
+    // refused even when whole, since their bits aren't an integer's
+    fn apply_bitwise(
+        op: &Token,
+        left: &Value,
+        right: &Value,
+        int_op: fn(i64, i64) -> i64,
+    ) -> Result<Value, LulaError> {
+        match (left, right) {
+            (Value::Int(left_val), Value::Int(right_val)) => {
+                Ok(Value::Int(int_op(*left_val, *right_val)))
+            }
+            _ => Err(Expr::binary_type_error(op, left, right)),
+        }
+    }
+
+    // Shifting by 64 or more moves every bit out, leaving 0, or -1 when shifting a negative
+    // number right. Shifting by a negative amount is an error
+    fn apply_shift(op: &Token, left: &Value, right: &Value) -> Result<Value, LulaError> {
+        match (left, right) {
+            (Value::Int(..), Value::Int(count)) if *count < 0 => Err(LulaError::runtime(
+                RuntimeErrorKind::Type,
+                Message::new("negative-shift").arg(count),
+                op.position,
+            )),
+            (Value::Int(..), Value::Int(..)) if op.kind == TokenKind::LessLess => {
+                Expr::apply_bitwise(op, left, right, |val, count| {
+                    val.checked_shl(count.min(64) as u32).unwrap_or(0)
+                })
+            }
+            _ => Expr::apply_bitwise(op, left, right, |val, count| val >> count.min(63)),
+        }
+    }
+
+    fn apply_comparison(
+        op: &Token,
+        left: &Value,
+        right: &Value,
+        accept: fn(Ordering) -> bool,
+    ) -> Result<Value, LulaError> {
+        match (to_number(left), to_number(right)) {
+            // Nothing is ordered against NaN, so every comparison with it is false
+            (Some(left_val), Some(right_val)) => Ok(Value::Bool(
+                left_val.partial_cmp(&right_val).is_some_and(accept),
+            )),
+            _ => Err(Expr::binary_type_error(op, left, right)),
+        }
+    }
+
+    pub(crate) fn apply_binary(op: &Token, left: Value, right: Value) -> Result<Value, LulaError> {
         // Apply operations
         match op.kind {
             // Mathematical operations
-            TokenKind::Plus => match (left_lit.clone(), right_lit.clone()) {
-                (Literal::Number(left_val), Literal::Number(right_val)) => {
-                    Some(Literal::Number(left_val + right_val))
-                }
-                (Literal::String(left_val), Literal::String(right_val)) => {
-                    Some(Literal::String(left_val + right_val.as_str()))
-                }
-                _ => {
-                    display_general_error(
-                        "Type",
-                        format!(
-                            "Could not apply operation {:?} on types {:?} and {:?}",
-                            op.kind, left_lit, right_lit
-                        ),
-                        op.position,
-                    );
-                    None
-                }
-            },
-            TokenKind::Minus => match (left_lit.clone(), right_lit.clone()) {
-                (Literal::Number(left_val), Literal::Number(right_val)) => {
-                    Some(Literal::Number(left_val - right_val))
-                }
-                _ => {
-                    display_general_error(
-                        "Type",
-                        format!(
-                            "Could not apply operation {:?} on types {:?} and {:?}",
-                            op.kind, left_lit, right_lit
-                        ),
-                        op.position,
-                    );
-                    None
-                }
-            },
-            TokenKind::Star => match (left_lit.clone(), right_lit.clone()) {
-                (Literal::Number(left_val), Literal::Number(right_val)) => {
-                    Some(Literal::Number(left_val * right_val))
-                }
-                _ => {
-                    display_general_error(
-                        "Type",
-                        format!(
-                            "Could not apply operation {:?} on types {:?} and {:?}",
-                            op.kind, left_lit, right_lit
-                        ),
-                        op.position,
-                    );
-                    None
-                }
-            },
-            TokenKind::Slash => match (left_lit.clone(), right_lit.clone()) {
-                (Literal::Number(left_val), Literal::Number(right_val)) => {
-                    Some(Literal::Number(left_val / right_val))
-                }
-                _ => {
-                    display_general_error(
-                        "Type",
-                        format!(
-                            "Could not apply operation {:?} on types {:?} and {:?}",
-                            op.kind, left_lit, right_lit
-                        ),
-                        op.position,
-                    );
-                    None
-                }
-            },
-            TokenKind::Percent => match (left_lit.clone(), right_lit.clone()) {
-                (Literal::Number(left_val), Literal::Number(right_val)) => {
-                    Some(Literal::Number(left_val % right_val))
-                }
-                _ => {
-                    display_general_error(
-                        "Type",
-                        format!(
-                            "Could not apply operation {:?} on types {:?} and {:?}",
-                            op.kind, left_lit, right_lit
-                        ),
-                        op.position,
-                    );
-                    None
+            TokenKind::Plus => match (&left, &right) {
+                (Value::String(left_val), Value::String(right_val)) => {
+                    Ok(Value::String(left_val.clone() + right_val.as_str()))
                 }
+                _ => Expr::apply_arithmetic(op, &left, &right, i64::checked_add, |l, r| l + r),
             },
+            TokenKind::Minus => {
+                Expr::apply_arithmetic(op, &left, &right, i64::checked_sub, |l, r| l - r)
+            }
+            TokenKind::Star => {
+                Expr::apply_arithmetic(op, &left, &right, i64::checked_mul, |l, r| l * r)
+            }
+
+            // Integers can't be divided by zero, though floats give infinity or NaN
+            TokenKind::Slash | TokenKind::Percent
+                if matches!((&left, &right), (Value::Int(..), Value::Int(0))) =>
+            {
+                Err(LulaError::runtime(
+                    RuntimeErrorKind::Arithmetic,
+                    Message::new("division-by-zero")
+                        .debug_arg(&op.kind)
+                        .debug_arg(&left)
+                        .debug_arg(&right),
+                    op.position,
+                ))
+            }
+
+            // Integer division and remainder round towards zero. The smallest integer divided
+            // by -1 is too large for an integer, but its remainder is still 0
+            TokenKind::Slash => {
+                Expr::apply_arithmetic(op, &left, &right, i64::checked_div, |l, r| l / r)
+            }
+            TokenKind::Percent => Expr::apply_arithmetic(
+                op,
+                &left,
+                &right,
+                |l, r| Some(l.wrapping_rem(r)),
+                |l, r| l % r,
+            ),
+            TokenKind::StarStar => Expr::apply_power(op, left, right),
+
+            // Bitwise operations
+            TokenKind::Ampersand => Expr::apply_bitwise(op, &left, &right, |l, r| l & r),
+            TokenKind::Pipe => Expr::apply_bitwise(op, &left, &right, |l, r| l | r),
+            TokenKind::Caret => Expr::apply_bitwise(op, &left, &right, |l, r| l ^ r),
+            TokenKind::LessLess | TokenKind::GreaterGreater => Expr::apply_shift(op, &left, &right),
 
             // Numeric comparisons
-            TokenKind::Less => match (left_lit.clone(), right_lit.clone()) {
-                (Literal::Number(left_val), Literal::Number(right_val)) => {
-                    Some(Literal::Bool(left_val < right_val))
-                }
-                _ => {
-                    display_general_error(
-                        "Type",
-                        format!(
-                            "Could not apply operation {:?} on types {:?} and {:?}",
-                            op.kind, left_lit, right_lit
-                        ),
-                        op.position,
-                    );
-                    None
-                }
-            },
-            TokenKind::LessEqual => match (left_lit.clone(), right_lit.clone()) {
-                (Literal::Number(left_val), Literal::Number(right_val)) => {
-                    Some(Literal::Bool(left_val <= right_val))
-                }
-                _ => {
-                    display_general_error(
-                        "Type",
-                        format!(
-                            "Could not apply operation {:?} on types {:?} and {:?}",
-                            op.kind, left_lit, right_lit
-                        ),
-                        op.position,
-                    );
-                    None
-                }
-            },
-            TokenKind::Greater => match (left_lit.clone(), right_lit.clone()) {
-                (Literal::Number(left_val), Literal::Number(right_val)) => {
-                    Some(Literal::Bool(left_val > right_val))
-                }
-                _ => {
-                    display_general_error(
-                        "Type",
-                        format!(
-                            "Could not apply operation {:?} on types {:?} and {:?}",
-                            op.kind, left_lit, right_lit
-                        ),
-                        op.position,
-                    );
-                    None
-                }
-            },
-            TokenKind::GreaterEqual => match (left_lit.clone(), right_lit.clone()) {
-                (Literal::Number(left_val), Literal::Number(right_val)) => {
-                    Some(Literal::Bool(left_val >= right_val))
-                }
-                _ => {
-                    display_general_error(
-                        "Type",
-                        format!(
-                            "Could not apply operation {:?} on types {:?} and {:?}",
-                            op.kind, left_lit, right_lit
-                        ),
-                        op.position,
-                    );
-                    None
-                }
-            },
+            TokenKind::Less => Expr::apply_comparison(op, &left, &right, Ordering::is_lt),
+            TokenKind::LessEqual => Expr::apply_comparison(op, &left, &right, Ordering::is_le),
+            TokenKind::Greater => Expr::apply_comparison(op, &left, &right, Ordering::is_gt),
+            TokenKind::GreaterEqual => Expr::apply_comparison(op, &left, &right, Ordering::is_ge),
 
             // Comparsions
-            TokenKind::EqualEqual => match (left_lit.clone(), right_lit.clone()) {
-                (Literal::Number(left_val), Literal::Number(right_val)) => {
-                    Some(Literal::Bool(left_val == right_val))
-                }
-                (Literal::Bool(left_val), Literal::Bool(right_val)) => {
-                    Some(Literal::Bool(left_val == right_val))
-                }
-                (Literal::String(left_val), Literal::String(right_val)) => {
-                    Some(Literal::Bool(left_val == right_val))
-                }
-                _ => {
-                    display_general_error(
-                        "Type",
-                        format!(
-                            "Could not apply operation {:?} on types {:?} and {:?}",
-                            op.kind, left_lit, right_lit
-                        ),
-                        op.position,
-                    );
-                    None
-                }
+            TokenKind::EqualEqual => match left.equals(&right) {
+                Some(equal) => Ok(Value::Bool(equal)),
+                None => Err(Expr::binary_type_error(op, &left, &right)),
             },
-            TokenKind::BangEqual => match (left_lit.clone(), right_lit.clone()) {
-                (Literal::Number(left_val), Literal::Number(right_val)) => {
-                    Some(Literal::Bool(left_val != right_val))
-                }
-                (Literal::Bool(left_val), Literal::Bool(right_val)) => {
-                    Some(Literal::Bool(left_val != right_val))
-                }
-                (Literal::String(left_val), Literal::String(right_val)) => {
-                    Some(Literal::Bool(left_val != right_val))
-                }
-                _ => {
-                    display_general_error(
-                        "Type",
-                        format!(
-                            "Could not apply operation {:?} on types {:?} and {:?}",
-                            op.kind, left_lit, right_lit
-                        ),
-                        op.position,
-                    );
-                    None
-                }
+            TokenKind::BangEqual => match left.equals(&right) {
+                Some(equal) => Ok(Value::Bool(!equal)),
+                None => Err(Expr::binary_type_error(op, &left, &right)),
             },
-            _ => unreachable!(),
+            _ => Err(LulaError::internal(
+                Message::new("unexpected-binary").debug_arg(&op.kind),
+                op.position,
+            )),
         }
     }
-
-    fn evaluate_grouping(&self) -> Option<Literal> {
-        let (_, expr, _) = match self {
-            Expr::Grouping(l, e, r) => (l, e, r),
-            _ => unreachable!(),
-        };
-
-        expr.evaluate()
-    }
 }
@@ -0,0 +1,56 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use crate::environment::{Environment, Scope};
+use crate::error::LulaError;
+use crate::statement::{Signal, Statement};
+use crate::value::Value;
+
+// Deep enough for ordinary recursion while staying well clear of the native stack limit
+pub const MAX_CALL_DEPTH: usize = 256;
+
+#[derive(Clone)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Rc<Vec<Statement>>,
+
+    // Scope the function was defined in, kept alive for as long as the function is
+    pub closure: Rc<RefCell<Scope>>,
+}
+
+impl Function {
+    pub fn arity(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn call(&self, args: Vec<Value>, env: &mut Environment) -> Result<Value, LulaError> {
+        let caller_scope = env.enter_call(&self.closure);
+
+        for (param, arg) in self.params.iter().zip(args) {
+            env.define(param.clone(), arg);
+        }
+
+        let signal = Statement::interpret_block(&self.body, env);
+        env.exit_call(caller_scope);
+
+        // Falling off the end of a function returns nil
+        match signal? {
+            Signal::Return(val) => Ok(val),
+            _ => Ok(Value::Nil),
+        }
+    }
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        // A function is only ever equal to itself
+        Rc::ptr_eq(&self.body, &other.body) && Rc::ptr_eq(&self.closure, &other.closure)
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The closure usually contains the function itself, so never print it
+        write!(f, "{:?}", self.name)
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+use crate::expr::Expr;
+use crate::json::quote;
+use crate::resolver::Resolution;
+use crate::statement::Statement;
+use crate::token::{Name, Position};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub label: String,
+
+    // Where the node is declared, for nodes standing for a declaration
+    pub position: Option<Position>,
+}
+
+// A directed graph between parts of a program, such as which functions call which
+#[derive(Debug, Clone, PartialEq)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+
+    // Pairs of node indices, sorted and without duplicates
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl Graph {
+    fn new(nodes: Vec<Node>, mut edges: Vec<(usize, usize)>) -> Self {
+        edges.sort_unstable();
+        edges.dedup();
+
+        Graph { nodes, edges }
+    }
+
+    pub fn to_dot(&self, name: &str) -> String {
+        let mut dot = format!("digraph {} {{\n", name);
+
+        for (id, node) in self.nodes.iter().enumerate() {
+            dot.push_str(&format!("    n{} [label={}];\n", id, quote(&node.label)));
+        }
+
+        for (from, to) in &self.edges {
+            dot.push_str(&format!("    n{} -> n{};\n", from, to));
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(id, node)| {
+                let line = match node.position {
+                    Some(position) => (position.0 + 1).to_string(),
+                    None => String::from("null"),
+                };
+
+                format!(
+                    "{{\"id\": {}, \"label\": {}, \"line\": {}}}",
+                    id,
+                    quote(&node.label),
+                    line
+                )
+            })
+            .collect();
+
+        let edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|(from, to)| format!("{{\"from\": {}, \"to\": {}}}", from, to))
+            .collect();
+
+        format!(
+            "{{\"nodes\": [{}], \"edges\": [{}]}}\n",
+            nodes.join(", "),
+            edges.join(", ")
+        )
+    }
+}
+
+struct CallWalker<'a> {
+    symbols: HashMap<Position, usize>,
+    resolution: &'a Resolution,
+
+    // Node 0 stands for the program's top-level code
+    nodes: Vec<Node>,
+    functions: HashMap<usize, usize>,
+    current: usize,
+
+    // Calls from a node to a symbol, which may turn out not to be a function
+    calls: Vec<(usize, usize)>,
+}
+
+// Builds a graph of which named functions call which. Calls made by top-level code come
+// from a '<main>' node, and those made by anonymous functions from the named function or
+// top-level code they appear in
+pub fn call_graph(statements: &[Statement], resolution: &Resolution) -> Graph {
+    let mut walker = CallWalker {
+        symbols: resolution
+            .occurrences
+            .iter()
+            .filter_map(|occ| Some((occ.position, occ.symbol?)))
+            .collect(),
+        resolution,
+        nodes: vec![Node {
+            label: String::from("<main>"),
+            position: None,
+        }],
+        functions: HashMap::new(),
+        current: 0,
+        calls: Vec::new(),
+    };
+
+    walker.statements(statements);
+
+    let edges = walker
+        .calls
+        .iter()
+        .filter_map(|(from, symbol)| Some((*from, *walker.functions.get(symbol)?)))
+        .collect();
+
+    Graph::new(walker.nodes, edges)
+}
+
+impl<'a> CallWalker<'a> {
+    fn function(&mut self, name: &Name, body: &[Statement]) {
+        let node = match self.symbols.get(&name.position) {
+            Some(symbol) => match self.functions.get(symbol) {
+                Some(node) => *node,
+                None => {
+                    self.nodes.push(Node {
+                        label: self.resolution.symbols[*symbol].name.clone(),
+                        position: Some(name.position),
+                    });
+                    self.functions.insert(*symbol, self.nodes.len() - 1);
+                    self.nodes.len() - 1
+                }
+            },
+            None => self.current,
+        };
+
+        let caller = self.current;
+        self.current = node;
+        self.statements(body);
+        self.current = caller;
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Print(expr) | Statement::Expr(expr) | Statement::Return(Some(expr)) => {
+                self.expr(expr)
+            }
+
+            // Functions stored straight into variables are named after them
+            Statement::VarDecl(name, Some(Expr::Func(_, body))) => self.function(name, body),
+            Statement::VarDecl(_, Some(expr)) => self.expr(expr),
+            Statement::VarDecl(_, None) => {}
+
+            Statement::If(_, condition, then_block, elif_blocks, else_block) => {
+                self.expr(condition);
+                self.statements(then_block);
+
+                for (_, cond, block) in elif_blocks {
+                    self.expr(cond);
+                    self.statements(block);
+                }
+
+                if let Some(block) = else_block {
+                    self.statements(block);
+                }
+            }
+
+            Statement::Block(block) | Statement::Loop(_, block) => self.statements(block),
+            Statement::While(_, expr, body) | Statement::For(_, _, expr, body) => {
+                self.expr(expr);
+                self.statements(body);
+            }
+
+            Statement::Break
+            | Statement::Continue
+            | Statement::Return(None)
+            | Statement::Error(..) => {}
+            Statement::Func(name, _, body) => self.function(name, body),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(..) | Expr::Variable(..) | Expr::Error(..) | Expr::Placeholder(..) => {}
+
+            Expr::Call(callee, _, args) => {
+                if let Expr::Variable(name) = &**callee {
+                    if let Some(symbol) = self.symbols.get(&name.position) {
+                        self.calls.push((self.current, *symbol));
+                    }
+                }
+
+                self.expr(callee);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            Expr::Method(receiver, _, _, args) => {
+                self.expr(receiver);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+
+            Expr::Assign(_, inner) | Expr::Unary(_, inner) | Expr::Grouping(_, inner, _) => {
+                self.expr(inner)
+            }
+            Expr::Binary(lhs, _, rhs)
+            | Expr::Logical(lhs, _, rhs)
+            | Expr::Range(lhs, _, rhs)
+            | Expr::Index(lhs, _, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            Expr::IndexAssign(target, _, index, value) => {
+                self.expr(target);
+                self.expr(index);
+                self.expr(value);
+            }
+            Expr::Conditional(condition, _, then_value, else_value) => {
+                self.expr(condition);
+                self.expr(then_value);
+                self.expr(else_value);
+            }
+
+            Expr::List(items) => items.iter().for_each(|item| self.expr(item)),
+            Expr::Map(entries) => {
+                for (key, _, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+
+            Expr::Func(_, body) => self.statements(body),
+        }
+    }
+}
+
+// Builds a graph of which files of a program use variables declared in which others.
+// Lula has no import statement, so this is what each file depends on being run with
+pub fn file_graph(resolution: &Resolution, paths: &[String]) -> Graph {
+    let nodes = paths
+        .iter()
+        .map(|path| Node {
+            label: path.clone(),
+            position: None,
+        })
+        .collect();
+
+    let edges = resolution
+        .occurrences
+        .iter()
+        .filter_map(|occ| {
+            let declaration = resolution.symbols[occ.symbol?].declaration;
+            Some((occ.position.2, declaration.2))
+        })
+        .filter(|(from, to)| from != to)
+        .collect();
+
+    Graph::new(nodes, edges)
+}
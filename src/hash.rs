@@ -0,0 +1,44 @@
+use std::hash::{Hash, Hasher};
+
+use crate::statement::Statement;
+
+// FNV-1a. Unlike the standard library's default hasher its output never changes between
+// Rust versions, runs or platforms, so hashes can be stored and compared later
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    // Lengths and enum discriminants are hashed at a fixed width so that 32 and 64-bit
+    // platforms agree
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64);
+    }
+}
+
+// Hashes the structure of a program. Positions, whitespace and comments don't affect the
+// result, so two sources differing only in layout hash the same
+pub fn hash_program(statements: &[Statement]) -> u64 {
+    let mut hasher = StableHasher::default();
+    statements.hash(&mut hasher);
+    hasher.finish()
+}
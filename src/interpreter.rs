@@ -0,0 +1,133 @@
+use std::rc::Rc;
+
+use crate::compiler::compile;
+use crate::environment::Environment;
+use crate::error::LulaError;
+use crate::native::define_natives;
+use crate::program::Program;
+use crate::semantics::Semantics;
+use crate::statement::Statement;
+use crate::value::Value;
+use crate::vm;
+
+// How an interpreter executes programs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    // Evaluates the syntax tree directly
+    Tree,
+    // Compiles programs to bytecode and runs them on a stack machine
+    Vm,
+}
+
+// Runs parsed programs. Globals outlive each call to 'run', so a program can be fed to
+// the same interpreter a piece at a time
+#[derive(Debug)]
+pub struct Interpreter {
+    env: Environment,
+    backend: Backend,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        let mut env = Environment::new();
+        define_natives(&mut env);
+
+        // Programs run without command-line arguments still get an empty list
+        env.define(String::from("args"), Value::list(Vec::new()));
+
+        Interpreter {
+            env,
+            backend: Backend::Tree,
+        }
+    }
+
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    // Sets the rules for what values can be used as conditions, which are lenient unless
+    // set otherwise
+    pub fn set_semantics(&mut self, semantics: Semantics) {
+        self.env.set_semantics(semantics);
+    }
+
+    // Sets how many loop iterations programs may run before being warned that they may be
+    // stuck in a loop that never ends, or turns the warning off
+    pub fn set_step_warning(&mut self, threshold: Option<u64>) {
+        self.env.steps().set_threshold(threshold);
+    }
+
+    // Sets what is done with warnings raised while programs run, which are otherwise
+    // dropped
+    pub fn set_warning_handler<F>(&mut self, handler: F)
+    where
+        F: FnMut(&LulaError) + 'static,
+    {
+        self.env.steps().set_warning_handler(Box::new(handler));
+    }
+
+    // How many statements have run, counting each time a statement in a loop or function
+    // body runs again
+    pub fn statements_run(&mut self) -> u64 {
+        self.env.steps().statements()
+    }
+
+    // Defines or replaces a global, letting the host pass values into programs
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.env.define(name.to_owned(), value);
+    }
+
+    // Defines a global that programs can neither assign to nor declare again, though they
+    // can still shadow it inside a block or function
+    pub fn define_constant(&mut self, name: &str, value: Value) {
+        self.env.define_constant(name.to_owned(), value);
+    }
+
+    // Makes every global defined so far a constant, builtins included, so that programs
+    // can't change the names the host provides. Programs can still declare new globals
+    pub fn freeze(&mut self) {
+        self.env.freeze_globals();
+    }
+
+    // Runs a program compiled ahead of time. The VM keeps the bytecode it compiles with the
+    // program, so running it again skips straight to executing it
+    pub fn run(&mut self, program: &Program) -> Result<Value, LulaError> {
+        if self.backend == Backend::Vm {
+            return vm::run(program.chunk()?, &mut self.env);
+        }
+
+        self.run_statements(program.statements())
+    }
+
+    // Runs statements in order, stopping at the first runtime error. Evaluates to the value
+    // of the last statement if it is an expression, and to nil otherwise
+    pub fn run_statements(&mut self, statements: &[Statement]) -> Result<Value, LulaError> {
+        if self.backend == Backend::Vm {
+            let chunk = compile(statements)?;
+            return vm::run(Rc::new(chunk), &mut self.env);
+        }
+
+        let mut result = Value::Nil;
+
+        for statement in statements {
+            result = match statement {
+                Statement::Expr(expr) => {
+                    self.env.steps().count_statement();
+                    expr.evaluate(&mut self.env)?
+                }
+                _ => {
+                    statement.interpret(&mut self.env)?;
+                    Value::Nil
+                }
+            };
+        }
+
+        Ok(result)
+    }
+}
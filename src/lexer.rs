@@ -1,48 +1,84 @@
-use crate::token::{Literal, Position, Token, TokenKind, KEYWORDS};
-
+use crate::error::LulaError;
+use crate::messages::Message;
+use crate::number::{parse_number, Number, NumberError};
+use crate::token::{keyword, Literal, Position, SourceId, Span, Token, TokenKind};
+use std::collections::{HashSet, VecDeque};
+use std::iter::Enumerate;
+use std::rc::Rc;
+
+// Lexes source on demand, as an iterator over its tokens. Errors are yielded as they are
+// found and lexing carries on past them, so every error in a source is reported
 pub struct Lexer {
-    source_path: String,
-    source: String,
+    // Decoded up front so that characters can be looked up by index in constant time
+    chars: Vec<char>,
     cursor: usize,
+
+    // Byte offset of the cursor, which token spans are measured in
+    offset: usize,
     position: Position,
 
     paren_stack: Vec<Position>,
     brace_stack: Vec<Position>,
     bracket_stack: Vec<Position>,
+
+    // Strings with an interpolation open, as the height of the brace stack once its '${'
+    // was pushed, along with where the string began
+    interpolations: Vec<(usize, Position)>,
+
+    // Errors found but not yet yielded
+    errors: VecDeque<LulaError>,
+
+    // Every identifier and string lexed so far, so that repeats can share their text
+    lexemmes: HashSet<Rc<str>>,
+
+    // The last token yielded, which decides whether a newline ends a statement
+    last_token: Option<TokenKind>,
+    finished: bool,
 }
 
 impl Lexer {
-    pub fn new(source_path: String, source: String) -> Lexer {
+    pub fn new(source: String) -> Lexer {
+        Lexer::for_source(source, 0)
+    }
+
+    // Creates a lexer whose tokens are marked as coming from the given source file
+    pub fn for_source(source: String, source_id: SourceId) -> Lexer {
         Lexer {
-            source_path,
-            source,
+            chars: source.chars().collect(),
             cursor: 0,
-            position: Position(0, 0),
+            offset: 0,
+            position: Position(0, 0, source_id),
 
             paren_stack: Vec::new(),
             brace_stack: Vec::new(),
             bracket_stack: Vec::new(),
+
+            interpolations: Vec::new(),
+
+            errors: VecDeque::new(),
+
+            lexemmes: HashSet::new(),
+
+            last_token: None,
+            finished: false,
         }
     }
 
-    fn display_error<S>(&self, message: S, position: Position)
-    where
-        S: Into<String>,
-    {
-        eprintln!(
-            "Lexing error in file '{}', {}:\n    {}.",
-            self.source_path,
-            position,
-            message.into()
-        );
+    fn error(&mut self, message: Message, position: Position) {
+        self.errors.push_back(LulaError::lex(message, position));
     }
 
     fn reached_end(&self) -> bool {
-        self.source.len() <= self.cursor
+        // The cursor counts characters, not bytes
+        self.chars.len() <= self.cursor
     }
 
     fn peek(&self) -> char {
-        self.source.chars().nth(self.cursor).unwrap_or('\0')
+        self.chars.get(self.cursor).copied().unwrap_or('\0')
+    }
+
+    fn peek_next(&self) -> char {
+        self.chars.get(self.cursor + 1).copied().unwrap_or('\0')
     }
 
     fn advance(&mut self) -> char {
@@ -57,8 +93,25 @@ impl Lexer {
         }
 
         // Advance cursor and return previous character
+        let c = self.chars.get(self.cursor).copied().unwrap_or('\0');
+
+        if !self.reached_end() {
+            self.offset += c.len_utf8();
+        }
+
         self.cursor += 1;
-        self.source.chars().nth(self.cursor - 1).unwrap_or('\0')
+        c
+    }
+
+    fn intern(&mut self, lexemme: String) -> Rc<str> {
+        match self.lexemmes.get(lexemme.as_str()) {
+            Some(interned) => Rc::clone(interned),
+            None => {
+                let interned: Rc<str> = Rc::from(lexemme);
+                self.lexemmes.insert(Rc::clone(&interned));
+                interned
+            }
+        }
     }
 
     fn skip_whitespace(&mut self) {
@@ -74,73 +127,137 @@ impl Lexer {
         }
     }
 
-    fn collect_identifier(&mut self) -> Option<Token> {
-        let mut lexemme = String::new();
+    // Skips whitespace and comments up to the next token or newline. Line comments run from
+    // '#' to the end of the line, and block comments from '#[' to ']#', nesting. Returns
+    // whether any block comment skipped spanned several lines
+    fn skip_trivia(&mut self) -> bool {
+        let mut crossed_lines = false;
+
+        loop {
+            self.skip_whitespace();
+
+            match (self.peek(), self.peek_next()) {
+                ('#', '[') => crossed_lines |= self.skip_block_comment(),
+                ('#', _) => self.skip_line(),
+                _ => return crossed_lines,
+            }
+        }
+    }
+
+    fn skip_block_comment(&mut self) -> bool {
         let start_pos = self.position;
+        let mut crossed_lines = false;
+        let mut depth = 0;
+
+        while !self.reached_end() {
+            match (self.peek(), self.peek_next()) {
+                ('#', '[') => {
+                    depth += 1;
+                    self.advance();
+                }
+                (']', '#') => {
+                    depth -= 1;
+                    self.advance();
+                }
+                ('\n', _) => crossed_lines = true,
+                _ => {}
+            }
+
+            self.advance();
+
+            if depth == 0 {
+                return crossed_lines;
+            }
+        }
+
+        self.error(Message::new("unterminated-comment"), start_pos);
+        crossed_lines
+    }
 
-        while !self.reached_end()
-            && (self.peek().is_ascii_alphabetic()
-                || self.peek().is_ascii_digit()
-                || self.peek() == '_')
-        {
+    fn collect_identifier(&mut self) -> Option<TokenKind> {
+        let mut lexemme = String::new();
+
+        while !self.reached_end() && is_identifier_continue(self.peek()) {
             lexemme.push(self.advance());
         }
 
         // Get token kind
-        let token_kind = match KEYWORDS.get(lexemme.as_str()) {
-            Some(t) => t.clone(),
-            None => TokenKind::Literal(Literal::Identifier(lexemme)),
-        };
-
-        Some(Token {
-            kind: token_kind,
-            position: start_pos,
-        })
+        match keyword(&lexemme) {
+            Some(t) => Some(t),
+            None => Some(TokenKind::Literal(Literal::Identifier(
+                self.intern(lexemme),
+            ))),
+        }
     }
 
-    fn collect_number(&mut self) -> Option<Token> {
+    fn collect_number(&mut self) -> Option<TokenKind> {
         let mut lexemme = String::new();
         let start_pos = self.position;
 
+        // In hexadecimal, binary and octal literals an 'e' is a digit rather than an exponent
+        let has_radix = self.peek() == '0' && "xXbBoO".contains(self.peek_next());
         let mut has_period = false;
 
-        while !self.reached_end()
-            && (self.peek().is_ascii_digit() || self.peek() == '.' && !has_period)
-        {
-            if self.peek() == '.' {
-                has_period = true;
+        // Take in any letters and digits following the number, so that malformed literals
+        // like '0b12' or '1e' are reported whole rather than split into several tokens
+        while !self.reached_end() {
+            let c = self.peek();
+            let has_exponent = !has_radix && lexemme.contains(['e', 'E']);
+
+            let accept = match c {
+                '_' => true,
+                c if c.is_ascii_alphanumeric() => true,
+
+                // Only treat a period as a decimal point if a digit follows it, so that ranges
+                // like '0..10' aren't swallowed by the number
+                '.' => {
+                    !has_radix && !has_period && !has_exponent && self.peek_next().is_ascii_digit()
+                }
+
+                '+' | '-' => has_exponent && lexemme.ends_with(['e', 'E']),
+                _ => false,
+            };
+
+            if !accept {
+                break;
             }
 
+            has_period |= c == '.';
             lexemme.push(self.advance());
         }
 
-        // Parse lexemme as f64
-        let value: f64 = match lexemme.parse() {
-            Ok(v) => v,
-            Err(..) => {
-                self.display_error(format!("Failed to parse number '{}'", lexemme), start_pos);
-                return None;
+        match parse_number(&lexemme) {
+            Ok(Number::Int(val)) => Some(TokenKind::Literal(Literal::Int(val))),
+            Ok(Number::Float(val)) => Some(TokenKind::Literal(Literal::Float(val))),
+            Err(err) => {
+                self.error(number_error_message(err, &lexemme), start_pos);
+                None
             }
-        };
-
-        Some(Token {
-            kind: TokenKind::Literal(Literal::Number(value)),
-            position: start_pos,
-        })
+        }
     }
 
-    fn collect_string(&mut self) -> Option<Token> {
+    fn collect_string(&mut self) -> Option<TokenKind> {
         let start_pos = self.position;
 
+        self.advance(); // Consume leading double-quote
+
+        self.collect_string_segment(start_pos)
+    }
+
+    // Reads a string up to its closing quote, or up to the next '${' beginning an
+    // interpolated expression
+    fn collect_string_segment(&mut self, start_pos: Position) -> Option<TokenKind> {
         let mut lexemme = String::new();
         let mut esc_pos = start_pos;
 
-        self.advance(); // Consume leading double-quote
-
         let mut escaped = false;
         let mut valid = true;
 
         while !self.reached_end() && self.peek() != '"' {
+            if !escaped && self.peek() == '$' && self.peek_next() == '{' {
+                return self.open_interpolation(start_pos, lexemme, valid);
+            }
+
             let curr_pos = self.position;
             let mut c = self.advance(); // Get next char in string
 
@@ -152,6 +269,7 @@ impl Lexer {
                     '\\' => '\\',
                     '\n' => '\n',
                     '"' => '"',
+                    '$' => '$',
 
                     'n' => '\n',
                     'r' => '\r',
@@ -159,11 +277,16 @@ impl Lexer {
 
                     '0' => '\0',
 
+                    'u' => match self.collect_unicode_escape(esc_pos) {
+                        Some(c) => c,
+                        None => {
+                            valid = false;
+                            '\0'
+                        }
+                    },
+
                     _ => {
-                        self.display_error(
-                            format!("Unrecognized escape sequence '\\{}'", c),
-                            esc_pos,
-                        );
+                        self.error(Message::new("unknown-escape").arg(c), esc_pos);
 
                         valid = false;
 
@@ -179,10 +302,7 @@ impl Lexer {
             } else {
                 // Disallow multi-line strings
                 if c == '\n' {
-                    self.display_error(
-                        "Encountered unexpected newline character while scanning string literal",
-                        curr_pos,
-                    );
+                    self.error(Message::new("newline-in-string"), curr_pos);
                     return None;
                 }
             }
@@ -196,26 +316,81 @@ impl Lexer {
         }
 
         if self.reached_end() {
-            self.display_error(
-                "Failed to locate closing double-quote for string literal",
-                start_pos,
-            );
+            self.error(Message::new("unterminated-string"), start_pos);
             return None;
         }
 
         self.advance(); // Consume trailing double-quote
 
         if valid {
-            Some(Token {
-                kind: TokenKind::Literal(Literal::String(lexemme)),
-                position: start_pos,
-            })
+            Some(TokenKind::Literal(Literal::String(self.intern(lexemme))))
         } else {
             None
         }
     }
 
-    fn collect_symbol(&mut self) -> Option<Token> {
+    fn open_interpolation(
+        &mut self,
+        start_pos: Position,
+        lexemme: String,
+        valid: bool,
+    ) -> Option<TokenKind> {
+        self.advance(); // Consume dollar sign
+
+        // The opening brace is matched like any other, so that braces inside the expression
+        // pair up with each other first
+        self.brace_stack.push(self.position);
+        self.interpolations
+            .push((self.brace_stack.len(), start_pos));
+
+        self.advance(); // Consume opening curly-brace
+
+        if valid {
+            Some(TokenKind::Interpolation(self.intern(lexemme)))
+        } else {
+            None
+        }
+    }
+
+    // Reads the '{XXXX}' following '\\u' in a string, where XXXX is the hexadecimal code
+    // point of a character
+    fn collect_unicode_escape(&mut self, esc_pos: Position) -> Option<char> {
+        if self.peek() != '{' {
+            self.error(Message::new("unicode-escape-brace"), esc_pos);
+            return None;
+        }
+
+        self.advance(); // Consume opening curly-brace
+
+        let mut digits = String::new();
+
+        while !self.reached_end() && self.peek().is_ascii_hexdigit() {
+            digits.push(self.advance());
+        }
+
+        if self.peek() != '}' {
+            self.error(Message::new("unicode-escape-digits"), esc_pos);
+            return None;
+        }
+
+        self.advance(); // Consume closing curly-brace
+
+        // Code points need at most six hexadecimal digits
+        let code = match digits.len() {
+            1..=6 => u32::from_str_radix(&digits, 16).ok(),
+            _ => None,
+        };
+
+        match code.and_then(char::from_u32) {
+            Some(c) => Some(c),
+            None => {
+                self.error(Message::new("invalid-unicode-escape").arg(digits), esc_pos);
+                None
+            }
+        }
+    }
+
+    fn collect_symbol(&mut self) -> Option<TokenKind> {
         let start_pos = self.position;
         let c = self.advance();
 
@@ -228,7 +403,7 @@ impl Lexer {
             ')' => match self.paren_stack.pop() {
                 Some(..) => TokenKind::RightParen,
                 None => {
-                    self.display_error("Unmatched right parenthesis", start_pos);
+                    self.error(Message::new("unmatched-right-paren"), start_pos);
                     return None;
                 }
             },
@@ -237,9 +412,16 @@ impl Lexer {
                 TokenKind::LeftBrace
             }
             '}' => match self.brace_stack.pop() {
-                Some(..) => TokenKind::RightBrace,
+                // A brace closing an interpolation carries on with the rest of its string
+                Some(..) => match self.interpolations.last() {
+                    Some(&(depth, string_pos)) if depth == self.brace_stack.len() + 1 => {
+                        self.interpolations.pop();
+                        return self.collect_string_segment(string_pos);
+                    }
+                    _ => TokenKind::RightBrace,
+                },
                 None => {
-                    self.display_error("Unmatched right curly-brace", start_pos);
+                    self.error(Message::new("unmatched-right-brace"), start_pos);
                     return None;
                 }
             },
@@ -250,18 +432,71 @@ impl Lexer {
             ']' => match self.bracket_stack.pop() {
                 Some(..) => TokenKind::RightBracket,
                 None => {
-                    self.display_error("Unmatched right square-bracket", start_pos);
+                    self.error(Message::new("unmatched-right-bracket"), start_pos);
                     return None;
                 }
             },
 
-            '+' => TokenKind::Plus,
-            '-' => TokenKind::Minus,
-            '*' => TokenKind::Star,
-            '/' => TokenKind::Slash,
-            '%' => TokenKind::Percent,
+            ',' => TokenKind::Comma,
+            ':' => TokenKind::Colon,
+            '&' => TokenKind::Ampersand,
+            '|' => TokenKind::Pipe,
+            '^' => TokenKind::Caret,
+            '~' => TokenKind::Tilde,
+            '?' => {
+                if self.peek() == '?' && self.peek_next() == '?' {
+                    self.advance();
+                    self.advance();
+                    TokenKind::Placeholder
+                } else {
+                    TokenKind::Question
+                }
+            }
 
             // Single and double character tokens
+            '+' => {
+                if self.peek() == '=' {
+                    self.advance();
+                    TokenKind::PlusEqual
+                } else {
+                    TokenKind::Plus
+                }
+            }
+            '-' => {
+                if self.peek() == '=' {
+                    self.advance();
+                    TokenKind::MinusEqual
+                } else {
+                    TokenKind::Minus
+                }
+            }
+            '*' => {
+                if self.peek() == '=' {
+                    self.advance();
+                    TokenKind::StarEqual
+                } else if self.peek() == '*' {
+                    self.advance();
+                    TokenKind::StarStar
+                } else {
+                    TokenKind::Star
+                }
+            }
+            '/' => {
+                if self.peek() == '=' {
+                    self.advance();
+                    TokenKind::SlashEqual
+                } else {
+                    TokenKind::Slash
+                }
+            }
+            '%' => {
+                if self.peek() == '=' {
+                    self.advance();
+                    TokenKind::PercentEqual
+                } else {
+                    TokenKind::Percent
+                }
+            }
             '=' => {
                 if self.peek() == '=' {
                     self.advance();
@@ -282,6 +517,9 @@ impl Lexer {
                 if self.peek() == '=' {
                     self.advance();
                     TokenKind::LessEqual
+                } else if self.peek() == '<' {
+                    self.advance();
+                    TokenKind::LessLess
                 } else {
                     TokenKind::Less
                 }
@@ -290,110 +528,309 @@ impl Lexer {
                 if self.peek() == '=' {
                     self.advance();
                     TokenKind::GreaterEqual
+                } else if self.peek() == '>' {
+                    self.advance();
+                    TokenKind::GreaterGreater
                 } else {
                     TokenKind::Greater
                 }
             }
+            '.' => {
+                if self.peek() == '.' {
+                    self.advance();
+                    TokenKind::DotDot
+                } else {
+                    TokenKind::Dot
+                }
+            }
 
             // Unrecognized character
             _ => {
-                self.display_error(format!("Encountered unrecognized symbol {}", c), start_pos);
+                self.error(Message::new("unrecognized-symbol").arg(c), start_pos);
                 return None;
             }
         };
 
-        Some(Token {
-            kind: token_kind,
-            position: start_pos,
-        })
+        Some(token_kind)
     }
 
-    pub fn collect_newline(&mut self, prev_token: Option<Token>) -> Option<Token> {
-        let start_pos = self.position;
-        self.advance();
-
-        match prev_token {
-            Some(t) => match t.kind {
-                TokenKind::RightParen
-                | TokenKind::RightBracket
-                | TokenKind::Literal(..)
-                | TokenKind::Break
-                | TokenKind::Continue => Some(Token {
-                    kind: TokenKind::Newline,
-                    position: start_pos,
-                }),
+    fn collect_newline(&mut self) -> Option<TokenKind> {
+        // Strings can't span lines, so neither can the expressions interpolated into them.
+        // Drop their braces so they aren't reported as unmatched too
+        if let Some(&(depth, _)) = self.interpolations.first() {
+            self.error(Message::new("newline-in-string"), self.position);
+            self.brace_stack.truncate(depth - 1);
+            self.interpolations.clear();
+        }
 
-                _ => None,
-            },
+        self.advance();
 
+        match &self.last_token {
+            Some(kind) if ends_statement(kind) => Some(TokenKind::Newline),
             _ => None,
         }
     }
 
-    pub fn collect_tokens(&mut self) -> Option<Vec<Token>> {
+    pub fn collect_tokens(&mut self) -> Result<Vec<Token>, Vec<LulaError>> {
         let mut tokens = Vec::new();
-        let mut contains_error = false;
+        let mut errors = Vec::new();
 
-        while !self.reached_end() {
-            // Skip whitespace
-            self.skip_whitespace();
+        for result in self {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(err) => errors.push(err),
+            }
+        }
+
+        // Return tokens if not errors were found
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn check_brackets(&mut self) {
+        for paren in std::mem::take(&mut self.paren_stack) {
+            self.error(Message::new("unmatched-left-paren"), paren);
+        }
+
+        for brace in std::mem::take(&mut self.brace_stack) {
+            self.error(Message::new("unmatched-left-brace"), brace);
+        }
+
+        for bracket in std::mem::take(&mut self.bracket_stack) {
+            self.error(Message::new("unmatched-left-bracket"), bracket);
+        }
+    }
+}
 
-            // Skip comment line
-            if self.peek() == '#' {
-                self.skip_line();
-                self.skip_whitespace();
+// Lexes the next token. The last token is always end-of-file, after any errors for
+// unmatched brackets
+impl Iterator for Lexer {
+    type Item = Result<Token, LulaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(err) = self.errors.pop_front() {
+                return Some(Err(err));
+            }
+
+            if self.finished {
+                return None;
+            }
+
+            if self.reached_end() {
+                self.check_brackets();
+
+                if let Some(err) = self.errors.pop_front() {
+                    return Some(Err(err));
+                }
+
+                self.finished = true;
+
+                return Some(Ok(Token {
+                    kind: TokenKind::Eof,
+                    position: self.position,
+                    span: Span {
+                        start: self.offset,
+                        end: self.offset,
+                    },
+                }));
+            }
+
+            let crossed_lines = self.skip_trivia();
+
+            // Trailing whitespace and comments leave nothing more to lex
+            if self.reached_end() {
+                continue;
+            }
+
+            // Block comments spanning several lines end statements just as newlines do
+            if crossed_lines && self.last_token.as_ref().is_some_and(ends_statement) {
+                self.last_token = Some(TokenKind::Newline);
+
+                return Some(Ok(Token {
+                    kind: TokenKind::Newline,
+                    position: self.position,
+                    span: Span {
+                        start: self.offset,
+                        end: self.offset,
+                    },
+                }));
             }
 
             // Collect token by type
+            let position = self.position;
+            let start = self.offset;
             let c = self.peek();
-            let token = match c {
-                'a'..='z' | 'A'..='Z' => self.collect_identifier(),
+            let kind = match c {
                 '0'..='9' => self.collect_number(),
+                c if is_identifier_start(c) => self.collect_identifier(),
                 '"' => self.collect_string(),
 
                 // Exeptionally ignore None case from 'collect_newline' as this may intentionally
                 // refuse to add a newline token based on the previous token
-                '\n' => match self.collect_newline(tokens.last().cloned()) {
-                    Some(t) => Some(t),
+                '\n' => match self.collect_newline() {
+                    Some(kind) => Some(kind),
                     None => continue,
                 },
 
                 _ => self.collect_symbol(),
             };
 
-            match token {
-                Some(t) => tokens.push(t),
-                None => contains_error = true,
+            // Errors have already been recorded if no token was produced
+            if let Some(kind) = kind {
+                self.last_token = Some(kind.clone());
+
+                return Some(Ok(Token {
+                    kind,
+                    position,
+                    span: Span {
+                        start,
+                        end: self.offset,
+                    },
+                }));
             }
         }
+    }
+}
 
-        // Check for unmatched brackets
-        for paren in self.paren_stack.clone() {
-            self.display_error("Unmatched left parenthesis", paren);
-            contains_error = true;
-        }
+// Whether a character can begin an identifier. This follows Unicode's XID_Start as closely
+// as the standard library allows, accepting any alphabetic character
+pub fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic()
+}
 
-        for brace in self.brace_stack.clone() {
-            self.display_error("Unmatched left curly-brace", brace);
-            contains_error = true;
-        }
+// Whether a character can appear in an identifier after its first, following XID_Continue
+pub fn is_identifier_continue(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || is_combining_mark(c)
+}
+
+// Marks such as accents written as a separate character after the letter they modify. The
+// standard library counts some of these as alphabetic but not the common ones below
+fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036f}'
+            | '\u{1ab0}'..='\u{1aff}'
+            | '\u{1dc0}'..='\u{1dff}'
+            | '\u{20d0}'..='\u{20ff}'
+            | '\u{fe20}'..='\u{fe2f}'
+    )
+}
+
+fn number_error_message(err: NumberError, lexemme: &str) -> Message {
+    let base = |radix| match radix {
+        2 => "word.binary",
+        8 => "word.octal",
+        16 => "word.hexadecimal",
+        _ => "word.decimal",
+    };
+
+    match err {
+        NumberError::MissingDigits(radix) => Message::new("missing-digits")
+            .word(base(radix))
+            .arg(&lexemme[..2]),
+        NumberError::InvalidDigit(c, radix) => Message::new("invalid-digit")
+            .arg(c)
+            .word(base(radix))
+            .arg(lexemme),
+        NumberError::MissingExponent => Message::new("missing-exponent").arg(lexemme),
+        NumberError::MisplacedUnderscore => Message::new("misplaced-underscore").arg(lexemme),
+        NumberError::TooLarge => Message::new("number-too-large").arg(lexemme),
+        NumberError::Malformed => Message::new("invalid-number").arg(lexemme),
+    }
+}
+
+// Whether a newline after a token of this kind terminates a statement
+fn ends_statement(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::RightParen
+            | TokenKind::RightBracket
+            | TokenKind::Literal(..)
+            | TokenKind::Placeholder
+            | TokenKind::Break
+            | TokenKind::Continue
+            | TokenKind::Return
+    )
+}
+
+// Lexes several sources one after another into a single token stream, as if they were one
+// file. The source id of each token's position is the index of the source it came from
+pub struct SourceTokens {
+    sources: Enumerate<std::vec::IntoIter<String>>,
+    lexer: Option<Lexer>,
+
+    // Whether the last token of the current source would need a newline to end its
+    // statement
+    ends_statement: bool,
+}
+
+pub fn source_tokens(sources: Vec<String>) -> SourceTokens {
+    let mut sources = sources.into_iter().enumerate();
+    let lexer = sources
+        .next()
+        .map(|(source_id, source)| Lexer::for_source(source, source_id));
+
+    SourceTokens {
+        sources,
+        lexer,
+        ends_statement: false,
+    }
+}
+
+impl Iterator for SourceTokens {
+    type Item = Result<Token, LulaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let token = match self.lexer.as_mut()?.next()? {
+                Ok(token) => token,
+                Err(err) => return Some(Err(err)),
+            };
+
+            if token.kind != TokenKind::Eof {
+                self.ends_statement = ends_statement(&token.kind);
+                return Some(Ok(token));
+            }
 
-        for bracket in self.bracket_stack.clone() {
-            self.display_error("Unmatched left square-bracket", bracket);
-            contains_error = true;
+            // Only the last source's end-of-file token is kept. Terminate each source's
+            // last statement with a newline where needed, so that it can't run into the next
+            match self.sources.next() {
+                Some((source_id, source)) => {
+                    self.lexer = Some(Lexer::for_source(source, source_id));
+
+                    if std::mem::take(&mut self.ends_statement) {
+                        return Some(Ok(Token {
+                            kind: TokenKind::Newline,
+                            position: token.position,
+                            span: token.span,
+                        }));
+                    }
+                }
+                None => return Some(Ok(token)),
+            }
         }
+    }
+}
 
-        // Append end-of-file token to vector
-        tokens.push(Token {
-            kind: TokenKind::Eof,
-            position: self.position,
-        });
+// Lexes several sources into a single list of tokens, or every error found in them
+pub fn collect_source_tokens(sources: Vec<String>) -> Result<Vec<Token>, Vec<LulaError>> {
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
 
-        // Return tokens if not errors were found
-        if !contains_error {
-            Some(tokens)
-        } else {
-            None
+    for result in source_tokens(sources) {
+        match result {
+            Ok(token) => tokens.push(token),
+            Err(err) => errors.push(err),
         }
     }
+
+    if errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(errors)
+    }
 }
@@ -0,0 +1,50 @@
+pub mod compiler;
+pub mod dump;
+pub mod environment;
+pub mod error;
+pub mod expr;
+pub mod function;
+#[cfg(feature = "tooling")]
+pub mod graph;
+pub mod hash;
+pub mod interpreter;
+pub mod json;
+pub mod lexer;
+pub mod messages;
+#[cfg(feature = "tooling")]
+pub mod metrics;
+pub mod native;
+pub mod number;
+pub mod optimize;
+pub mod parser;
+pub mod program;
+#[cfg(feature = "tooling")]
+pub mod refactor;
+#[cfg(feature = "tooling")]
+pub mod resolver;
+#[cfg(feature = "tooling")]
+pub mod rewrite;
+pub mod semantics;
+pub mod spell;
+pub mod statement;
+pub mod steps;
+pub mod token;
+pub mod value;
+pub mod vm;
+
+pub use error::LulaError;
+pub use interpreter::Interpreter;
+pub use lexer::Lexer;
+pub use parser::Parser;
+pub use program::Program;
+pub use value::Value;
+
+// Lexes, parses and runs a complete program, returning the value of its final expression
+pub fn eval(source: &str) -> Result<Value, Vec<LulaError>> {
+    let mut parser = Parser::new(Lexer::new(source.to_owned()));
+    let statements = parser.collect_statements()?;
+
+    Interpreter::new()
+        .run_statements(&statements)
+        .map_err(|err| vec![err])
+}
@@ -1,75 +1,444 @@
-#[macro_use]
-extern crate lazy_static;
+use std::{env, panic, process, thread};
 
-use std::{env, fs::File, io::Read};
+use driver::{
+    check_file, explain, hash_file, print_summary, run_files, watch_file, AstFormat, RunError,
+    RunOptions,
+};
+use lula::{
+    error,
+    interpreter::Backend,
+    messages::{set_explain_errors, set_language, Language},
+    semantics::Semantics,
+};
 
-use lexer::Lexer;
-use parser::Parser;
+#[cfg(feature = "tooling")]
+use driver::{extract_in_file, graph_files, metrics_file, rename_in_file, GraphKind};
+#[cfg(feature = "tooling")]
+use lula::metrics::Thresholds;
 
-pub mod error;
-pub mod expr;
-pub mod lexer;
-pub mod parser;
-pub mod statement;
-pub mod token;
+mod driver;
+
+// Environment variable choosing the language diagnostics are shown in, unless '--lang' is
+// given
+const LANGUAGE_VAR: &str = "LULA_LANG";
+
+// The tree-walking interpreter recurses once per nested expression, statement and call,
+// so give it far more room than the main thread's default stack
+const INTERPRETER_STACK_SIZE: usize = 64 * 1024 * 1024;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    // Any panic past this point is an interpreter bug, so report it as an internal error
+    // rather than letting malformed input crash the process with a raw backtrace
+    panic::set_hook(Box::new(|info| {
+        eprintln!(
+            "Internal compiler error: {}\n~ this is a bug in Lula, please file a report at {}",
+            info,
+            error::BUG_REPORT_URL
+        );
+    }));
+
+    let interpreter = match thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(run)
+    {
+        Ok(handle) => handle,
+        Err(..) => {
+            eprintln!("Fatal error: Failed to start interpreter thread");
+            process::exit(RunError::Io.exit_code());
+        }
+    };
+
+    match interpreter.join() {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => process::exit(err.exit_code()),
 
-    // Check for input file
-    if args.len() < 2 {
-        eprintln!("Fatal error: No input file provided");
-        return;
+        // Joining fails if the interpreter thread panicked
+        Err(..) => process::exit(101),
     }
+}
+
+fn run() -> Result<(), RunError> {
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    let in_file_path = args.get(1).unwrap();
+    // Options for running a script come before its path
+    let (options, args) = parse_run_options(&args)?;
 
-    // Check if input file uses the '.lla' file extension
-    if !in_file_path.ends_with(".lla") {
-        eprintln!("Fatal error: Input file does not use the '.lla' file extension");
-        return;
+    if let Ok(code) = env::var(LANGUAGE_VAR) {
+        set_language(parse_language(&code)?);
     }
 
-    // Attempt to open input file
-    let mut in_file = match File::open(in_file_path) {
-        Ok(file) => file,
-        Err(..) => {
+    let result = match args.first().map(String::as_str) {
+        Some("check") => run_check(&args[1..]),
+        Some("hash") => run_hash(&args[1..]),
+        #[cfg(feature = "tooling")]
+        Some("rename") => run_rename(&args[1..]),
+        #[cfg(feature = "tooling")]
+        Some("extract") => run_extract(&args[1..]),
+        #[cfg(feature = "tooling")]
+        Some("graph") => run_graph(&args[1..]),
+        #[cfg(feature = "tooling")]
+        Some("metrics") => run_metrics(&args[1..]),
+        Some("explain") => run_explain(&args[1..]),
+
+        // Builds without the 'tooling' feature leave these commands out, but still know
+        // them, rather than mistaking them for script paths
+        #[cfg(not(feature = "tooling"))]
+        Some(command @ ("rename" | "extract" | "graph" | "metrics")) => {
             eprintln!(
-                "Fatal error: Failed to open file '{}'\n~ are you sure this file exists?",
-                in_file_path
+                "Fatal error: The '{}' command isn't available in this build of Lula",
+                command
             );
-            return;
+            Err(RunError::Usage)
+        }
+        Some(..) => {
+            // The script path may be followed by more '.lla' files, which are run after it
+            // as part of the same program
+            let file_count = 1 + args[1..]
+                .iter()
+                .take_while(|arg| arg.ends_with(".lla"))
+                .count();
+
+            run_files(
+                &args[..file_count],
+                script_args(&args[file_count..]),
+                options,
+            )
+        }
+
+        // Check for input file
+        None => {
+            eprintln!("Fatal error: No input file provided");
+            Err(RunError::Usage)
         }
     };
 
-    // Read file contents into string
-    let mut in_file_contents = String::new();
+    // Fatal errors stop a command before it gets to check or run anything, so there is
+    // nothing to sum up
+    if !options.quiet && !matches!(result, Err(RunError::Usage) | Err(RunError::Io)) {
+        print_summary();
+    }
+
+    result
+}
+
+// Takes '--backend=<name>', '--optimize', '--no-opt', '--strict', '--warn-steps=<n>',
+// '--dump-ast[=<format>]', '--quiet', '--lang=<code>' and '--explain-errors' from the front
+// of the arguments, returning the options and whatever follows them. Folding is on unless
+// turned off, and of '--optimize' and '--no-opt' the last given wins. The last two change
+// how every command shows diagnostics, so are set straight away
+fn parse_run_options(mut args: &[String]) -> Result<(RunOptions, &[String]), RunError> {
+    let mut options = RunOptions::default();
+
+    while let Some((flag, rest)) = args.split_first() {
+        if let Some(name) = flag.strip_prefix("--backend=") {
+            options.backend = parse_backend(name)?;
+        } else if flag == "--optimize" {
+            options.optimize = true;
+        } else if flag == "--no-opt" {
+            options.optimize = false;
+        } else if flag == "--strict" {
+            options.semantics = Semantics::Strict;
+        } else if let Some(steps) = flag.strip_prefix("--warn-steps=") {
+            options.step_warning = parse_step_warning(steps)?;
+        } else if flag == "--dump-ast" {
+            options.dump_ast = Some(AstFormat::Tree);
+        } else if let Some(format) = flag.strip_prefix("--dump-ast=") {
+            options.dump_ast = Some(parse_ast_format(format)?);
+        } else if flag == "--quiet" {
+            options.quiet = true;
+        } else if let Some(code) = flag.strip_prefix("--lang=") {
+            set_language(parse_language(code)?);
+        } else if flag == "--explain-errors" {
+            set_explain_errors(true);
+        } else {
+            break;
+        }
 
-    if in_file.read_to_string(&mut in_file_contents).is_err() {
-        eprintln!("Fatal error: Failed to read file contents into string");
-        return;
+        args = rest;
     }
 
-    // Lex file contents into a vector of tokens
-    let mut lexer = Lexer::new(in_file_path.to_owned(), in_file_contents);
-    let tokens = match lexer.collect_tokens() {
-        Some(t) => t,
-        None => return,
-    };
+    Ok((options, args))
+}
 
-    // Parse tokens into a vector of statements
-    let mut parser = Parser::new(in_file_path.to_owned(), tokens);
+fn parse_backend(name: &str) -> Result<Backend, RunError> {
+    match name {
+        "tree" => Ok(Backend::Tree),
+        "vm" => Ok(Backend::Vm),
+
+        _ => {
+            eprintln!(
+                "Fatal error: Unknown backend '{}', expected 'tree' or 'vm'",
+                name
+            );
+            Err(RunError::Usage)
+        }
+    }
+}
+
+fn parse_ast_format(name: &str) -> Result<AstFormat, RunError> {
+    match name {
+        "tree" => Ok(AstFormat::Tree),
+        "sexp" => Ok(AstFormat::Sexp),
+        "json" => Ok(AstFormat::Json),
+
+        _ => {
+            eprintln!(
+                "Fatal error: Unknown AST format '{}', expected 'tree', 'sexp' or 'json'",
+                name
+            );
+            Err(RunError::Usage)
+        }
+    }
+}
+
+// A step count of 0 turns the warning off
+fn parse_step_warning(steps: &str) -> Result<Option<u64>, RunError> {
+    match steps.parse() {
+        Ok(0) => Ok(None),
+        Ok(steps) => Ok(Some(steps)),
+
+        Err(..) => {
+            eprintln!(
+                "Fatal error: Expected a number for '--warn-steps', found '{}'",
+                steps
+            );
+            Err(RunError::Usage)
+        }
+    }
+}
+
+fn parse_language(code: &str) -> Result<Language, RunError> {
+    match Language::from_code(code) {
+        Some(language) => Ok(language),
+
+        None => {
+            let codes: Vec<&str> = Language::ALL.iter().map(|l| l.code()).collect();
+
+            eprintln!(
+                "Fatal error: Unknown language '{}', expected one of: {}",
+                code,
+                codes.join(", ")
+            );
+            Err(RunError::Usage)
+        }
+    }
+}
+
+// Everything after the script's files is passed on to the script, optionally separated from it
+// by '--'
+fn script_args(args: &[String]) -> &[String] {
+    match args.split_first() {
+        Some((separator, rest)) if separator == "--" => rest,
+        _ => args,
+    }
+}
 
-    let statements = match parser.collect_statements() {
-        Some(s) => s,
-        None => return,
+// Handles 'check [--watch] [--spell] <file>', which reports errors in a file without running
+// it, and with '--spell' likely typos in its strings and comments
+fn run_check(args: &[String]) -> Result<(), RunError> {
+    let mut watch = false;
+    let mut spell = false;
+
+    let in_file_path = match args.split_last() {
+        Some((in_file_path, flags)) if !in_file_path.starts_with("--") => {
+            for flag in flags {
+                match flag.as_str() {
+                    "--watch" => watch = true,
+                    "--spell" => spell = true,
+
+                    _ => {
+                        eprintln!("Fatal error: Unknown check option '{}'", flag);
+                        return Err(RunError::Usage);
+                    }
+                }
+            }
+
+            in_file_path
+        }
+
+        _ => {
+            eprintln!("Fatal error: Expected 'check [--watch] [--spell] <file>'");
+            return Err(RunError::Usage);
+        }
     };
 
-    // Interpret statements sequentially
-    for statement in statements {
-        if !statement.interpret() {
-            // Return if a runtime error occurs
-            return;
+    if watch {
+        watch_file(in_file_path, spell)
+    } else {
+        check_file(in_file_path, spell)
+    }
+}
+
+// Handles 'explain <code>', which describes a class of error with examples, as
+// '--explain-errors' does after each diagnostic
+fn run_explain(args: &[String]) -> Result<(), RunError> {
+    match args {
+        [code] => explain(code),
+
+        _ => {
+            eprintln!("Fatal error: Expected 'explain <code>'");
+            Err(RunError::Usage)
+        }
+    }
+}
+
+// Handles 'hash <file>', which prints a hash of the file's syntax tree
+fn run_hash(args: &[String]) -> Result<(), RunError> {
+    match args {
+        [in_file_path] => hash_file(in_file_path),
+
+        _ => {
+            eprintln!("Fatal error: Expected 'hash <file>'");
+            Err(RunError::Usage)
+        }
+    }
+}
+
+// Parses a 'line:column' position, both counted from 1
+#[cfg(feature = "tooling")]
+fn parse_position(position: &str) -> Option<(usize, usize)> {
+    let (line, column) = position.split_once(':')?;
+    Some((line.parse().ok()?, column.parse().ok()?))
+}
+
+// Handles 'metrics <file> [--max-complexity=N] [--max-statements=N] [--max-nesting=N]
+// [--min-duplicate=N]', which measures each function and warns about any over the limits
+#[cfg(feature = "tooling")]
+fn run_metrics(args: &[String]) -> Result<(), RunError> {
+    let mut thresholds = Thresholds::default();
+    let mut in_file_paths = Vec::new();
+
+    for arg in args {
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, value),
+            _ if arg.starts_with("--") => (arg.as_str(), ""),
+
+            _ => {
+                in_file_paths.push(arg);
+                continue;
+            }
+        };
+
+        let threshold = match flag {
+            "--max-complexity" => &mut thresholds.complexity,
+            "--max-statements" => &mut thresholds.statements,
+            "--max-nesting" => &mut thresholds.nesting,
+            "--min-duplicate" => &mut thresholds.duplicate_statements,
+
+            _ => {
+                eprintln!("Fatal error: Unknown metrics option '{}'", flag);
+                return Err(RunError::Usage);
+            }
+        };
+
+        *threshold = match value.parse() {
+            Ok(value) => value,
+            Err(..) => {
+                eprintln!(
+                    "Fatal error: Expected a number for '{}', found '{}'",
+                    flag, value
+                );
+                return Err(RunError::Usage);
+            }
+        };
+    }
+
+    match in_file_paths[..] {
+        [in_file_path] => metrics_file(in_file_path, &thresholds),
+
+        _ => {
+            eprintln!("Fatal error: Expected 'metrics <file> [--max-complexity=N] [--max-statements=N] [--max-nesting=N] [--min-duplicate=N]'");
+            Err(RunError::Usage)
+        }
+    }
+}
+
+// Handles 'graph <files...> [--calls|--imports] [--dot|--json]', which prints a graph of
+// function calls or of dependencies between files
+#[cfg(feature = "tooling")]
+fn run_graph(args: &[String]) -> Result<(), RunError> {
+    let mut kind = GraphKind::Calls;
+    let mut dot = false;
+    let mut in_file_paths = Vec::new();
+
+    for arg in args {
+        match arg.as_str() {
+            "--calls" => kind = GraphKind::Calls,
+            "--imports" => kind = GraphKind::Files,
+            "--dot" => dot = true,
+            "--json" => dot = false,
+
+            flag if flag.starts_with("--") => {
+                eprintln!("Fatal error: Unknown graph option '{}'", flag);
+                return Err(RunError::Usage);
+            }
+
+            _ => in_file_paths.push(arg.clone()),
+        }
+    }
+
+    if in_file_paths.is_empty() {
+        eprintln!("Fatal error: Expected 'graph <files...> [--calls|--imports] [--dot|--json]'");
+        return Err(RunError::Usage);
+    }
+
+    graph_files(&in_file_paths, kind, dot)
+}
+
+// Handles 'extract <file> --lines <first>-<last> --name <name>', which moves whole
+// statements into a new function and calls it in their place
+#[cfg(feature = "tooling")]
+fn run_extract(args: &[String]) -> Result<(), RunError> {
+    match args {
+        [in_file_path, lines, range, name_flag, name]
+            if lines == "--lines" && name_flag == "--name" =>
+        {
+            let range = range
+                .split_once('-')
+                .and_then(|(first, last)| Some((first.parse().ok()?, last.parse().ok()?)));
+
+            match range {
+                Some((first, last)) => extract_in_file(in_file_path, first, last, name),
+                None => {
+                    eprintln!(
+                        "Fatal error: Expected a line range like '3-7', found '{}'",
+                        args[2]
+                    );
+                    Err(RunError::Usage)
+                }
+            }
+        }
+
+        _ => {
+            eprintln!(
+                "Fatal error: Expected 'extract <file> --lines <first>-<last> --name <name>'"
+            );
+            Err(RunError::Usage)
+        }
+    }
+}
+
+// Handles 'rename <file> --at <line>:<column> --to <name>', which renames the variable at
+// a position everywhere it is used
+#[cfg(feature = "tooling")]
+fn run_rename(args: &[String]) -> Result<(), RunError> {
+    match args {
+        [in_file_path, at, position, to, new_name] if at == "--at" && to == "--to" => {
+            match parse_position(position) {
+                Some((line, column)) => rename_in_file(in_file_path, line, column, new_name),
+                None => {
+                    eprintln!(
+                        "Fatal error: Expected a position like '12:5', found '{}'",
+                        position
+                    );
+                    Err(RunError::Usage)
+                }
+            }
+        }
+
+        _ => {
+            eprintln!("Fatal error: Expected 'rename <file> --at <line>:<column> --to <name>'");
+            Err(RunError::Usage)
         }
     }
 }
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+// Catalogs of diagnostic text, one per language. Each line maps a key to a template, where
+// '{0}', '{1}' and so on stand for the message's arguments. Messages are keyed by their
+// error code and a name, such as 'E0302.undefined-variable', and a bare error code names
+// the class of error. Lines starting with '|' continue the template before them on a new
+// line, for the longer explanations of each error. Keys missing from a translation fall
+// back to English
+const ENGLISH: &str = include_str!("messages/en.txt");
+const SPANISH: &str = include_str!("messages/es.txt");
+
+static LANGUAGE: OnceLock<Language> = OnceLock::new();
+static EXPLAIN_ERRORS: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Spanish];
+
+    // Reads a language code such as 'es', ignoring any region or encoding after it, so
+    // that locale names such as 'es_MX.UTF-8' work too
+    pub fn from_code(code: &str) -> Option<Language> {
+        let code = code.split(['_', '-', '.']).next().unwrap_or_default();
+
+        Language::ALL
+            .iter()
+            .copied()
+            .find(|language| language.code().eq_ignore_ascii_case(code))
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Spanish => "es",
+        }
+    }
+
+    pub fn catalog(self) -> &'static str {
+        match self {
+            Language::English => ENGLISH,
+            Language::Spanish => SPANISH,
+        }
+    }
+
+    fn templates(self) -> &'static HashMap<&'static str, String> {
+        static ENGLISH_TEMPLATES: OnceLock<HashMap<&str, String>> = OnceLock::new();
+        static SPANISH_TEMPLATES: OnceLock<HashMap<&str, String>> = OnceLock::new();
+
+        let templates = match self {
+            Language::English => &ENGLISH_TEMPLATES,
+            Language::Spanish => &SPANISH_TEMPLATES,
+        };
+
+        templates.get_or_init(|| parse_catalog(self.catalog()))
+    }
+
+    // Looks up the template for a key, in English if this language has no translation
+    pub fn template(self, key: &str) -> Option<&'static str> {
+        self.templates()
+            .get(key)
+            .or_else(|| Language::English.templates().get(key))
+            .map(String::as_str)
+    }
+
+    // A beginner-friendly explanation of an error code, or of one of its messages if given
+    // as a key like 'E0302.undeclared-assignment', with examples of wrong and right code
+    pub fn explanation(self, key: &str) -> Option<&'static str> {
+        self.template(&format!("explain.{}", key)).or_else(|| {
+            let (code, _) = key.split_once('.')?;
+            self.template(&format!("explain.{}", code))
+        })
+    }
+}
+
+// Sets the language diagnostics are rendered in for the rest of the process. Only the first
+// call has any effect
+pub fn set_language(language: Language) {
+    let _ = LANGUAGE.set(language);
+}
+
+pub fn language() -> Language {
+    LANGUAGE.get().copied().unwrap_or(Language::English)
+}
+
+// Sets whether rendered diagnostics are followed by an explanation of the error, for people
+// learning to program
+pub fn set_explain_errors(explain: bool) {
+    EXPLAIN_ERRORS.store(explain, Ordering::Relaxed);
+}
+
+pub fn explain_errors() -> bool {
+    EXPLAIN_ERRORS.load(Ordering::Relaxed)
+}
+
+// Maps each key in a catalog to its template
+pub fn parse_catalog(catalog: &str) -> HashMap<&str, String> {
+    let mut templates: HashMap<&str, String> = HashMap::new();
+    let mut last_key = None;
+
+    for line in catalog.lines().map(str::trim) {
+        if let Some(continued) = line.strip_prefix('|') {
+            // Continuation lines keep their indentation after the '| '
+            let continued = continued.strip_prefix(' ').unwrap_or(continued);
+
+            if let Some(template) = last_key.and_then(|key| templates.get_mut(key)) {
+                template.push('\n');
+                template.push_str(continued.trim_end());
+            }
+        } else if let Some((key, template)) = line.split_once('=') {
+            if !line.starts_with('#') {
+                let key = key.trim();
+                templates.insert(key, template.trim().to_owned());
+                last_key = Some(key);
+            }
+        }
+    }
+
+    templates
+}
+
+// Fills a template's '{N}' placeholders in with arguments. Braces that don't surround an
+// argument number are kept as they are
+pub fn fill(template: &str, args: &[String]) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(open) = rest.find('{') {
+        text.push_str(&rest[..open]);
+        rest = &rest[open..];
+
+        let placeholder = rest[1..]
+            .find('}')
+            .and_then(|close| Some((close + 2, rest[1..=close].parse::<usize>().ok()?)));
+
+        match placeholder {
+            Some((len, index)) => {
+                text.push_str(args.get(index).map_or("", String::as_str));
+                rest = &rest[len..];
+            }
+            None => {
+                text.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    text.push_str(rest);
+    text
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Arg {
+    Text(String),
+
+    // Catalog key of a word or phrase, such as 'word.list', translated along with the
+    // message it appears in
+    Word(&'static str),
+}
+
+// A diagnostic message, kept as a catalog key and arguments until it is rendered so that it
+// can be shown in any language
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub key: &'static str,
+    args: Vec<Arg>,
+}
+
+impl Message {
+    pub fn new(key: &'static str) -> Self {
+        Message {
+            key,
+            args: Vec::new(),
+        }
+    }
+
+    pub fn arg<T: Display>(mut self, arg: T) -> Self {
+        self.args.push(Arg::Text(arg.to_string()));
+        self
+    }
+
+    // Adds an argument shown as the interpreter's debug representation, as tokens and values
+    // are in most messages
+    pub fn debug_arg<T: Debug>(mut self, arg: T) -> Self {
+        self.args.push(Arg::Text(format!("{:?}", arg)));
+        self
+    }
+
+    pub fn word(mut self, key: &'static str) -> Self {
+        self.args.push(Arg::Word(key));
+        self
+    }
+
+    // Renders the message for an error code in a language
+    pub fn render(&self, code: &str, language: Language) -> String {
+        let args: Vec<String> = self
+            .args
+            .iter()
+            .map(|arg| match arg {
+                Arg::Text(text) => text.clone(),
+                Arg::Word(key) => language.template(key).unwrap_or(key).to_owned(),
+            })
+            .collect();
+
+        match language.template(&format!("{}.{}", code, self.key)) {
+            Some(template) => fill(template, &args),
+
+            // Every key should be in the English catalog, but show what we can if not
+            None => format!("{} ({})", self.key, args.join(", ")),
+        }
+    }
+}
@@ -0,0 +1,486 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::mem::discriminant;
+
+use crate::expr::Expr;
+use crate::hash::StableHasher;
+use crate::statement::Statement;
+use crate::token::{Name, Position, Token, TokenKind};
+
+// Measurements of a named function. Anonymous functions count towards the function they
+// appear in
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionMetrics {
+    pub name: String,
+    pub position: Position,
+
+    // One more than the number of branches, loops and short-circuiting operators
+    pub complexity: usize,
+
+    // Statements in the body, counting those nested inside others
+    pub statements: usize,
+
+    // How many blocks deep the most deeply nested statement is
+    pub nesting: usize,
+}
+
+// Limits past which measurements are reported as warnings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    pub complexity: usize,
+    pub statements: usize,
+    pub nesting: usize,
+
+    // Smaller blocks are too common to be worth reporting as duplicates
+    pub duplicate_statements: usize,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            complexity: 10,
+            statements: 40,
+            nesting: 4,
+            duplicate_statements: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metrics {
+    pub functions: Vec<FunctionMetrics>,
+
+    // Where each set of blocks differing only in names and literal values starts
+    pub duplicates: Vec<Vec<Position>>,
+}
+
+impl Metrics {
+    // Describes every measurement over its threshold, along with where it was found
+    pub fn warnings(&self, thresholds: &Thresholds) -> Vec<(Position, String)> {
+        let mut warnings = Vec::new();
+
+        for function in &self.functions {
+            let limits = [
+                ("complexity", function.complexity, thresholds.complexity),
+                ("statements", function.statements, thresholds.statements),
+                ("nesting depth", function.nesting, thresholds.nesting),
+            ];
+
+            for (measure, value, limit) in limits.iter() {
+                if value > limit {
+                    warnings.push((
+                        function.position,
+                        format!(
+                            "Function '{}' has {} {}, above the limit of {}",
+                            function.name, measure, value, limit
+                        ),
+                    ));
+                }
+            }
+        }
+
+        for positions in &self.duplicates {
+            let lines: Vec<String> = positions[1..]
+                .iter()
+                .map(|position| (position.0 + 1).to_string())
+                .collect();
+
+            warnings.push((
+                positions[0],
+                format!("Block is duplicated on line(s) {}", lines.join(", ")),
+            ));
+        }
+
+        warnings.sort_by_key(|(position, _)| (position.2, position.0, position.1));
+        warnings
+    }
+}
+
+// Measures every named function in a program and finds blocks of at least the given
+// number of statements that appear more than once
+pub fn measure(statements: &[Statement], duplicate_statements: usize) -> Metrics {
+    let mut functions = Vec::new();
+    find_functions(statements, &mut functions);
+
+    let mut blocks = Vec::new();
+    find_blocks(statements, None, &mut blocks);
+
+    Metrics {
+        functions,
+        duplicates: duplicates(&blocks, duplicate_statements),
+    }
+}
+
+fn find_functions(statements: &[Statement], functions: &mut Vec<FunctionMetrics>) {
+    let mut outside = FunctionMetrics {
+        name: String::new(),
+        position: Position(0, 0, 0),
+        complexity: 0,
+        statements: 0,
+        nesting: 0,
+    };
+
+    // Code outside of functions isn't measured itself, but holds functions that are
+    for statement in statements {
+        measure_statement(statement, 0, &mut outside, functions);
+    }
+}
+
+fn measure_function(name: &Name, body: &[Statement], functions: &mut Vec<FunctionMetrics>) {
+    let mut function = FunctionMetrics {
+        name: name.identifier.clone(),
+        position: name.position,
+        complexity: 1,
+        statements: 0,
+        nesting: 0,
+    };
+
+    // Keep functions in the order they are declared in
+    let index = functions.len();
+
+    for statement in body {
+        measure_statement(statement, 0, &mut function, functions);
+    }
+
+    functions.insert(index, function);
+}
+
+fn measure_block(
+    block: &[Statement],
+    depth: usize,
+    function: &mut FunctionMetrics,
+    functions: &mut Vec<FunctionMetrics>,
+) {
+    for statement in block {
+        measure_statement(statement, depth + 1, function, functions);
+    }
+}
+
+fn measure_statement(
+    statement: &Statement,
+    depth: usize,
+    function: &mut FunctionMetrics,
+    functions: &mut Vec<FunctionMetrics>,
+) {
+    function.statements += 1;
+    function.nesting = function.nesting.max(depth);
+
+    match statement {
+        Statement::Print(expr) | Statement::Expr(expr) | Statement::Return(Some(expr)) => {
+            measure_expr(expr, depth, function, functions)
+        }
+
+        // Functions stored straight into variables are named after them
+        Statement::VarDecl(name, Some(Expr::Func(_, body))) => {
+            measure_function(name, body, functions)
+        }
+        Statement::VarDecl(_, Some(expr)) => measure_expr(expr, depth, function, functions),
+        Statement::VarDecl(_, None) => {}
+
+        Statement::If(_, condition, then_block, elif_blocks, else_block) => {
+            function.complexity += 1 + elif_blocks.len();
+
+            measure_expr(condition, depth, function, functions);
+            measure_block(then_block, depth, function, functions);
+
+            for (_, cond, block) in elif_blocks {
+                measure_expr(cond, depth, function, functions);
+                measure_block(block, depth, function, functions);
+            }
+
+            if let Some(block) = else_block {
+                measure_block(block, depth, function, functions);
+            }
+        }
+
+        Statement::Block(block) => measure_block(block, depth, function, functions),
+        Statement::Loop(_, block) => {
+            function.complexity += 1;
+            measure_block(block, depth, function, functions);
+        }
+        Statement::While(_, expr, body) | Statement::For(_, _, expr, body) => {
+            function.complexity += 1;
+            measure_expr(expr, depth, function, functions);
+            measure_block(body, depth, function, functions);
+        }
+
+        Statement::Break | Statement::Continue | Statement::Return(None) | Statement::Error(..) => {
+        }
+        Statement::Func(name, _, body) => measure_function(name, body, functions),
+    }
+}
+
+fn measure_expr(
+    expr: &Expr,
+    depth: usize,
+    function: &mut FunctionMetrics,
+    functions: &mut Vec<FunctionMetrics>,
+) {
+    let mut measure = |expr: &Expr| measure_expr(expr, depth, function, functions);
+
+    match expr {
+        Expr::Literal(..) | Expr::Variable(..) | Expr::Error(..) | Expr::Placeholder(..) => {}
+
+        Expr::Logical(lhs, _, rhs) => {
+            function.complexity += 1;
+
+            measure_expr(lhs, depth, function, functions);
+            measure_expr(rhs, depth, function, functions);
+        }
+        Expr::Conditional(condition, _, then_value, else_value) => {
+            function.complexity += 1;
+
+            measure_expr(condition, depth, function, functions);
+            measure_expr(then_value, depth, function, functions);
+            measure_expr(else_value, depth, function, functions);
+        }
+
+        Expr::Assign(_, inner) | Expr::Unary(_, inner) | Expr::Grouping(_, inner, _) => {
+            measure(inner)
+        }
+        Expr::Binary(lhs, _, rhs) | Expr::Range(lhs, _, rhs) | Expr::Index(lhs, _, rhs) => {
+            measure(lhs);
+            measure(rhs);
+        }
+        Expr::IndexAssign(target, _, index, value) => {
+            measure(target);
+            measure(index);
+            measure(value);
+        }
+        Expr::Call(callee, _, args) | Expr::Method(callee, _, _, args) => {
+            measure(callee);
+            args.iter().for_each(measure);
+        }
+
+        Expr::List(items) => items.iter().for_each(measure),
+        Expr::Map(entries) => {
+            for (key, _, value) in entries {
+                measure(key);
+                measure(value);
+            }
+        }
+
+        Expr::Func(_, body) => measure_block(body, depth, function, functions),
+    }
+}
+
+struct Block {
+    hash: u64,
+    position: Option<Position>,
+
+    // Statements in the block, counting those nested inside others
+    size: usize,
+
+    // Index of the block this one is nested in
+    parent: Option<usize>,
+}
+
+// Records every block in a list of statements, returning how many statements they hold
+fn find_blocks(statements: &[Statement], parent: Option<usize>, blocks: &mut Vec<Block>) -> usize {
+    let mut size = 0;
+
+    for statement in statements {
+        size += 1;
+
+        for block in child_blocks(statement) {
+            let index = blocks.len();
+            blocks.push(Block {
+                hash: shape_hash(block),
+                position: block.iter().find_map(Statement::position),
+                size: 0,
+                parent,
+            });
+
+            blocks[index].size = find_blocks(block, Some(index), blocks);
+            size += blocks[index].size;
+        }
+    }
+
+    size
+}
+
+fn child_blocks(statement: &Statement) -> Vec<&[Statement]> {
+    let mut blocks: Vec<&[Statement]> = Vec::new();
+
+    match statement {
+        Statement::If(_, _, then_block, elif_blocks, else_block) => {
+            blocks.push(then_block);
+            blocks.extend(elif_blocks.iter().map(|(_, _, block)| &block[..]));
+            blocks.extend(else_block.as_deref());
+        }
+
+        Statement::Block(block) | Statement::Loop(_, block) => blocks.push(block),
+        Statement::While(_, _, body) | Statement::For(_, _, _, body) => blocks.push(body),
+        Statement::Func(_, _, body) => blocks.push(body),
+        Statement::VarDecl(_, Some(Expr::Func(_, body))) => blocks.push(body),
+
+        _ => {}
+    }
+
+    blocks
+}
+
+// Groups large enough blocks with the same shape. Blocks nested in duplicated blocks are
+// left out, since they are only duplicated along with them
+fn duplicates(blocks: &[Block], min_size: usize) -> Vec<Vec<Position>> {
+    let mut groups: HashMap<u64, Vec<usize>> = HashMap::new();
+
+    for (index, block) in blocks.iter().enumerate() {
+        if block.size >= min_size && block.position.is_some() {
+            groups.entry(block.hash).or_default().push(index);
+        }
+    }
+
+    groups.retain(|_, group| group.len() > 1);
+    let duplicated: HashSet<u64> = groups.keys().copied().collect();
+
+    let mut duplicates: Vec<Vec<Position>> = groups
+        .values()
+        .filter(|group| {
+            !group.iter().all(|index| {
+                blocks[*index]
+                    .parent
+                    .is_some_and(|parent| duplicated.contains(&blocks[parent].hash))
+            })
+        })
+        .map(|group| group.iter().filter_map(|i| blocks[*i].position).collect())
+        .collect();
+
+    duplicates.sort_by_key(|positions: &Vec<Position>| {
+        positions
+            .first()
+            .map(|position| (position.2, position.0, position.1))
+    });
+    duplicates
+}
+
+// Hashes the shape of a block, leaving out names and literal values, so that blocks which
+// only differ in those hash the same
+fn shape_hash(block: &[Statement]) -> u64 {
+    let mut hasher = StableHasher::default();
+    hash_block(block, &mut hasher);
+    hasher.finish()
+}
+
+fn hash_block(block: &[Statement], hasher: &mut StableHasher) {
+    block.len().hash(hasher);
+
+    for statement in block {
+        hash_statement(statement, hasher);
+    }
+}
+
+fn hash_statement(statement: &Statement, hasher: &mut StableHasher) {
+    discriminant(statement).hash(hasher);
+
+    match statement {
+        Statement::Print(expr) | Statement::Expr(expr) | Statement::Return(Some(expr)) => {
+            hash_expr(expr, hasher)
+        }
+        Statement::VarDecl(_, initializer) => {
+            initializer.is_some().hash(hasher);
+            initializer.iter().for_each(|expr| hash_expr(expr, hasher));
+        }
+
+        Statement::If(_, condition, then_block, elif_blocks, else_block) => {
+            hash_expr(condition, hasher);
+            hash_block(then_block, hasher);
+
+            elif_blocks.len().hash(hasher);
+            for (_, cond, block) in elif_blocks {
+                hash_expr(cond, hasher);
+                hash_block(block, hasher);
+            }
+
+            else_block.is_some().hash(hasher);
+            else_block
+                .iter()
+                .for_each(|block| hash_block(block, hasher));
+        }
+
+        Statement::Block(block) | Statement::Loop(_, block) => hash_block(block, hasher),
+        Statement::While(_, expr, body) | Statement::For(_, _, expr, body) => {
+            hash_expr(expr, hasher);
+            hash_block(body, hasher);
+        }
+
+        Statement::Func(_, params, body) => {
+            params.len().hash(hasher);
+            hash_block(body, hasher);
+        }
+
+        Statement::Error(tokens) => tokens.iter().for_each(|tok| hash_token(tok, hasher)),
+
+        Statement::Break | Statement::Continue | Statement::Return(None) => {}
+    }
+}
+
+fn hash_token(token: &Token, hasher: &mut StableHasher) {
+    match &token.kind {
+        TokenKind::Literal(lit) => discriminant(lit).hash(hasher),
+        kind => kind.hash(hasher),
+    }
+}
+
+fn hash_expr(expr: &Expr, hasher: &mut StableHasher) {
+    discriminant(expr).hash(hasher);
+
+    match expr {
+        Expr::Literal(token) | Expr::Variable(token) | Expr::Placeholder(token) => {
+            hash_token(token, hasher)
+        }
+        Expr::Assign(_, value) => hash_expr(value, hasher),
+
+        Expr::Unary(op, operand) => {
+            hash_token(op, hasher);
+            hash_expr(operand, hasher);
+        }
+        Expr::Grouping(_, inner, _) => hash_expr(inner, hasher),
+
+        Expr::Binary(lhs, op, rhs) | Expr::Logical(lhs, op, rhs) | Expr::Range(lhs, op, rhs) => {
+            hash_expr(lhs, hasher);
+            hash_token(op, hasher);
+            hash_expr(rhs, hasher);
+        }
+        Expr::Index(target, _, index) => {
+            hash_expr(target, hasher);
+            hash_expr(index, hasher);
+        }
+        Expr::IndexAssign(target, _, index, value) => {
+            hash_expr(target, hasher);
+            hash_expr(index, hasher);
+            hash_expr(value, hasher);
+        }
+        Expr::Conditional(condition, _, then_value, else_value) => {
+            hash_expr(condition, hasher);
+            hash_expr(then_value, hasher);
+            hash_expr(else_value, hasher);
+        }
+
+        Expr::Call(callee, _, args) | Expr::Method(callee, _, _, args) => {
+            hash_expr(callee, hasher);
+            args.len().hash(hasher);
+            args.iter().for_each(|arg| hash_expr(arg, hasher));
+        }
+
+        Expr::List(items) => {
+            items.len().hash(hasher);
+            items.iter().for_each(|item| hash_expr(item, hasher));
+        }
+        Expr::Map(entries) => {
+            entries.len().hash(hasher);
+
+            for (key, _, value) in entries {
+                hash_expr(key, hasher);
+                hash_expr(value, hasher);
+            }
+        }
+
+        Expr::Func(params, body) => {
+            params.len().hash(hasher);
+            hash_block(body, hasher);
+        }
+
+        Expr::Error(tokens) => tokens.iter().for_each(|tok| hash_token(tok, hasher)),
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::environment::Environment;
+use crate::error::{LulaError, RuntimeErrorKind};
+use crate::messages::Message;
+use crate::number::{parse_number, Number};
+use crate::semantics::to_number;
+use crate::token::Position;
+use crate::value::Value;
+
+mod io;
+mod math;
+mod string;
+
+// Natives receive the position of their call so that they can report errors against it
+pub type NativeImpl = fn(&[Value], Position) -> Result<Value, LulaError>;
+
+// A function implemented in Rust and callable from Lula code
+#[derive(Clone, Copy)]
+pub struct NativeFn {
+    pub name: &'static str,
+    pub arity: usize,
+    pub func: NativeImpl,
+}
+
+impl NativeFn {
+    pub fn call(&self, args: &[Value], position: Position) -> Result<Value, LulaError> {
+        (self.func)(args, position)
+    }
+}
+
+impl PartialEq for NativeFn {
+    fn eq(&self, other: &Self) -> bool {
+        // Every native has a unique name
+        self.name == other.name
+    }
+}
+
+impl fmt::Debug for NativeFn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.name)
+    }
+}
+
+const CORE_NATIVES: &[NativeFn] = &[
+    NativeFn {
+        name: "len",
+        arity: 1,
+        func: len,
+    },
+    NativeFn {
+        name: "type",
+        arity: 1,
+        func: type_name,
+    },
+    NativeFn {
+        name: "str",
+        arity: 1,
+        func: to_str,
+    },
+    NativeFn {
+        name: "num",
+        arity: 1,
+        func: to_num,
+    },
+    NativeFn {
+        name: "abs",
+        arity: 1,
+        func: abs,
+    },
+    NativeFn {
+        name: "clock",
+        arity: 0,
+        func: clock,
+    },
+];
+
+fn natives() -> impl Iterator<Item = &'static NativeFn> {
+    CORE_NATIVES
+        .iter()
+        .chain(string::NATIVES)
+        .chain(math::NATIVES)
+        .chain(io::NATIVES)
+}
+
+// Defines every native function and constant as a global, where user code is free to
+// shadow them
+pub fn define_natives(env: &mut Environment) {
+    io::clear_error();
+
+    for native in natives() {
+        env.define(native.name.to_owned(), Value::NativeFn(*native));
+    }
+
+    for (name, val) in math::CONSTANTS {
+        env.define((*name).to_owned(), Value::Float(*val));
+    }
+}
+
+// Looks a native up by name regardless of what user code has shadowed it with, for use as a
+// method
+pub fn find_native(name: &str) -> Option<NativeFn> {
+    natives().find(|native| native.name == name).copied()
+}
+
+// 'expected' is the catalog key of a phrase describing the values the function accepts,
+// such as 'word.a-number'
+pub fn argument_error(
+    name: &str,
+    expected: &'static str,
+    found: &Value,
+    position: Position,
+) -> LulaError {
+    LulaError::runtime(
+        RuntimeErrorKind::Type,
+        Message::new("argument-type")
+            .arg(name)
+            .word(expected)
+            .debug_arg(found),
+        position,
+    )
+}
+
+pub fn string_arg<'a>(
+    name: &str,
+    val: &'a Value,
+    position: Position,
+) -> Result<&'a str, LulaError> {
+    match val {
+        Value::String(val) => Ok(val),
+        val => Err(argument_error(name, "word.a-string", val, position)),
+    }
+}
+
+pub fn number_arg(name: &str, val: &Value, position: Position) -> Result<Number, LulaError> {
+    match to_number(val) {
+        Some(val) => Ok(val),
+        None => Err(argument_error(name, "word.a-number", val, position)),
+    }
+}
+
+fn len(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let len = match &args[0] {
+        Value::String(val) => val.chars().count(),
+        Value::List(items) => items.borrow().len(),
+        Value::Map(entries) => entries.borrow().len(),
+        val => {
+            return Err(argument_error(
+                "len",
+                "word.a-string-list-or-map",
+                val,
+                position,
+            ))
+        }
+    };
+
+    Ok(Value::Int(len as i64))
+}
+
+fn type_name(args: &[Value], _: Position) -> Result<Value, LulaError> {
+    let name = match &args[0] {
+        Value::Int(..) => "int",
+        Value::Float(..) => "float",
+        Value::String(..) => "string",
+        Value::Bool(..) => "bool",
+        Value::Function(..) | Value::NativeFn(..) => "function",
+        Value::List(..) => "list",
+        Value::Map(..) => "map",
+        Value::Nil => "nil",
+    };
+
+    Ok(Value::String(name.to_owned()))
+}
+
+fn to_str(args: &[Value], _: Position) -> Result<Value, LulaError> {
+    Ok(Value::String(args[0].to_string()))
+}
+
+// Converts a string to a number, giving nil if the string doesn't hold one
+fn to_num(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let text = match &args[0] {
+        Value::Int(..) | Value::Float(..) => return Ok(args[0].clone()),
+        Value::String(val) => val.trim(),
+        val => {
+            return Err(argument_error(
+                "num",
+                "word.a-number-or-string",
+                val,
+                position,
+            ))
+        }
+    };
+
+    Ok(parse_number(text).ok().map_or(Value::Nil, Value::from))
+}
+
+fn abs(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let val = match number_arg("abs", &args[0], position)? {
+        Number::Int(val) => val
+            .checked_abs()
+            .map_or(Number::Float((val as f64).abs()), Number::Int),
+        Number::Float(val) => Number::Float(val.abs()),
+    };
+
+    Ok(Value::from(val))
+}
+
+// Seconds since the Unix epoch, for timing code
+fn clock(_: &[Value], _: Position) -> Result<Value, LulaError> {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |duration| duration.as_secs_f64());
+
+    Ok(Value::Float(elapsed))
+}
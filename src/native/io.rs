@@ -0,0 +1,120 @@
+use super::{string_arg, NativeFn};
+use crate::error::LulaError;
+use crate::token::Position;
+use crate::value::Value;
+use std::{
+    cell::RefCell,
+    fs,
+    fs::OpenOptions,
+    io::{self, BufRead, Write},
+};
+
+// Input and file operations give nil when they fail, so scripts can handle failure with
+// 'let x = ... else fallback' or 'let x = ... or return'. The reason is kept for 'io_error'
+// to tell, until the next operation replaces it
+pub const NATIVES: &[NativeFn] = &[
+    NativeFn {
+        name: "input",
+        arity: 1,
+        func: input,
+    },
+    NativeFn {
+        name: "read_file",
+        arity: 1,
+        func: read_file,
+    },
+    NativeFn {
+        name: "write_file",
+        arity: 2,
+        func: write_file,
+    },
+    NativeFn {
+        name: "append_file",
+        arity: 2,
+        func: append_file,
+    },
+    NativeFn {
+        name: "io_error",
+        arity: 0,
+        func: io_error,
+    },
+];
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// A program starts with no failure to tell about, even if one ran before it on this thread
+pub(super) fn clear_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
+
+// Turns the result of an operation into its value, or nil if it failed, recording the
+// operating system's reason for the failure
+fn record<T>(result: io::Result<T>, value: impl FnOnce(T) -> Value) -> Value {
+    let (value, error) = match result {
+        Ok(val) => (value(val), None),
+        Err(err) => (Value::Nil, Some(err.to_string())),
+    };
+
+    LAST_ERROR.with(|last| *last.borrow_mut() = error);
+    value
+}
+
+// Gives the reason the last input or file operation failed, such as a missing file or a
+// lack of permission, or nil if it succeeded
+fn io_error(_: &[Value], _: Position) -> Result<Value, LulaError> {
+    Ok(LAST_ERROR.with(|last| last.borrow().clone().map_or(Value::Nil, Value::String)))
+}
+
+// Prints a prompt and reads a line from standard input, without its line ending. Gives nil
+// once input runs out
+fn input(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let prompt = string_arg("input", &args[0], position)?;
+
+    let mut stdout = io::stdout();
+    let _ = stdout
+        .write_all(prompt.as_bytes())
+        .and_then(|_| stdout.flush());
+
+    let mut line = String::new();
+    let result = io::stdin().lock().read_line(&mut line);
+
+    // Running out of input isn't a failure, so it's nil without a reason
+    Ok(record(result, |read| {
+        if read == 0 {
+            return Value::Nil;
+        }
+
+        let len = line.trim_end_matches(&['\r', '\n'][..]).len();
+        line.truncate(len);
+
+        Value::String(line)
+    }))
+}
+
+fn read_file(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let path = string_arg("read_file", &args[0], position)?;
+    Ok(record(fs::read_to_string(path), Value::String))
+}
+
+// Replaces the file's contents, creating it if it doesn't exist
+fn write_file(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let path = string_arg("write_file", &args[0], position)?;
+    let contents = string_arg("write_file", &args[1], position)?;
+
+    Ok(record(fs::write(path, contents), |_| Value::Bool(true)))
+}
+
+fn append_file(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let path = string_arg("append_file", &args[0], position)?;
+    let contents = string_arg("append_file", &args[1], position)?;
+
+    let result = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()));
+
+    Ok(record(result, |_| Value::Bool(true)))
+}
@@ -0,0 +1,183 @@
+use super::{argument_error, number_arg, NativeFn};
+use crate::error::LulaError;
+use crate::expr::Expr;
+use crate::number::{format_fixed, Number};
+use crate::token::{Position, Span, Token, TokenKind};
+use crate::value::Value;
+use std::f64::consts;
+
+pub const CONSTANTS: &[(&str, f64)] = &[("PI", consts::PI), ("E", consts::E)];
+
+pub const NATIVES: &[NativeFn] = &[
+    NativeFn {
+        name: "sqrt",
+        arity: 1,
+        func: sqrt,
+    },
+    NativeFn {
+        name: "pow",
+        arity: 2,
+        func: pow,
+    },
+    NativeFn {
+        name: "floor",
+        arity: 1,
+        func: floor,
+    },
+    NativeFn {
+        name: "ceil",
+        arity: 1,
+        func: ceil,
+    },
+    NativeFn {
+        name: "round",
+        arity: 1,
+        func: round,
+    },
+    NativeFn {
+        name: "sin",
+        arity: 1,
+        func: sin,
+    },
+    NativeFn {
+        name: "cos",
+        arity: 1,
+        func: cos,
+    },
+    NativeFn {
+        name: "log",
+        arity: 1,
+        func: log,
+    },
+    NativeFn {
+        name: "to_fixed",
+        arity: 2,
+        func: to_fixed,
+    },
+    NativeFn {
+        name: "min",
+        arity: 2,
+        func: min,
+    },
+    NativeFn {
+        name: "max",
+        arity: 2,
+        func: max,
+    },
+];
+
+fn sqrt(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    Ok(Value::Float(
+        number_arg("sqrt", &args[0], position)?.to_f64().sqrt(),
+    ))
+}
+
+// Works out powers as '**' does, so integer powers stay integers where they can
+fn pow(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let base = number_arg("pow", &args[0], position)?;
+    let exponent = number_arg("pow", &args[1], position)?;
+
+    let op = Token {
+        kind: TokenKind::StarStar,
+        position,
+        span: Span::default(),
+    };
+
+    Expr::apply_power(&op, Value::from(base), Value::from(exponent))
+}
+
+// Rounds a float to an integer, leaving integers as they are. Floats too large for an
+// integer to hold, infinity and NaN stay floats
+fn to_integer(val: Number, round: fn(f64) -> f64) -> Value {
+    // 2^63, the first float past the largest integer
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
+    match val {
+        Number::Int(val) => Value::Int(val),
+        Number::Float(val) => {
+            let rounded = round(val);
+
+            if (-LIMIT..LIMIT).contains(&rounded) {
+                Value::Int(rounded as i64)
+            } else {
+                Value::Float(rounded)
+            }
+        }
+    }
+}
+
+fn floor(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    Ok(to_integer(
+        number_arg("floor", &args[0], position)?,
+        f64::floor,
+    ))
+}
+
+fn ceil(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    Ok(to_integer(
+        number_arg("ceil", &args[0], position)?,
+        f64::ceil,
+    ))
+}
+
+// Halfway cases round away from zero
+fn round(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    Ok(to_integer(
+        number_arg("round", &args[0], position)?,
+        f64::round,
+    ))
+}
+
+fn sin(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    Ok(Value::Float(
+        number_arg("sin", &args[0], position)?.to_f64().sin(),
+    ))
+}
+
+fn cos(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    Ok(Value::Float(
+        number_arg("cos", &args[0], position)?.to_f64().cos(),
+    ))
+}
+
+// Natural logarithm
+fn log(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    Ok(Value::Float(
+        number_arg("log", &args[0], position)?.to_f64().ln(),
+    ))
+}
+
+// Formats a number as a string with a set number of decimal places, for when a float's
+// shortest form, or an integer's lack of a decimal point, isn't wanted
+fn to_fixed(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let val = number_arg("to_fixed", &args[0], position)?;
+
+    let digits = match args[1] {
+        Value::Int(digits @ 0..=100) => digits as usize,
+        ref val => {
+            return Err(argument_error(
+                "to_fixed",
+                "word.a-digit-count",
+                val,
+                position,
+            ))
+        }
+    };
+
+    Ok(Value::String(format_fixed(val, digits)))
+}
+
+// Gives whichever argument is smaller, keeping its type
+fn min(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let left = number_arg("min", &args[0], position)?;
+    let right = number_arg("min", &args[1], position)?;
+
+    Ok(Value::from(if right < left { right } else { left }))
+}
+
+fn max(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let left = number_arg("max", &args[0], position)?;
+    let right = number_arg("max", &args[1], position)?;
+
+    Ok(Value::from(if right > left { right } else { left }))
+}
@@ -0,0 +1,126 @@
+use super::{argument_error, string_arg, NativeFn};
+use crate::error::LulaError;
+use crate::token::Position;
+use crate::value::Value;
+
+pub const NATIVES: &[NativeFn] = &[
+    NativeFn {
+        name: "split",
+        arity: 2,
+        func: split,
+    },
+    NativeFn {
+        name: "join",
+        arity: 2,
+        func: join,
+    },
+    NativeFn {
+        name: "trim",
+        arity: 1,
+        func: trim,
+    },
+    NativeFn {
+        name: "upper",
+        arity: 1,
+        func: upper,
+    },
+    NativeFn {
+        name: "lower",
+        arity: 1,
+        func: lower,
+    },
+    NativeFn {
+        name: "contains",
+        arity: 2,
+        func: contains,
+    },
+    NativeFn {
+        name: "replace",
+        arity: 3,
+        func: replace,
+    },
+    NativeFn {
+        name: "starts_with",
+        arity: 2,
+        func: starts_with,
+    },
+    NativeFn {
+        name: "ends_with",
+        arity: 2,
+        func: ends_with,
+    },
+];
+
+fn string(val: &str) -> Value {
+    Value::String(val.to_owned())
+}
+
+// Splitting on an empty separator splits a string into its characters
+fn split(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let text = string_arg("split", &args[0], position)?;
+    let separator = string_arg("split", &args[1], position)?;
+
+    let parts = if separator.is_empty() {
+        text.chars().map(|c| Value::String(c.to_string())).collect()
+    } else {
+        text.split(separator).map(string).collect()
+    };
+
+    Ok(Value::list(parts))
+}
+
+fn join(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let items = match &args[0] {
+        Value::List(items) => items.borrow(),
+        val => return Err(argument_error("join", "word.a-list", val, position)),
+    };
+    let separator = string_arg("join", &args[1], position)?;
+
+    let parts: Vec<String> = items.iter().map(Value::to_string).collect();
+    Ok(Value::String(parts.join(separator)))
+}
+
+fn trim(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    Ok(string(string_arg("trim", &args[0], position)?.trim()))
+}
+
+fn upper(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    Ok(Value::String(
+        string_arg("upper", &args[0], position)?.to_uppercase(),
+    ))
+}
+
+fn lower(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    Ok(Value::String(
+        string_arg("lower", &args[0], position)?.to_lowercase(),
+    ))
+}
+
+fn contains(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let text = string_arg("contains", &args[0], position)?;
+    let pattern = string_arg("contains", &args[1], position)?;
+
+    Ok(Value::Bool(text.contains(pattern)))
+}
+
+fn replace(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let text = string_arg("replace", &args[0], position)?;
+    let from = string_arg("replace", &args[1], position)?;
+    let to = string_arg("replace", &args[2], position)?;
+
+    Ok(Value::String(text.replace(from, to)))
+}
+
+fn starts_with(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let text = string_arg("starts_with", &args[0], position)?;
+    let prefix = string_arg("starts_with", &args[1], position)?;
+
+    Ok(Value::Bool(text.starts_with(prefix)))
+}
+
+fn ends_with(args: &[Value], position: Position) -> Result<Value, LulaError> {
+    let text = string_arg("ends_with", &args[0], position)?;
+    let suffix = string_arg("ends_with", &args[1], position)?;
+
+    Ok(Value::Bool(text.ends_with(suffix)))
+}
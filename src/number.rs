@@ -0,0 +1,233 @@
+// Number parsing and formatting shared by the lexer and the value formatter.
+//
+// Both directions go through the standard library's integer and float routines,
+// which are locale-independent; parsing is correctly rounded and formatting emits
+// the shortest digit string that parses back to the same value. Floats always
+// print with a decimal point or exponent, so that they read back as floats rather
+// than integers, and negative numbers print with a leading minus sign, which the
+// parser accepts. Keeping them here guarantees that `parse_number(&format_number(x))
+// == Ok(x)` for every finite number, and that every part of the interpreter prints
+// numbers the same way.
+
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display},
+    ops::Neg,
+};
+
+// A number read from a literal or held by a value. Integers and floats compare and test
+// equal by their exact numeric value, so '1 == 1.0', but nothing equals NaN
+#[derive(Debug, Clone, Copy)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Number::Int(val) => val as f64,
+            Number::Float(val) => val,
+        }
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        match (*self, *other) {
+            (Number::Int(left), Number::Int(right)) => Some(left.cmp(&right)),
+            (Number::Float(left), Number::Float(right)) => left.partial_cmp(&right),
+            (Number::Int(left), Number::Float(right)) => compare_int_float(left, right),
+            (Number::Float(left), Number::Int(right)) => {
+                compare_int_float(right, left).map(Ordering::reverse)
+            }
+        }
+    }
+}
+
+// Compares without converting the integer to a float, which would round integers too large
+// for a float to hold exactly
+fn compare_int_float(int: i64, float: f64) -> Option<Ordering> {
+    // 2^63, the first float past the largest integer
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
+    if float.is_nan() {
+        None
+    } else if float >= LIMIT {
+        Some(Ordering::Less)
+    } else if float < -LIMIT {
+        Some(Ordering::Greater)
+    } else {
+        let whole = float.trunc();
+
+        // Integers equal to the float's whole part are on the other side of its fraction
+        Some(
+            int.cmp(&(whole as i64))
+                .then_with(|| 0.0.partial_cmp(&(float - whole)).unwrap_or(Ordering::Equal)),
+        )
+    }
+}
+
+impl Neg for Number {
+    type Output = Number;
+
+    // Negating the smallest integer overflows, so gives a float instead
+    fn neg(self) -> Number {
+        match self {
+            Number::Int(val) => val
+                .checked_neg()
+                .map_or(Number::Float(-(val as f64)), Number::Int),
+            Number::Float(val) => Number::Float(-val),
+        }
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Number::Int(val) => write!(f, "{}", val),
+            Number::Float(val) => write!(f, "{}", format_number(*val)),
+        }
+    }
+}
+
+// Why a lexemme isn't a valid number literal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberError {
+    // A '0x', '0b' or '0o' prefix with no digits after it
+    MissingDigits(u32),
+    // A character that isn't a digit of the literal's base
+    InvalidDigit(char, u32),
+    // An 'e' with no digits after it
+    MissingExponent,
+    // An underscore that doesn't sit between two digits
+    MisplacedUnderscore,
+    // A literal too large to represent
+    TooLarge,
+    // Anything else that isn't a number, such as an empty string
+    Malformed,
+}
+
+// Parses Lula's number grammar: decimal numbers with an optional fraction and exponent, such
+// as '1.5e-3', or whole numbers in hexadecimal, binary or octal, such as '0xff'. Underscores
+// may separate digits, as in '1_000'. Inputs like "inf" or "NaN" that the standard parser
+// allows are rejected. Numbers without a fraction or exponent are integers. Decimal ones too
+// large for an integer become floats, and hexadecimal, binary or octal ones are rejected as
+// too large. A single leading minus sign negates the number;
+// the lexer never passes one, since in source code it is an operator
+pub fn parse_number(lexemme: &str) -> Result<Number, NumberError> {
+    match lexemme.strip_prefix('-') {
+        Some(digits) => parse_unsigned(digits).map(|val| -val),
+        None => parse_unsigned(lexemme),
+    }
+}
+
+fn parse_unsigned(lexemme: &str) -> Result<Number, NumberError> {
+    let radix = match lexemme.get(..2) {
+        Some("0x") | Some("0X") => Some(16),
+        Some("0b") | Some("0B") => Some(2),
+        Some("0o") | Some("0O") => Some(8),
+        _ => None,
+    };
+
+    if let Some(radix) = radix {
+        let digits = check_digits(&lexemme[2..], radix, NumberError::MissingDigits(radix))?;
+
+        // These are as often bit patterns as amounts, so one too large for an integer is an
+        // error rather than a float, which the bitwise operators would reject
+        return i64::from_str_radix(&digits, radix)
+            .map(Number::Int)
+            .map_err(|_| NumberError::TooLarge);
+    }
+
+    let (mantissa, exponent) = match lexemme.find(['e', 'E']) {
+        Some(i) => (&lexemme[..i], Some(&lexemme[i + 1..])),
+        None => (lexemme, None),
+    };
+
+    let (whole, fraction) = match mantissa.find('.') {
+        Some(i) => (&mantissa[..i], Some(&mantissa[i + 1..])),
+        None => (mantissa, None),
+    };
+
+    let mut number = check_digits(whole, 10, NumberError::Malformed)?;
+
+    if fraction.is_none() && exponent.is_none() {
+        if let Ok(val) = number.parse() {
+            return Ok(Number::Int(val));
+        }
+    }
+
+    if let Some(fraction) = fraction {
+        let fraction = check_digits(fraction, 10, NumberError::Malformed)?;
+        number = format!("{}.{}", number, fraction);
+    }
+
+    if let Some(exponent) = exponent {
+        let (sign, digits) = match exponent.strip_prefix(['+', '-']) {
+            Some(digits) => (&exponent[..1], digits),
+            None => ("", exponent),
+        };
+
+        let digits = check_digits(digits, 10, NumberError::MissingExponent)?;
+        number = format!("{}e{}{}", number, sign, digits);
+    }
+
+    let val: f64 = number.parse().map_err(|_| NumberError::Malformed)?;
+
+    if val.is_finite() {
+        Ok(Number::Float(val))
+    } else {
+        Err(NumberError::TooLarge)
+    }
+}
+
+// Checks that text is made of digits in a base, optionally separated by single underscores,
+// returning the digits alone
+fn check_digits(text: &str, radix: u32, missing: NumberError) -> Result<String, NumberError> {
+    if text.is_empty() {
+        return Err(missing);
+    }
+
+    if text.starts_with('_') || text.ends_with('_') || text.contains("__") {
+        return Err(NumberError::MisplacedUnderscore);
+    }
+
+    match text.chars().find(|&c| c != '_' && !c.is_digit(radix)) {
+        Some(c) => Err(NumberError::InvalidDigit(c, radix)),
+        None => Ok(text.replace('_', "")),
+    }
+}
+
+// Formats a float in the shortest form that reads back as the same float. Whole floats keep
+// a '.0', so that they aren't mistaken for integers: '3.0' rather than the '3' printed before
+// integers were separated from floats. Very large and very small magnitudes switch to an
+// exponent, so '1e300' rather than all 301 of its digits
+pub fn format_number(val: f64) -> String {
+    if val.is_nan() {
+        String::from("nan")
+    } else if val.is_infinite() {
+        String::from(if val > 0.0 { "inf" } else { "-inf" })
+    } else {
+        format!("{:?}", val)
+    }
+}
+
+// Formats a number with exactly 'digits' digits after the decimal point, and no point at all
+// when 'digits' is 0. Floats are rounded from their exact value, with exact halves going to
+// the even digit, and integers are padded with zeros rather than converted to floats, which
+// couldn't hold every one of them. Infinity and NaN print as they do anywhere else
+pub fn format_fixed(val: Number, digits: usize) -> String {
+    match val {
+        Number::Int(val) if digits == 0 => val.to_string(),
+        Number::Int(val) => format!("{}.{}", val, "0".repeat(digits)),
+        Number::Float(val) if val.is_finite() => format!("{:.*}", digits, val),
+        Number::Float(val) => format_number(val),
+    }
+}
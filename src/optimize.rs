@@ -0,0 +1,180 @@
+use std::rc::Rc;
+
+use crate::expr::Expr;
+use crate::statement::Statement;
+use crate::token::{Position, Span, Token, TokenKind};
+use crate::value::Value;
+
+// Folds constant subexpressions, such as '1 + 2 * 3' into '7', so that they are worked
+// out once before a program runs instead of each time they are evaluated. Expressions
+// that would fail are left alone, so their errors are still raised at runtime
+pub fn optimize(statements: Vec<Statement>) -> Vec<Statement> {
+    statements.into_iter().map(optimize_statement).collect()
+}
+
+fn optimize_body(body: Rc<Vec<Statement>>) -> Rc<Vec<Statement>> {
+    // Bodies are only shared once a program is running, so this always succeeds on
+    // freshly parsed code
+    match Rc::try_unwrap(body) {
+        Ok(body) => Rc::new(optimize(body)),
+        Err(body) => body,
+    }
+}
+
+fn optimize_statement(statement: Statement) -> Statement {
+    match statement {
+        Statement::Print(expr) => Statement::Print(fold(expr)),
+        Statement::VarDecl(name, initializer) => Statement::VarDecl(name, initializer.map(fold)),
+        Statement::Expr(expr) => Statement::Expr(fold(expr)),
+
+        Statement::If(keyword, condition, then_block, elif_blocks, else_block) => Statement::If(
+            keyword,
+            fold(condition),
+            optimize(then_block),
+            elif_blocks
+                .into_iter()
+                .map(|(keyword, cond, block)| (keyword, fold(cond), optimize(block)))
+                .collect(),
+            else_block.map(optimize),
+        ),
+
+        Statement::Block(block) => Statement::Block(optimize(block)),
+        Statement::Loop(keyword, body) => Statement::Loop(keyword, optimize(body)),
+        Statement::While(keyword, condition, body) => {
+            Statement::While(keyword, fold(condition), optimize(body))
+        }
+        Statement::For(keyword, name, iterable, body) => {
+            Statement::For(keyword, name, fold(iterable), optimize(body))
+        }
+
+        Statement::Func(name, params, body) => Statement::Func(name, params, optimize_body(body)),
+        Statement::Return(value) => Statement::Return(value.map(fold)),
+
+        Statement::Break | Statement::Continue | Statement::Error(..) => statement,
+    }
+}
+
+fn constant(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Literal(Token {
+            kind: TokenKind::Literal(lit),
+            ..
+        }) => Value::from_literal(lit),
+        _ => None,
+    }
+}
+
+// Replaces an expression with the literal for its value, if the value has one
+fn literal(value: Value, position: Position) -> Option<Expr> {
+    Some(Expr::Literal(Token {
+        kind: TokenKind::Literal(value.to_literal()?),
+        position,
+        span: Span::default(),
+    }))
+}
+
+fn boolean(expr: &Expr) -> Option<bool> {
+    match constant(expr) {
+        Some(Value::Bool(val)) => Some(val),
+        _ => None,
+    }
+}
+
+fn fold(expr: Expr) -> Expr {
+    match expr {
+        Expr::Unary(op, operand) => {
+            let operand = fold(*operand);
+
+            constant(&operand)
+                .and_then(|val| Expr::apply_unary(&op, val).ok())
+                .and_then(|val| literal(val, op.position))
+                .unwrap_or_else(|| Expr::Unary(op, Box::new(operand)))
+        }
+
+        Expr::Binary(lhs, op, rhs) => {
+            let lhs = fold(*lhs);
+            let rhs = fold(*rhs);
+
+            constant(&lhs)
+                .zip(constant(&rhs))
+                .and_then(|(left, right)| Expr::apply_binary(&op, left, right).ok())
+                .and_then(|val| literal(val, op.position))
+                .unwrap_or_else(|| Expr::Binary(Box::new(lhs), op, Box::new(rhs)))
+        }
+
+        // A constant left operand decides which operand the expression evaluates to. Only
+        // booleans are folded, since strict semantics reject anything else as a condition
+        Expr::Logical(lhs, op, rhs) => {
+            let lhs = fold(*lhs);
+            let rhs = fold(*rhs);
+
+            match (boolean(&lhs), &op.kind) {
+                (Some(false), TokenKind::And) | (Some(true), TokenKind::Or) => lhs,
+                (Some(true), TokenKind::And) | (Some(false), TokenKind::Or) => rhs,
+                _ => Expr::Logical(Box::new(lhs), op, Box::new(rhs)),
+            }
+        }
+
+        // So does a constant condition
+        Expr::Conditional(condition, question, then_value, else_value) => {
+            let condition = fold(*condition);
+            let then_value = fold(*then_value);
+            let else_value = fold(*else_value);
+
+            match boolean(&condition) {
+                Some(true) => then_value,
+                Some(false) => else_value,
+                None => Expr::Conditional(
+                    Box::new(condition),
+                    question,
+                    Box::new(then_value),
+                    Box::new(else_value),
+                ),
+            }
+        }
+
+        Expr::Grouping(open, inner, close) => {
+            let inner = fold(*inner);
+
+            match constant(&inner) {
+                Some(..) => inner,
+                None => Expr::Grouping(open, Box::new(inner), close),
+            }
+        }
+
+        Expr::Assign(name, value) => Expr::Assign(name, Box::new(fold(*value))),
+        Expr::Range(start, op, end) => {
+            Expr::Range(Box::new(fold(*start)), op, Box::new(fold(*end)))
+        }
+        Expr::Call(callee, paren, args) => Expr::Call(
+            Box::new(fold(*callee)),
+            paren,
+            args.into_iter().map(fold).collect(),
+        ),
+        Expr::Method(receiver, name, paren, args) => Expr::Method(
+            Box::new(fold(*receiver)),
+            name,
+            paren,
+            args.into_iter().map(fold).collect(),
+        ),
+        Expr::List(items) => Expr::List(items.into_iter().map(fold).collect()),
+        Expr::Map(entries) => Expr::Map(
+            entries
+                .into_iter()
+                .map(|(key, colon, value)| (fold(key), colon, fold(value)))
+                .collect(),
+        ),
+        Expr::Index(target, bracket, index) => {
+            Expr::Index(Box::new(fold(*target)), bracket, Box::new(fold(*index)))
+        }
+        Expr::IndexAssign(target, bracket, index, value) => Expr::IndexAssign(
+            Box::new(fold(*target)),
+            bracket,
+            Box::new(fold(*index)),
+            Box::new(fold(*value)),
+        ),
+        Expr::Func(params, body) => Expr::Func(params, optimize_body(body)),
+
+        Expr::Literal(..) | Expr::Variable(..) | Expr::Error(..) | Expr::Placeholder(..) => expr,
+    }
+}
@@ -1,46 +1,138 @@
+use crate::error::LulaError;
 use crate::expr::Expr;
+use crate::messages::Message;
 use crate::statement::Statement;
-use crate::token::{Literal, Position, Token, TokenKind};
-use std::mem::discriminant;
+use crate::token::{Literal, Name, Position, Span, Token, TokenKind};
+use std::{collections::VecDeque, mem::discriminant, rc::Rc};
 
-pub struct Parser {
-    source_path: String,
-    tokens: Vec<Token>,
-    cursor: usize,
+pub struct Parser<I> {
+    source: I,
+
+    // Tokens read from the source but not yet consumed, starting with the current token.
+    // Never empty, and once the source runs out it ends with an Eof token
+    lookahead: VecDeque<Token>,
+
+    // Tokens consumed since the top-level statement being parsed began, which error
+    // recovery looks back over. Earlier tokens are dropped, so only the statement in hand
+    // is ever held in memory
+    consumed: Vec<Token>,
+
+    loop_depth: usize,
+    func_depth: usize,
+
+    errors: Vec<LulaError>,
+    lexing_errors: Vec<LulaError>,
 }
 
-impl Parser {
-    pub fn new(source_path: String, tokens: Vec<Token>) -> Parser {
-        Parser {
-            source_path,
-            tokens,
-            cursor: 0,
+impl<I> Parser<I>
+where
+    I: Iterator<Item = Result<Token, LulaError>>,
+{
+    // Tokens are pulled from the source only as the parser reaches them, so a lexer can be
+    // handed over directly without collecting its tokens first
+    pub fn new(source: I) -> Parser<I> {
+        let mut parser = Parser {
+            source,
+            lookahead: VecDeque::new(),
+            consumed: Vec::new(),
+
+            loop_depth: 0,
+            func_depth: 0,
+
+            errors: Vec::new(),
+            lexing_errors: Vec::new(),
+        };
+
+        parser.read_token();
+        parser
+    }
+
+    // Reads the next token from the source into the lookahead, setting lexing errors aside
+    // on the way. Guarantees the token stream is terminated, so lookahead never runs off
+    // the end
+    fn read_token(&mut self) {
+        let last = self.lookahead.back().or_else(|| self.consumed.last());
+
+        if last.is_some_and(|tok| tok.kind == TokenKind::Eof) {
+            return;
+        }
+
+        let (position, end) = last.map_or((Position(0, 0, 0), 0), |t| (t.position, t.span.end));
+
+        loop {
+            match self.source.next() {
+                Some(Ok(tok)) => {
+                    self.lookahead.push_back(tok);
+                    return;
+                }
+                Some(Err(err)) => self.lexing_errors.push(err),
+                None => {
+                    self.lookahead.push_back(Token {
+                        kind: TokenKind::Eof,
+                        position,
+                        span: Span { start: end, end },
+                    });
+                    return;
+                }
+            }
         }
     }
 
-    fn display_error<S>(&self, message: S, position: Position)
-    where
-        S: Into<String>,
-    {
-        eprintln!(
-            "Parsing error in file '{}', {}:\n    {}.",
-            self.source_path,
-            position,
-            message.into()
-        );
+    fn error(&mut self, message: Message, position: Position) {
+        self.errors.push(LulaError::parse(message, position));
     }
 
     fn reached_end(&self) -> bool {
-        self.cursor >= self.tokens.len() - 1
+        self.peek().kind == TokenKind::Eof
+    }
+
+    fn peek(&self) -> &Token {
+        &self.lookahead[0]
     }
 
-    fn peek(&self) -> Token {
-        self.tokens[self.cursor].clone()
+    // The Eof token is returned indefinitely
+    fn peek_next(&mut self) -> &Token {
+        if self.lookahead.len() < 2 {
+            self.read_token();
+        }
+
+        let index = 1.min(self.lookahead.len() - 1);
+        &self.lookahead[index]
+    }
+
+    fn previous(&self) -> Option<&Token> {
+        self.consumed.last()
     }
 
     fn advance(&mut self) -> Token {
-        self.cursor += 1;
-        self.tokens[self.cursor - 1].clone()
+        let tok = self.peek().clone();
+
+        if !self.reached_end() {
+            self.consumed.push(
+                self.lookahead
+                    .pop_front()
+                    .expect("lookahead is never empty"),
+            );
+
+            if self.lookahead.is_empty() {
+                self.read_token();
+            }
+        }
+
+        tok
+    }
+
+    // Marks the current point in the statement being parsed, so that it can be returned to
+    fn mark(&self) -> usize {
+        self.consumed.len()
+    }
+
+    // Returns to a point marked earlier, putting the tokens consumed since back in front of
+    // the lookahead
+    fn rewind(&mut self, mark: usize) {
+        for tok in self.consumed.split_off(mark).into_iter().rev() {
+            self.lookahead.push_front(tok);
+        }
     }
 
     fn is_match(&self, kind: TokenKind) -> bool {
@@ -51,12 +143,10 @@ impl Parser {
         if self.is_match(kind.clone()) {
             Some(self.advance())
         } else {
-            self.display_error(
-                format!(
-                    "Expected token of type {:?}, found {:?} instead",
-                    kind,
-                    self.peek().kind
-                ),
+            self.error(
+                Message::new("expected-token")
+                    .debug_arg(&kind)
+                    .debug_arg(&self.peek().kind),
                 self.peek().position,
             );
             None
@@ -69,18 +159,49 @@ impl Parser {
         if discriminant(&self.peek().kind) == disc_kind {
             Some(self.advance())
         } else {
-            self.display_error(
-                format!(
-                    "Expected token of type {:?}, found {:?} instead",
-                    kind,
-                    self.peek().kind
-                ),
+            self.error(
+                Message::new("expected-token")
+                    .debug_arg(&kind)
+                    .debug_arg(&self.peek().kind),
                 self.peek().position,
             );
             None
         }
     }
 
+    fn consume_name(&mut self) -> Option<Name> {
+        let identifier =
+            self.consume_discriminant(TokenKind::Literal(Literal::Identifier(Rc::from("any"))))?;
+
+        match identifier.kind {
+            TokenKind::Literal(Literal::Identifier(val)) => Some(Name {
+                identifier: val.to_string(),
+                position: identifier.position,
+            }),
+            _ => None,
+        }
+    }
+
+    fn consume_terminator(&mut self) -> Option<()> {
+        // The last statement in a block or file doesn't need a trailing newline
+        if self.is_match(TokenKind::RightBrace) || self.is_match(TokenKind::Eof) {
+            return Some(());
+        }
+
+        // Neither does a statement ending in a block, since the lexer never emits newlines
+        // after closing braces
+        if let Some(Token {
+            kind: TokenKind::RightBrace,
+            ..
+        }) = self.previous()
+        {
+            return Some(());
+        }
+
+        self.consume(TokenKind::Newline)?;
+        Some(())
+    }
+
     fn expect_closing(&mut self, kind: TokenKind) -> Option<Token> {
         let tok = self.advance();
         let expect = match kind {
@@ -89,8 +210,8 @@ impl Parser {
             TokenKind::LeftBracket => TokenKind::RightBracket,
 
             _ => {
-                self.display_error(
-                    format!("Could not find complementary type for token {:?}", tok.kind),
+                self.error(
+                    Message::new("no-complement").debug_arg(&tok.kind),
                     tok.position,
                 );
                 return None;
@@ -98,11 +219,10 @@ impl Parser {
         };
 
         if tok.kind != expect {
-            self.display_error(
-                format!(
-                    "Expected token of type {:?}; found token of type {:?} instead",
-                    expect, tok.kind
-                ),
+            self.error(
+                Message::new("expected-closing")
+                    .debug_arg(&expect)
+                    .debug_arg(&tok.kind),
                 tok.position,
             );
             return None;
@@ -111,55 +231,290 @@ impl Parser {
         Some(tok)
     }
 
-    fn synchronize(&mut self) {
-        if !self.reached_end() {
-            self.advance();
-        }
+    // Skips over a statement that failed to parse, from its first token to the newline
+    // ending it, which is left for the caller to consume. Brackets are matched so that
+    // newlines inside them are passed over, and skipping stops early at the brace closing an
+    // enclosing block. Returns the tokens skipped
+    fn skip_statement(&mut self, start: usize) -> Vec<Token> {
+        let failed_at = self.mark();
+        let mut depth = 0;
+
+        self.rewind(start);
 
         while !self.reached_end() {
-            match self.peek().kind {
-                TokenKind::Newline => {
-                    self.advance();
-                    return;
+            let tok = self.peek();
+
+            // Nothing before the point of failure can end the statement, except for the end
+            // of its block
+            if self.mark() >= failed_at && depth == 0 {
+                if tok.kind == TokenKind::Newline {
+                    break;
                 }
 
-                TokenKind::If
-                | TokenKind::Func
-                | TokenKind::Let
-                | TokenKind::Loop
-                | TokenKind::Eof => return,
+                // Statements ending in a block need no newline, so the next statement may
+                // start on any line after the closing brace
+                let after_block = self.mark() > start
+                    && self.previous().is_some_and(|prev| {
+                        prev.kind == TokenKind::RightBrace
+                            && (prev.position.0, prev.position.2)
+                                != (tok.position.0, tok.position.2)
+                    });
 
-                _ => self.advance(),
-            };
+                if after_block {
+                    break;
+                }
+            }
+
+            match tok.kind {
+                TokenKind::LeftParen | TokenKind::LeftBrace | TokenKind::LeftBracket => depth += 1,
+                TokenKind::RightParen | TokenKind::RightBrace | TokenKind::RightBracket => {
+                    if depth == 0 {
+                        break;
+                    }
+
+                    depth -= 1;
+                }
+                _ => {}
+            }
+
+            self.advance();
         }
+
+        self.consumed[start..].to_vec()
     }
 
     fn parse_primary(&mut self) -> Option<Expr> {
         let tok = self.advance();
 
         match tok.kind {
-            TokenKind::Literal(l) => Some(Expr::Literal(l)),
+            TokenKind::Literal(Literal::Identifier(..)) => Some(Expr::Variable(tok)),
+            TokenKind::Literal(..) => Some(Expr::Literal(tok)),
+            TokenKind::Interpolation(..) => self.parse_interpolation(tok),
+            TokenKind::Placeholder => Some(Expr::Placeholder(tok)),
+
+            TokenKind::LeftBracket => self.parse_list(),
 
-            TokenKind::LeftParen | TokenKind::LeftBrace | TokenKind::LeftBracket => {
+            TokenKind::LeftBrace => self.parse_map(),
+
+            TokenKind::LeftParen => {
                 let expr = self.parse_expr()?;
                 let rhs = self.expect_closing(tok.kind.clone())?;
 
                 Some(Expr::Grouping(tok, Box::new(expr), rhs))
             }
 
-            _ => None,
+            TokenKind::Func => {
+                let (params, body) = self.parse_func_body()?;
+                Some(Expr::Func(params, Rc::new(body)))
+            }
+
+            _ => {
+                self.error(
+                    Message::new("expected-expression").debug_arg(&tok.kind),
+                    tok.position,
+                );
+                None
+            }
         }
     }
 
+    // Joins the pieces of an interpolated string with '+', turning each interpolated value
+    // into a string by calling the 'str' builtin as a method, which no variable can shadow
+    fn parse_interpolation(&mut self, head: Token) -> Option<Expr> {
+        let mut pieces = Vec::new();
+        let mut segment = head;
+
+        loop {
+            // Tokens standing in for syntax the user didn't write take the position of the
+            // text before the interpolated expression
+            let synthesized = |kind| Token {
+                kind,
+                position: segment.position,
+                span: segment.span,
+            };
+
+            if let TokenKind::Interpolation(text) = &segment.kind {
+                if !text.is_empty() {
+                    let text = TokenKind::Literal(Literal::String(Rc::clone(text)));
+                    pieces.push(Expr::Literal(synthesized(text)));
+                }
+            }
+
+            let value = self.parse_expr()?;
+            let name = synthesized(TokenKind::Literal(Literal::Identifier(Rc::from("str"))));
+            let paren = synthesized(TokenKind::LeftParen);
+            pieces.push(Expr::Method(Box::new(value), name, paren, Vec::new()));
+
+            let plus = synthesized(TokenKind::Plus);
+            let tok = self.advance();
+
+            match &tok.kind {
+                TokenKind::Interpolation(..) => segment = tok,
+                TokenKind::Literal(Literal::String(text)) => {
+                    if !text.is_empty() {
+                        pieces.push(Expr::Literal(tok));
+                    }
+
+                    return pieces.into_iter().reduce(|lhs, rhs| {
+                        Expr::Binary(Box::new(lhs), plus.clone(), Box::new(rhs))
+                    });
+                }
+                _ => {
+                    self.error(
+                        Message::new("unclosed-interpolation").debug_arg(&tok.kind),
+                        tok.position,
+                    );
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn parse_list(&mut self) -> Option<Expr> {
+        let mut items = Vec::new();
+
+        while !self.is_match(TokenKind::RightBracket) {
+            items.push(self.parse_expr()?);
+
+            // Lists may span several lines, so skip any newline following an item
+            while self.is_match(TokenKind::Newline) {
+                self.advance();
+            }
+
+            if !self.is_match(TokenKind::Comma) {
+                break;
+            }
+
+            self.advance();
+        }
+
+        self.consume(TokenKind::RightBracket)?;
+
+        Some(Expr::List(items))
+    }
+
+    fn parse_map(&mut self) -> Option<Expr> {
+        let mut entries = Vec::new();
+
+        while !self.is_match(TokenKind::RightBrace) {
+            let key = self.parse_map_key()?;
+            let colon = self.consume(TokenKind::Colon)?;
+            let value = self.parse_expr()?;
+            entries.push((key, colon, value));
+
+            // Maps may span several lines, so skip any newline following an entry
+            while self.is_match(TokenKind::Newline) {
+                self.advance();
+            }
+
+            if !self.is_match(TokenKind::Comma) {
+                break;
+            }
+
+            self.advance();
+        }
+
+        self.consume(TokenKind::RightBrace)?;
+
+        Some(Expr::Map(entries))
+    }
+
+    fn parse_map_key(&mut self) -> Option<Expr> {
+        // A bare identifier before a colon names its key directly, as in '{ count: 3 }'
+        if let TokenKind::Literal(Literal::Identifier(name)) = self.peek().kind.clone() {
+            if self.peek_next().kind == TokenKind::Colon {
+                let tok = self.advance();
+
+                return Some(Expr::Literal(Token {
+                    kind: TokenKind::Literal(Literal::String(name)),
+                    position: tok.position,
+                    span: tok.span,
+                }));
+            }
+        }
+
+        self.parse_expr()
+    }
+
+    fn parse_arguments(&mut self) -> Option<Vec<Expr>> {
+        let mut args = Vec::new();
+
+        if !self.is_match(TokenKind::RightParen) {
+            loop {
+                args.push(self.parse_expr()?);
+
+                if !self.is_match(TokenKind::Comma) {
+                    break;
+                }
+
+                self.advance();
+            }
+        }
+
+        self.consume(TokenKind::RightParen)?;
+
+        Some(args)
+    }
+
+    fn parse_call(&mut self) -> Option<Expr> {
+        let mut expr = self.parse_primary()?;
+
+        loop {
+            if self.is_match(TokenKind::LeftParen) {
+                let paren = self.advance();
+                let args = self.parse_arguments()?;
+                expr = Expr::Call(Box::new(expr), paren, args);
+            } else if self.is_match(TokenKind::Dot) {
+                // 'value.name(args)' calls the builtin 'name' with the value as its first
+                // argument
+                self.advance();
+                let name = self.consume_discriminant(TokenKind::Literal(Literal::Identifier(
+                    Rc::from("any"),
+                )))?;
+                let paren = self.consume(TokenKind::LeftParen)?;
+                let args = self.parse_arguments()?;
+                expr = Expr::Method(Box::new(expr), name, paren, args);
+            } else if self.is_match(TokenKind::LeftBracket) {
+                let bracket = self.advance();
+                let index = self.parse_range()?;
+                self.consume(TokenKind::RightBracket)?;
+                expr = Expr::Index(Box::new(expr), bracket, Box::new(index));
+            } else {
+                break;
+            }
+        }
+
+        Some(expr)
+    }
+
     fn parse_unary(&mut self) -> Option<Expr> {
-        while self.is_match(TokenKind::Bang) || self.is_match(TokenKind::Minus) {
+        if self.is_match(TokenKind::Bang)
+            || self.is_match(TokenKind::Minus)
+            || self.is_match(TokenKind::Tilde)
+        {
             let op = self.advance();
             let rhs = self.parse_unary()?;
 
             return Some(Expr::Unary(op, Box::new(rhs)));
         }
 
-        self.parse_primary()
+        self.parse_power()
+    }
+
+    // Powers bind tighter than a unary operator to their left, so '-2 ** 2' is -4, but not
+    // one in their exponent, so '2 ** -1' is 0.5. They're right-associative, so '2 ** 3 ** 2'
+    // is 2 ** 9
+    fn parse_power(&mut self) -> Option<Expr> {
+        let base = self.parse_call()?;
+
+        if !self.is_match(TokenKind::StarStar) {
+            return Some(base);
+        }
+
+        let op = self.advance();
+        let exponent = self.parse_unary()?;
+
+        Some(Expr::Binary(Box::new(base), op, Box::new(exponent)))
     }
 
     fn parse_factor(&mut self) -> Option<Expr> {
@@ -189,16 +544,67 @@ impl Parser {
         Some(expr)
     }
 
-    fn parse_comparison(&mut self) -> Option<Expr> {
+    // Bitwise operators bind looser than arithmetic but tighter than comparisons, so
+    // 'x & 1 == 0' tests the lowest bit of x. From tightest to loosest they are shifts, '&',
+    // '^' and '|'
+    fn parse_shift(&mut self) -> Option<Expr> {
         let mut expr = self.parse_term()?;
 
+        while self.is_match(TokenKind::LessLess) || self.is_match(TokenKind::GreaterGreater) {
+            let op = self.advance();
+            let rhs = self.parse_term()?;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+        }
+
+        Some(expr)
+    }
+
+    fn parse_bit_and(&mut self) -> Option<Expr> {
+        let mut expr = self.parse_shift()?;
+
+        while self.is_match(TokenKind::Ampersand) {
+            let op = self.advance();
+            let rhs = self.parse_shift()?;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+        }
+
+        Some(expr)
+    }
+
+    fn parse_bit_xor(&mut self) -> Option<Expr> {
+        let mut expr = self.parse_bit_and()?;
+
+        while self.is_match(TokenKind::Caret) {
+            let op = self.advance();
+            let rhs = self.parse_bit_and()?;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+        }
+
+        Some(expr)
+    }
+
+    fn parse_bit_or(&mut self) -> Option<Expr> {
+        let mut expr = self.parse_bit_xor()?;
+
+        while self.is_match(TokenKind::Pipe) {
+            let op = self.advance();
+            let rhs = self.parse_bit_xor()?;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+        }
+
+        Some(expr)
+    }
+
+    fn parse_comparison(&mut self) -> Option<Expr> {
+        let mut expr = self.parse_bit_or()?;
+
         while self.is_match(TokenKind::Less)
             || self.is_match(TokenKind::LessEqual)
             || self.is_match(TokenKind::Greater)
             || self.is_match(TokenKind::GreaterEqual)
         {
             let op = self.advance();
-            let rhs = self.parse_term()?;
+            let rhs = self.parse_bit_or()?;
             expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
         }
 
@@ -223,7 +629,7 @@ impl Parser {
         while self.is_match(TokenKind::And) {
             let op = self.advance();
             let rhs = self.parse_equality()?;
-            expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+            expr = Expr::Logical(Box::new(expr), op, Box::new(rhs));
         }
 
         Some(expr)
@@ -232,17 +638,108 @@ impl Parser {
     fn parse_or(&mut self) -> Option<Expr> {
         let mut expr = self.parse_and()?;
 
-        while self.is_match(TokenKind::Or) {
+        // 'or return' is left for declarations to treat as an early exit
+        while self.is_match(TokenKind::Or) && self.peek_next().kind != TokenKind::Return {
             let op = self.advance();
             let rhs = self.parse_and()?;
-            expr = Expr::Binary(Box::new(expr), op, Box::new(rhs));
+            expr = Expr::Logical(Box::new(expr), op, Box::new(rhs));
+        }
+
+        Some(expr)
+    }
+
+    // Parses 'condition ? then : else', which is right-associative so that conditionals can
+    // be chained like elif clauses
+    fn parse_conditional(&mut self) -> Option<Expr> {
+        let condition = self.parse_or()?;
+
+        if !self.is_match(TokenKind::Question) {
+            return Some(condition);
+        }
+
+        let question = self.advance();
+        let then_value = self.parse_expr()?;
+        self.consume(TokenKind::Colon)?;
+        let else_value = self.parse_conditional()?;
+
+        Some(Expr::Conditional(
+            Box::new(condition),
+            question,
+            Box::new(then_value),
+            Box::new(else_value),
+        ))
+    }
+
+    fn parse_assignment(&mut self) -> Option<Expr> {
+        let expr = self.parse_conditional()?;
+
+        if self.is_match(TokenKind::Equal) {
+            let equals = self.advance();
+
+            // Recurse to make assignment right-associative
+            let value = self.parse_assignment()?;
+
+            return match expr {
+                Expr::Variable(name) => Some(Expr::Assign(name, Box::new(value))),
+                // Slices are copies, so assigning to one would have no effect
+                Expr::Index(target, bracket, index) if !matches!(*index, Expr::Range(..)) => {
+                    Some(Expr::IndexAssign(target, bracket, index, Box::new(value)))
+                }
+                _ => {
+                    self.error(Message::new("invalid-assignment-target"), equals.position);
+                    None
+                }
+            };
+        }
+
+        if let Some(kind) = Self::compound_operator(&self.peek().kind) {
+            let compound = self.advance();
+            let value = self.parse_assignment()?;
+
+            // `x += 1` is sugar for `x = x + 1`, with the operator taking the position of the
+            // compound token so errors in the arithmetic point at it
+            let op = Token {
+                kind,
+                ..compound.clone()
+            };
+
+            return match expr {
+                Expr::Variable(name) => {
+                    let current = Expr::Variable(name.clone());
+                    let value = Expr::Binary(Box::new(current), op, Box::new(value));
+                    Some(Expr::Assign(name, Box::new(value)))
+                }
+                // The list and index of an indexed target are evaluated twice, once to read
+                // the element and once to assign it
+                Expr::Index(target, bracket, index) if !matches!(*index, Expr::Range(..)) => {
+                    let current = Expr::Index(target.clone(), bracket.clone(), index.clone());
+                    let value = Expr::Binary(Box::new(current), op, Box::new(value));
+                    Some(Expr::IndexAssign(target, bracket, index, Box::new(value)))
+                }
+                _ => {
+                    self.error(Message::new("invalid-assignment-target"), compound.position);
+                    None
+                }
+            };
         }
 
         Some(expr)
     }
 
+    // The arithmetic operator a compound assignment token applies
+    fn compound_operator(kind: &TokenKind) -> Option<TokenKind> {
+        match kind {
+            TokenKind::PlusEqual => Some(TokenKind::Plus),
+            TokenKind::MinusEqual => Some(TokenKind::Minus),
+            TokenKind::StarEqual => Some(TokenKind::Star),
+            TokenKind::SlashEqual => Some(TokenKind::Slash),
+            TokenKind::PercentEqual => Some(TokenKind::Percent),
+            _ => None,
+        }
+    }
+
     pub fn parse_expr(&mut self) -> Option<Expr> {
-        self.parse_or()
+        self.parse_assignment()
     }
 
     fn parse_print(&mut self) -> Option<Statement> {
@@ -252,9 +749,189 @@ impl Parser {
         Some(Statement::Print(value))
     }
 
+    fn parse_block(&mut self) -> Option<Vec<Statement>> {
+        self.consume(TokenKind::LeftBrace)?;
+
+        let mut statements = Vec::new();
+
+        while !self.is_match(TokenKind::RightBrace) && !self.reached_end() {
+            statements.extend(self.recover_declaration());
+        }
+
+        self.consume(TokenKind::RightBrace)?;
+
+        Some(statements)
+    }
+
+    // Parses either a block or a short-form body introduced by a colon, such as
+    // 'if x > 0: print x', which is desugared into a single-statement block
+    fn parse_body(&mut self) -> Option<Vec<Statement>> {
+        if self.is_match(TokenKind::Colon) {
+            self.advance();
+            return Some(vec![self.parse_statement()?]);
+        }
+
+        self.parse_block()
+    }
+
+    // Short-form bodies end with a newline, which may separate them from an elif or else
+    fn is_continued_by(&mut self, kind: TokenKind) -> bool {
+        if self.is_match(TokenKind::Newline) && self.peek_next().kind == kind {
+            self.advance();
+        }
+
+        self.is_match(kind)
+    }
+
+    fn parse_if(&mut self) -> Option<Statement> {
+        let keyword = self.consume(TokenKind::If)?;
+
+        let condition = self.parse_expr()?;
+        let then_block = self.parse_body()?;
+
+        let mut elif_blocks = Vec::new();
+
+        while self.is_continued_by(TokenKind::Elif) {
+            let elif_keyword = self.advance();
+
+            let elif_condition = self.parse_expr()?;
+            let elif_block = self.parse_body()?;
+            elif_blocks.push((elif_keyword, elif_condition, elif_block));
+        }
+
+        let else_block = if self.is_continued_by(TokenKind::Else) {
+            self.advance();
+            Some(self.parse_body()?)
+        } else {
+            None
+        };
+
+        Some(Statement::If(
+            keyword,
+            condition,
+            then_block,
+            elif_blocks,
+            else_block,
+        ))
+    }
+
+    fn parse_loop(&mut self) -> Option<Statement> {
+        let keyword = self.consume(TokenKind::Loop)?;
+
+        self.loop_depth += 1;
+        let body = self.parse_body();
+        self.loop_depth -= 1;
+
+        Some(Statement::Loop(keyword, body?))
+    }
+
+    fn parse_while(&mut self) -> Option<Statement> {
+        let keyword = self.consume(TokenKind::While)?;
+
+        let condition = self.parse_expr()?;
+
+        self.loop_depth += 1;
+        let body = self.parse_body();
+        self.loop_depth -= 1;
+
+        Some(Statement::While(keyword, condition, body?))
+    }
+
+    // Parses an expression, or a range if it is followed by '..'. Ranges are only allowed
+    // where a for loop's iterable or a slice's bounds are expected
+    fn parse_range(&mut self) -> Option<Expr> {
+        let start = self.parse_or()?;
+
+        if !self.is_match(TokenKind::DotDot) {
+            return Some(start);
+        }
+
+        let op = self.advance();
+        let end = self.parse_or()?;
+
+        Some(Expr::Range(Box::new(start), op, Box::new(end)))
+    }
+
+    fn parse_for(&mut self) -> Option<Statement> {
+        let keyword = self.consume(TokenKind::For)?;
+
+        let name =
+            self.consume_discriminant(TokenKind::Literal(Literal::Identifier(Rc::from("any"))))?;
+
+        self.consume(TokenKind::In)?;
+
+        let iterable = self.parse_range()?;
+
+        self.loop_depth += 1;
+        let body = self.parse_body();
+        self.loop_depth -= 1;
+
+        Some(Statement::For(keyword, name, iterable, body?))
+    }
+
+    fn parse_loop_control(&mut self) -> Option<Statement> {
+        let tok = self.advance();
+
+        if self.loop_depth == 0 {
+            self.error(
+                Message::new("outside-loop").debug_arg(&tok.kind),
+                tok.position,
+            );
+            return None;
+        }
+
+        let control = match tok.kind {
+            TokenKind::Break => Statement::Break,
+            _ => Statement::Continue,
+        };
+
+        // Desugar guard clauses like 'break if x > 10' into an if statement
+        if self.is_match(TokenKind::If) {
+            let keyword = self.advance();
+
+            let condition = self.parse_expr()?;
+            return Some(Statement::If(
+                keyword,
+                condition,
+                vec![control],
+                Vec::new(),
+                None,
+            ));
+        }
+
+        Some(control)
+    }
+
+    fn parse_return(&mut self) -> Option<Statement> {
+        let tok = self.consume(TokenKind::Return)?;
+
+        if self.func_depth == 0 {
+            self.error(Message::new("outside-function"), tok.position);
+            return None;
+        }
+
+        let value = if self.is_match(TokenKind::Newline)
+            || self.is_match(TokenKind::RightBrace)
+            || self.is_match(TokenKind::Eof)
+        {
+            None
+        } else {
+            Some(self.parse_expr()?)
+        };
+
+        Some(Statement::Return(value))
+    }
+
     fn parse_statement(&mut self) -> Option<Statement> {
         match self.peek().kind {
             TokenKind::Print => self.parse_print(),
+            TokenKind::If => self.parse_if(),
+            TokenKind::LeftBrace => Some(Statement::Block(self.parse_block()?)),
+            TokenKind::Loop => self.parse_loop(),
+            TokenKind::While => self.parse_while(),
+            TokenKind::For => self.parse_for(),
+            TokenKind::Break | TokenKind::Continue => self.parse_loop_control(),
+            TokenKind::Return => self.parse_return(),
 
             // Return expression
             _ => {
@@ -264,81 +941,199 @@ impl Parser {
         }
     }
 
-    fn parse_var_decl(&mut self) -> Option<Statement> {
+    fn parse_var_decl(&mut self) -> Option<Vec<Statement>> {
         self.consume(TokenKind::Let);
 
-        let identifier = self
-            .consume_discriminant(TokenKind::Literal(Literal::Identifier(String::from("any"))))?;
-
-        let name = match identifier.kind {
-            TokenKind::Literal(Literal::Identifier(val)) => val,
-            _ => return None,
-        };
+        let name_tok = self.peek().clone();
+        let name = self.consume_name()?;
 
         let initializer = if self.is_match(TokenKind::Equal) {
             let equals = self.advance();
+            let start = self.mark();
 
-            // Attempt to get expression, returning None on failure
+            // A variable whose value fails to parse is still declared, so that tooling knows
+            // of it, with an error node standing in for the value
             if !self.reached_end() {
-                let pre = self.peek();
+                let pre = self.peek().clone();
                 let expr = self.parse_expr();
 
                 match expr {
                     Some(..) => expr,
                     None => {
-                        self.display_error(
-                            format!(
-                                "Expected expession after assignment operator, found {:?} instead",
-                                pre.kind
-                            ),
+                        self.error(
+                            Message::new("expected-assigned-value").debug_arg(&pre.kind),
                             equals.position,
                         );
-                        return None;
+
+                        let value = Expr::Error(self.skip_statement(start));
+                        return Some(vec![Statement::VarDecl(name, Some(value))]);
                     }
                 }
             } else {
-                self.display_error(
-                    "Expected expession after assignment operator",
-                    equals.position,
-                );
-                return None;
+                self.error(Message::new("missing-assigned-value"), equals.position);
+
+                let value = Expr::Error(self.skip_statement(start));
+                return Some(vec![Statement::VarDecl(name, Some(value))]);
             }
+        } else {
+            return Some(vec![Statement::VarDecl(name, None)]);
+        };
+
+        // Desugar 'let x = value else fallback' and 'let x = value or return' into a check on
+        // the freshly declared variable that runs when its value is nil
+        let fallback = if self.is_match(TokenKind::Else) {
+            self.advance();
+
+            let fallback = self.parse_expr()?;
+            Some(Statement::Expr(Expr::Assign(
+                name_tok.clone(),
+                Box::new(fallback),
+            )))
+        } else if self.is_match(TokenKind::Or) && self.peek_next().kind == TokenKind::Return {
+            self.advance();
+            Some(self.parse_return()?)
         } else {
             None
         };
 
-        Some(Statement::VarDecl(name, initializer))
+        let mut statements = vec![Statement::VarDecl(name, initializer)];
+
+        if let Some(fallback) = fallback {
+            // Compared with nil rather than tested, so that false is kept like any other value
+            // and strict semantics don't reject the check
+            let is_nil = Expr::Binary(
+                Box::new(Expr::Variable(name_tok.clone())),
+                Token {
+                    kind: TokenKind::EqualEqual,
+                    ..name_tok.clone()
+                },
+                Box::new(Expr::Literal(Token {
+                    kind: TokenKind::Literal(Literal::Nil),
+                    ..name_tok.clone()
+                })),
+            );
+            let keyword = Token {
+                kind: TokenKind::If,
+                ..name_tok
+            };
+
+            statements.push(Statement::If(
+                keyword,
+                is_nil,
+                vec![fallback],
+                Vec::new(),
+                None,
+            ));
+        }
+
+        Some(statements)
+    }
+
+    fn parse_func_decl(&mut self) -> Option<Statement> {
+        self.consume(TokenKind::Func)?;
+
+        let name = self.consume_name()?;
+        let (params, body) = self.parse_func_body()?;
+
+        Some(Statement::Func(name, params, Rc::new(body)))
     }
 
-    fn parse_declaration(&mut self) -> Option<Statement> {
-        let decl = match self.peek().kind {
+    // Parses the parameter list and body shared by function declarations and expressions
+    fn parse_func_body(&mut self) -> Option<(Vec<Name>, Vec<Statement>)> {
+        self.consume(TokenKind::LeftParen)?;
+
+        let mut params = Vec::new();
+
+        if !self.is_match(TokenKind::RightParen) {
+            loop {
+                params.push(self.consume_name()?);
+
+                if !self.is_match(TokenKind::Comma) {
+                    break;
+                }
+
+                self.advance();
+            }
+        }
+
+        self.consume(TokenKind::RightParen)?;
+
+        // Loops surrounding the declaration can't be broken out of from the function body
+        let loop_depth = self.loop_depth;
+        self.loop_depth = 0;
+        self.func_depth += 1;
+
+        let body = self.parse_block();
+
+        self.func_depth -= 1;
+        self.loop_depth = loop_depth;
+
+        Some((params, body?))
+    }
+
+    // Some declarations desugar into several statements, so these are returned as a list
+    fn parse_declaration(&mut self) -> Option<Vec<Statement>> {
+        let decls = match self.peek().kind {
             TokenKind::Let => self.parse_var_decl()?,
-            _ => self.parse_statement()?,
+            TokenKind::Func => vec![self.parse_func_decl()?],
+            _ => vec![self.parse_statement()?],
         };
 
-        self.consume(TokenKind::Newline)?;
+        self.consume_terminator()?;
 
-        Some(decl)
+        Some(decls)
     }
 
-    pub fn collect_statements(&mut self) -> Option<Vec<Statement>> {
+    // Parses a declaration, or skips over it if it fails to parse, leaving an error node in
+    // its place
+    fn recover_declaration(&mut self) -> Vec<Statement> {
+        let start = self.mark();
+
+        match self.parse_declaration() {
+            Some(decls) => decls,
+            None => {
+                let skipped = self.skip_statement(start);
+                self.consume_terminator();
+
+                vec![Statement::Error(skipped)]
+            }
+        }
+    }
+
+    // Parses as much of a program as possible, even if it has errors. Statements and values
+    // that fail to parse are replaced by error nodes, so tooling can still make use of the
+    // rest of a file that doesn't compile
+    //
+    // Errors from lexing take the place of any from parsing, which are likely to have been
+    // caused by the tokens the lexer had to leave out
+    pub fn collect_partial(&mut self) -> (Vec<Statement>, Vec<LulaError>) {
         let mut statements = Vec::new();
-        let mut contains_error = false;
 
         while !self.reached_end() {
-            match self.parse_declaration() {
-                Some(stmt) => statements.push(stmt),
-                None => {
-                    contains_error = true;
-                    self.synchronize();
-                }
-            }
+            // Only the last token of earlier statements is ever looked back at
+            let earlier = self.consumed.len().saturating_sub(1);
+            self.consumed.drain(..earlier);
+
+            statements.extend(self.recover_declaration());
         }
 
-        if !contains_error {
-            Some(statements)
+        let errors = if self.lexing_errors.is_empty() {
+            self.errors.drain(..).collect()
         } else {
-            None
+            self.errors.clear();
+            self.lexing_errors.drain(..).collect()
+        };
+
+        (statements, errors)
+    }
+
+    pub fn collect_statements(&mut self) -> Result<Vec<Statement>, Vec<LulaError>> {
+        let (statements, errors) = self.collect_partial();
+
+        if errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(errors)
         }
     }
 }
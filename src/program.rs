@@ -0,0 +1,52 @@
+use std::cell::OnceCell;
+use std::rc::Rc;
+
+use crate::compiler::{compile, Chunk};
+use crate::error::LulaError;
+use crate::lexer::Lexer;
+use crate::optimize::optimize;
+use crate::parser::Parser;
+use crate::statement::Statement;
+
+// A program lexed, parsed and optimized ahead of time, so that a host running the same script
+// many times only pays for that once. The VM compiles it to bytecode the first time it runs
+// it, and reuses that on later runs
+#[derive(Debug)]
+pub struct Program {
+    statements: Vec<Statement>,
+    chunk: OnceCell<Rc<Chunk>>,
+}
+
+impl Program {
+    pub fn compile(source: &str) -> Result<Program, Vec<LulaError>> {
+        let mut parser = Parser::new(Lexer::new(source.to_owned()));
+        let statements = parser.collect_statements()?;
+
+        Ok(Program::from(optimize(statements)))
+    }
+
+    pub fn statements(&self) -> &[Statement] {
+        &self.statements
+    }
+
+    // Compiles the program to bytecode, unless it already has been. Programs that fail to
+    // compile aren't cached, and fail the same way each time
+    pub(crate) fn chunk(&self) -> Result<Rc<Chunk>, LulaError> {
+        if let Some(chunk) = self.chunk.get() {
+            return Ok(Rc::clone(chunk));
+        }
+
+        let chunk = Rc::new(compile(&self.statements)?);
+        Ok(Rc::clone(self.chunk.get_or_init(|| chunk)))
+    }
+}
+
+// Wraps statements parsed by the host, which are run as they are
+impl From<Vec<Statement>> for Program {
+    fn from(statements: Vec<Statement>) -> Program {
+        Program {
+            statements,
+            chunk: OnceCell::new(),
+        }
+    }
+}
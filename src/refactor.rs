@@ -0,0 +1,442 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+
+use crate::error::LulaError;
+use crate::lexer::{is_identifier_continue, is_identifier_start};
+use crate::parser::Parser;
+use crate::resolver::{resolve, Access, Resolution};
+use crate::rewrite::{resynthesize, source_tokens, SourceToken};
+use crate::statement::Statement;
+use crate::token::{keyword, Position, TokenKind};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RefactorError {
+    // The source doesn't parse, so it can't be safely rewritten
+    Syntax(Vec<LulaError>),
+
+    // The refactoring doesn't apply at the given place, or would change what the program
+    // does
+    Rejected(String),
+}
+
+impl Display for RefactorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RefactorError::Syntax(errors) => {
+                write!(f, "Source contains {} syntax error(s)", errors.len())
+            }
+            RefactorError::Rejected(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+fn parse(source: &str) -> Result<(Vec<SourceToken>, Vec<Statement>), RefactorError> {
+    let tokens = source_tokens(source).map_err(RefactorError::Syntax)?;
+    let statements = Parser::new(tokens.iter().map(|t| Ok(t.token.clone())))
+        .collect_statements()
+        .map_err(RefactorError::Syntax)?;
+
+    Ok((tokens, statements))
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars.next().is_some_and(is_identifier_start)
+        && chars.all(is_identifier_continue)
+        && keyword(name).is_none()
+}
+
+// Labels each occurrence with the variable it refers to, with undeclared names standing
+// for themselves
+fn occurrence_labels(resolution: &Resolution) -> Vec<(Option<usize>, &str)> {
+    resolution
+        .occurrences
+        .iter()
+        .map(|occ| match occ.symbol {
+            Some(symbol) => (Some(symbol), ""),
+            None => (None, occ.name.as_str()),
+        })
+        .collect()
+}
+
+// Whether two resolutions of the same program link its names up in the same way
+fn same_bindings(before: &Resolution, after: &Resolution) -> bool {
+    let before = occurrence_labels(before);
+    let after = occurrence_labels(after);
+
+    if before.len() != after.len() {
+        return false;
+    }
+
+    let mut forward = HashMap::new();
+    let mut backward = HashMap::new();
+
+    before.iter().zip(&after).all(|(old, new)| {
+        *forward.entry(old).or_insert(new) == new && *backward.entry(new).or_insert(old) == old
+    })
+}
+
+// Renames the variable whose name covers a line and column, counted from 1, along with
+// every other occurrence of the same variable. The rest of the source is left untouched
+pub fn rename(
+    source: &str,
+    line: usize,
+    column: usize,
+    new_name: &str,
+) -> Result<String, RefactorError> {
+    let (mut tokens, statements) = parse(source)?;
+    let resolution = resolve(&statements);
+
+    let position = Position(line.saturating_sub(1), column.saturating_sub(1), 0);
+    let occurrence = resolution.occurrence_at(position).ok_or_else(|| {
+        RefactorError::Rejected(format!("No variable found at {}:{}", line, column))
+    })?;
+
+    let symbol = occurrence.symbol.ok_or_else(|| {
+        RefactorError::Rejected(format!(
+            "'{}' is not declared in this file",
+            occurrence.name
+        ))
+    })?;
+
+    if !is_identifier(new_name) {
+        return Err(RefactorError::Rejected(format!(
+            "'{}' is not a valid variable name",
+            new_name
+        )));
+    }
+
+    let positions: HashSet<Position> = resolution
+        .occurrences
+        .iter()
+        .filter(|occ| occ.symbol == Some(symbol))
+        .map(|occ| occ.position)
+        .collect();
+
+    for token in &mut tokens {
+        if positions.contains(&token.token.position) {
+            token.text = new_name.to_owned();
+        }
+    }
+
+    let renamed = resynthesize(&tokens);
+
+    // The new name mustn't capture or be captured by any other variable
+    let (_, renamed_statements) = parse(&renamed)?;
+
+    if !same_bindings(&resolution, &resolve(&renamed_statements)) {
+        return Err(RefactorError::Rejected(format!(
+            "Renaming '{}' to '{}' would change what other names refer to",
+            resolution.symbols[symbol].name, new_name
+        )));
+    }
+
+    Ok(renamed)
+}
+
+// Whether a token can only begin a new statement. The lexer emits no newline after a
+// closing brace, so any other token following one may continue the statement before it
+fn starts_statement(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Literal(..)
+            | TokenKind::LeftBrace
+            | TokenKind::Bang
+            | TokenKind::If
+            | TokenKind::Func
+            | TokenKind::Let
+            | TokenKind::Loop
+            | TokenKind::While
+            | TokenKind::For
+            | TokenKind::Print
+            | TokenKind::Return
+            | TokenKind::Break
+            | TokenKind::Continue
+    )
+}
+
+fn bracket_depth_change(kind: &TokenKind) -> isize {
+    match kind {
+        TokenKind::LeftParen | TokenKind::LeftBrace | TokenKind::LeftBracket => 1,
+        TokenKind::RightParen | TokenKind::RightBrace | TokenKind::RightBracket => -1,
+        _ => 0,
+    }
+}
+
+fn is_newline(token: &SourceToken) -> bool {
+    token.token.kind == TokenKind::Newline
+}
+
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+// Finds the tokens on a range of lines, counted from 0, provided they make up whole
+// statements of a single block
+fn statement_tokens(
+    tokens: &[SourceToken],
+    first_line: usize,
+    last_line: usize,
+) -> Option<(usize, usize)> {
+    let on_lines = |token: &SourceToken| {
+        let line = token.token.position.0;
+        token.token.kind != TokenKind::Eof && (first_line..=last_line).contains(&line)
+    };
+
+    let first = tokens.iter().position(|t| on_lines(t) && !is_newline(t))?;
+    let last = tokens.iter().rposition(|t| on_lines(t) && !is_newline(t))?;
+
+    // No token, such as a string, may run across either end of the lines. Newlines end the
+    // line they are on
+    let straddles = tokens.iter().filter(|t| !is_newline(t)).any(|t| {
+        let start = t.token.position.0;
+        let end = start + t.text.matches('\n').count();
+        (start < first_line && end >= first_line) || (start <= last_line && end > last_line)
+    });
+
+    let mut depth = 0;
+    let balanced = tokens[first..=last].iter().all(|t| {
+        depth += bracket_depth_change(&t.token.kind);
+        depth >= 0
+    }) && depth == 0;
+
+    let starts_cleanly = match first.checked_sub(1).map(|i| &tokens[i].token.kind) {
+        None | Some(TokenKind::Newline) | Some(TokenKind::LeftBrace) => true,
+        Some(TokenKind::RightBrace) => starts_statement(&tokens[first].token.kind),
+        Some(..) => false,
+    };
+
+    let following = tokens[last + 1..].iter().find(|t| !is_newline(t));
+    let ends_cleanly = match tokens.get(last + 1).map(|t| &t.token.kind) {
+        None | Some(TokenKind::Newline) | Some(TokenKind::RightBrace) | Some(TokenKind::Eof) => {
+            true
+        }
+        Some(kind) => tokens[last].token.kind == TokenKind::RightBrace && starts_statement(kind),
+    } && !following
+        .is_some_and(|t| matches!(t.token.kind, TokenKind::Elif | TokenKind::Else));
+
+    if straddles || !balanced || !starts_cleanly || !ends_cleanly {
+        return None;
+    }
+
+    Some((first, last))
+}
+
+// Finds the line of the outermost statement holding a token, counted from 0
+fn outermost_statement_line(tokens: &[SourceToken], index: usize) -> usize {
+    let mut depth = 0;
+    let mut line = tokens[index].token.position.0;
+
+    for (i, token) in tokens[..index].iter().enumerate() {
+        let kind = &token.token.kind;
+        let starts = match i.checked_sub(1).map(|i| &tokens[i].token.kind) {
+            None | Some(TokenKind::Newline) => true,
+            Some(TokenKind::RightBrace) => starts_statement(kind),
+            Some(..) => false,
+        };
+
+        if depth == 0 && starts && *kind != TokenKind::Newline {
+            line = token.token.position.0;
+        }
+
+        depth += bracket_depth_change(kind);
+    }
+
+    if depth == 0 {
+        tokens[index].token.position.0
+    } else {
+        line
+    }
+}
+
+// Moves a range of lines, counted from 1, into a new function declared before the
+// outermost statement holding them, and replaces them with a call to it. Local variables
+// the lines use become parameters, and those they assign or declare for later code are
+// returned, in a list when there are several
+pub fn extract_function(
+    source: &str,
+    first_line: usize,
+    last_line: usize,
+    name: &str,
+) -> Result<String, RefactorError> {
+    let (tokens, statements) = parse(source)?;
+    let resolution = resolve(&statements);
+    let lines: Vec<&str> = source.split('\n').collect();
+
+    if first_line == 0 || first_line > last_line || last_line > lines.len() {
+        return Err(RefactorError::Rejected(format!(
+            "Lines {}-{} are not in the file",
+            first_line, last_line
+        )));
+    }
+
+    let (first, last) = (first_line - 1, last_line - 1);
+    let rejected = || {
+        RefactorError::Rejected(format!(
+            "Lines {}-{} are not whole statements that can run on their own",
+            first_line, last_line
+        ))
+    };
+
+    let (first_token, last_token) = statement_tokens(&tokens, first, last).ok_or_else(rejected)?;
+
+    // Statements that return, break or continue out of the lines can't be moved
+    Parser::new(
+        tokens[first_token..=last_token]
+            .iter()
+            .map(|t| Ok(t.token.clone())),
+    )
+    .collect_statements()
+    .map_err(|_| rejected())?;
+
+    let taken = |name: &str| {
+        resolution.symbols.iter().any(|s| s.name == name)
+            || resolution.occurrences.iter().any(|o| o.name == name)
+    };
+
+    let results = format!("{}_results", name);
+
+    if !is_identifier(name) || taken(name) {
+        return Err(RefactorError::Rejected(format!(
+            "'{}' is not a valid, unused function name",
+            name
+        )));
+    }
+
+    let inside = |position: Position| (first..=last).contains(&position.0);
+    let mut params = Vec::new();
+    let mut outputs = Vec::new();
+
+    for occ in &resolution.occurrences {
+        let symbol = match occ.symbol {
+            Some(symbol) => symbol,
+            None => continue,
+        };
+        let declaration = &resolution.symbols[symbol];
+
+        if inside(occ.position) && !inside(declaration.declaration) {
+            // Globals are still in reach of the new function, unless the lines redeclare
+            // them
+            if !declaration.global && !params.contains(&symbol) {
+                if declaration.declaration.0 > last {
+                    return Err(RefactorError::Rejected(format!(
+                        "Lines {}-{} use '{}' before it is declared",
+                        first_line, last_line, declaration.name
+                    )));
+                }
+
+                params.push(symbol);
+            }
+
+            let assigned = match occ.access {
+                Access::Read => false,
+                Access::Write => !declaration.global,
+                Access::Declare => true,
+            };
+
+            if assigned && !outputs.contains(&symbol) {
+                outputs.push(symbol);
+            }
+        } else if !inside(occ.position)
+            && inside(declaration.declaration)
+            && !outputs.contains(&symbol)
+        {
+            outputs.push(symbol);
+        }
+    }
+
+    if outputs.len() > 1 && taken(&results) {
+        return Err(RefactorError::Rejected(format!(
+            "'{}' is already in use, so can't hold the function's results",
+            results
+        )));
+    }
+
+    let symbol_name = |symbol: &usize| resolution.symbols[*symbol].name.as_str();
+    let declares = |symbol: &usize| inside(resolution.symbols[*symbol].declaration);
+    let args = params
+        .iter()
+        .map(symbol_name)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    // The function is declared at the indentation of the statement it goes before, with the
+    // lines reindented one level deeper
+    let insert_at = outermost_statement_line(&tokens, first_token);
+    let func_indent = indentation(lines[insert_at]);
+    let common = lines[first..=last]
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| indentation(line).len())
+        .min()
+        .unwrap_or(0);
+
+    let mut func = vec![format!("{}func {}({}) {{", func_indent, name, args)];
+
+    for line in &lines[first..=last] {
+        if line.trim().is_empty() {
+            func.push(String::new());
+        } else {
+            let dedent = indentation(line).len().min(common);
+            func.push(format!("{}    {}", func_indent, &line[dedent..]));
+        }
+    }
+
+    match &outputs[..] {
+        [] => {}
+        [output] => func.push(format!("{}    return {}", func_indent, symbol_name(output))),
+        outputs => func.push(format!(
+            "{}    return [{}]",
+            func_indent,
+            outputs
+                .iter()
+                .map(symbol_name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+
+    func.push(format!("{}}}", func_indent));
+    func.push(String::new());
+
+    let call_indent = indentation(lines[first]);
+    let call = format!("{}({})", name, args);
+    let assign = |symbol: &usize, value: &str| {
+        let keyword = if declares(symbol) { "let " } else { "" };
+        format!(
+            "{}{}{} = {}",
+            call_indent,
+            keyword,
+            symbol_name(symbol),
+            value
+        )
+    };
+
+    let calls = match &outputs[..] {
+        [] => vec![format!("{}{}", call_indent, call)],
+        [output] => vec![assign(output, &call)],
+        outputs => std::iter::once(format!("{}let {} = {}", call_indent, results, call))
+            .chain(
+                outputs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, output)| assign(output, &format!("{}[{}]", results, i))),
+            )
+            .collect(),
+    };
+
+    let extracted = lines[..insert_at]
+        .iter()
+        .map(|line| line.to_string())
+        .chain(func)
+        .chain(lines[insert_at..first].iter().map(|line| line.to_string()))
+        .chain(calls)
+        .chain(lines[last + 1..].iter().map(|line| line.to_string()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    parse(&extracted).map_err(|_| rejected())?;
+
+    Ok(extracted)
+}
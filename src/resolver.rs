@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+
+use crate::expr::Expr;
+use crate::statement::Statement;
+use crate::token::{Literal, Name, Position, Token, TokenKind};
+
+// A variable declared by a program. Redeclaring a name in the same scope reuses the
+// variable, just as it does at runtime
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub declaration: Position,
+
+    // Whether the variable lives in the program's outermost scope
+    pub global: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Declare,
+    Read,
+    Write,
+}
+
+// A place a variable's name appears, whether declaring, reading or assigning it. Names
+// the program never declares, such as builtins, have no symbol
+#[derive(Debug, Clone, PartialEq)]
+pub struct Occurrence {
+    pub name: String,
+    pub position: Position,
+    pub symbol: Option<usize>,
+    pub access: Access,
+}
+
+#[derive(Debug, Default)]
+pub struct Resolution {
+    pub symbols: Vec<Symbol>,
+
+    // Sorted by position, without duplicates
+    pub occurrences: Vec<Occurrence>,
+}
+
+impl Resolution {
+    // Finds the occurrence whose name covers a position
+    pub fn occurrence_at(&self, position: Position) -> Option<&Occurrence> {
+        self.occurrences.iter().find(|occ| {
+            let Position(line, column, source_id) = occ.position;
+
+            line == position.0
+                && source_id == position.2
+                && (column..column + occ.name.chars().count()).contains(&position.1)
+        })
+    }
+}
+
+#[derive(Default)]
+struct Scope {
+    // Every name declared directly in the scope, and whether its declaration has run yet
+    names: HashMap<String, (usize, bool)>,
+
+    // Whether the scope is the outermost scope of a function body
+    function: bool,
+}
+
+#[derive(Default)]
+struct Resolver {
+    scopes: Vec<Scope>,
+    resolution: Resolution,
+}
+
+// Works out which declaration each variable in a program refers to. Code only sees
+// declarations that have run before it, except that function bodies see every
+// declaration of the scopes around them, since they usually run later
+pub fn resolve(statements: &[Statement]) -> Resolution {
+    let mut resolver = Resolver::default();
+    resolver.block(statements, Vec::new(), false);
+
+    let mut resolution = resolver.resolution;
+    resolution.occurrences.sort_by_key(|occ| {
+        let Position(line, column, source_id) = occ.position;
+        (source_id, line, column)
+    });
+    resolution.occurrences.dedup();
+
+    resolution
+}
+
+impl Resolver {
+    // Resolves a block in a scope of its own, holding the given names before anything in
+    // the block runs
+    fn block(&mut self, statements: &[Statement], names: Vec<&Name>, function: bool) {
+        self.scopes.push(Scope {
+            names: HashMap::new(),
+            function,
+        });
+
+        for name in names {
+            self.declare(name);
+        }
+
+        // Note every declaration up front, for function bodies to find
+        for statement in statements {
+            match statement {
+                Statement::VarDecl(name, _) | Statement::Func(name, ..) => {
+                    self.symbol_in_scope(name);
+                }
+                _ => {}
+            }
+        }
+
+        for statement in statements {
+            self.statement(statement);
+        }
+
+        self.scopes.pop();
+    }
+
+    fn symbol_in_scope(&mut self, name: &Name) -> usize {
+        let symbols = &mut self.resolution.symbols;
+        let global = self.scopes.len() == 1;
+        let scope = self.scopes.last_mut().expect("resolver scope underflow");
+
+        let (symbol, _) = *scope
+            .names
+            .entry(name.identifier.clone())
+            .or_insert_with(|| {
+                symbols.push(Symbol {
+                    name: name.identifier.clone(),
+                    declaration: name.position,
+                    global,
+                });
+                (symbols.len() - 1, false)
+            });
+
+        symbol
+    }
+
+    fn declare(&mut self, name: &Name) {
+        let symbol = self.symbol_in_scope(name);
+
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.names.insert(name.identifier.clone(), (symbol, true));
+        }
+
+        self.occurrence(
+            &name.identifier,
+            name.position,
+            Some(symbol),
+            Access::Declare,
+        );
+    }
+
+    fn occurrence(
+        &mut self,
+        name: &str,
+        position: Position,
+        symbol: Option<usize>,
+        access: Access,
+    ) {
+        self.resolution.occurrences.push(Occurrence {
+            name: name.to_owned(),
+            position,
+            symbol,
+            access,
+        });
+    }
+
+    fn reference(&mut self, token: &Token, access: Access) {
+        let name = match &token.kind {
+            TokenKind::Literal(Literal::Identifier(name)) => name,
+            _ => return,
+        };
+
+        let mut in_function = false;
+        let mut symbol = None;
+
+        for scope in self.scopes.iter().rev() {
+            match scope.names.get(&name[..]) {
+                Some((found, declared)) if *declared || in_function => {
+                    symbol = Some(*found);
+                    break;
+                }
+                _ => {}
+            }
+
+            in_function |= scope.function;
+        }
+
+        self.occurrence(name, token.position, symbol, access);
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Print(expr) | Statement::Expr(expr) => self.expr(expr),
+
+            Statement::VarDecl(name, initializer) => {
+                // The initializer runs before the variable is declared
+                if let Some(expr) = initializer {
+                    self.expr(expr);
+                }
+
+                self.declare(name);
+            }
+
+            Statement::If(_, condition, then_block, elif_blocks, else_block) => {
+                self.expr(condition);
+                self.block(then_block, Vec::new(), false);
+
+                for (_, cond, block) in elif_blocks {
+                    self.expr(cond);
+                    self.block(block, Vec::new(), false);
+                }
+
+                if let Some(block) = else_block {
+                    self.block(block, Vec::new(), false);
+                }
+            }
+
+            Statement::Block(block) | Statement::Loop(_, block) => {
+                self.block(block, Vec::new(), false)
+            }
+
+            Statement::While(_, condition, body) => {
+                self.expr(condition);
+                self.block(body, Vec::new(), false);
+            }
+
+            Statement::For(_, name, iterable, body) => {
+                self.expr(iterable);
+
+                if let TokenKind::Literal(Literal::Identifier(identifier)) = &name.kind {
+                    let name = Name {
+                        identifier: identifier.to_string(),
+                        position: name.position,
+                    };
+                    self.block(body, vec![&name], false);
+                }
+            }
+
+            Statement::Break
+            | Statement::Continue
+            | Statement::Return(None)
+            | Statement::Error(..) => {}
+            Statement::Return(Some(expr)) => self.expr(expr),
+
+            Statement::Func(name, params, body) => {
+                // Declared before its body is resolved, so that it can call itself
+                self.declare(name);
+                self.block(body, params.iter().collect(), true);
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(..) | Expr::Error(..) | Expr::Placeholder(..) => {}
+            Expr::Variable(name) => self.reference(name, Access::Read),
+
+            Expr::Assign(name, value) => {
+                self.expr(value);
+                self.reference(name, Access::Write);
+            }
+
+            Expr::Unary(_, inner) | Expr::Grouping(_, inner, _) => self.expr(inner),
+
+            Expr::Binary(lhs, _, rhs)
+            | Expr::Logical(lhs, _, rhs)
+            | Expr::Range(lhs, _, rhs)
+            | Expr::Index(lhs, _, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+
+            Expr::Call(callee, _, args) | Expr::Method(callee, _, _, args) => {
+                self.expr(callee);
+
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+
+            Expr::List(items) => {
+                for item in items {
+                    self.expr(item);
+                }
+            }
+
+            Expr::Map(entries) => {
+                for (key, _, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+
+            Expr::IndexAssign(target, _, index, value) => {
+                self.expr(target);
+                self.expr(index);
+                self.expr(value);
+            }
+
+            Expr::Conditional(condition, _, then_value, else_value) => {
+                self.expr(condition);
+                self.expr(then_value);
+                self.expr(else_value);
+            }
+
+            Expr::Func(params, body) => self.block(body, params.iter().collect(), true),
+        }
+    }
+}
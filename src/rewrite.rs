@@ -0,0 +1,51 @@
+use crate::error::LulaError;
+use crate::lexer::Lexer;
+use crate::token::Token;
+
+// A token together with the exact text it was lexed from and the whitespace, comments and
+// ignored newlines that came before it. Together these cover every character of the
+// source, so refactoring tools can edit the text of a few tokens and rebuild the rest of
+// the file exactly as the user wrote it
+#[derive(Debug, Clone)]
+pub struct SourceToken {
+    pub token: Token,
+    pub trivia: String,
+    pub text: String,
+}
+
+// Lexes source into tokens that keep their original text. The end-of-file token carries
+// any trailing whitespace and comments
+pub fn source_tokens(source: &str) -> Result<Vec<SourceToken>, Vec<LulaError>> {
+    let mut trivia_start = 0;
+
+    let tokens = Lexer::new(source.to_owned())
+        .collect_tokens()?
+        .into_iter()
+        .map(|token| {
+            let span = token.span;
+            let trivia = source[trivia_start..span.start].to_owned();
+            trivia_start = span.end;
+
+            SourceToken {
+                token,
+                trivia,
+                text: source[span.start..span.end].to_owned(),
+            }
+        })
+        .collect();
+
+    Ok(tokens)
+}
+
+// Rebuilds source text from its tokens. Untouched tokens reproduce the original source
+// character for character
+pub fn resynthesize(tokens: &[SourceToken]) -> String {
+    let mut source = String::new();
+
+    for token in tokens {
+        source.push_str(&token.trivia);
+        source.push_str(&token.text);
+    }
+
+    source
+}
@@ -0,0 +1,55 @@
+use crate::error::{LulaError, RuntimeErrorKind};
+use crate::messages::Message;
+use crate::number::Number;
+use crate::token::Position;
+use crate::value::Value;
+
+// How values act where another type is expected. Both backends consult these rules rather
+// than deciding for themselves, so that they can't drift apart.
+//
+// Integers and floats are both numbers. Arithmetic on two integers stays in integers, while
+// mixing an integer with a float works in floats. Nothing else converts to a number; strings
+// and numbers are only turned into each other explicitly, with 'num' and 'str'. These rules
+// are the same in every mode.
+//
+// The mode only decides which values can be conditions, and is switched by '--strict'
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Semantics {
+    // Any value can be a condition, with only nil and false counting as false
+    #[default]
+    Lenient,
+    // Only booleans can be conditions
+    Strict,
+}
+
+impl Semantics {
+    // Decides whether a condition holds. Non-booleans are an error under strict semantics,
+    // reported at the condition's position, which is only worked out if it's needed
+    pub fn test<F>(self, condition: &Value, position: F) -> Result<bool, LulaError>
+    where
+        F: FnOnce() -> Position,
+    {
+        match (self, condition) {
+            (_, Value::Bool(val)) => Ok(*val),
+            (Semantics::Lenient, val) => Ok(is_truthy(val)),
+            (Semantics::Strict, val) => Err(LulaError::runtime(
+                RuntimeErrorKind::Type,
+                Message::new("non-bool-condition").debug_arg(val),
+                position(),
+            )),
+        }
+    }
+}
+
+// Whether a value counts as true under lenient semantics
+pub fn is_truthy(value: &Value) -> bool {
+    !matches!(value, Value::Nil | Value::Bool(false))
+}
+
+pub fn to_number(value: &Value) -> Option<Number> {
+    match value {
+        Value::Int(val) => Some(Number::Int(*val)),
+        Value::Float(val) => Some(Number::Float(*val)),
+        _ => None,
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::BTreeSet;
+
+use crate::error::LulaError;
+use crate::lexer::Lexer;
+use crate::messages::Message;
+use crate::token::{Literal, Position, SourceId, TokenKind};
+
+// Words every program is checked against, before any project wordlist is added
+const BUILTIN_WORDS: &str = include_str!("words.txt");
+
+// Shorter words are too often abbreviations to be worth flagging
+const MIN_WORD_LENGTH: usize = 4;
+
+// Endings stripped to find the stem of an inflected word, paired with what replaces them
+const SUFFIXES: &[(&str, &str)] = &[
+    ("s", ""),
+    ("es", ""),
+    ("ies", "y"),
+    ("d", ""),
+    ("ed", ""),
+    ("ied", "y"),
+    ("ing", ""),
+    ("ing", "e"),
+    ("ly", ""),
+    ("r", ""),
+    ("er", ""),
+    ("est", ""),
+];
+
+// Lowercase words considered correctly spelled
+#[derive(Debug, Clone)]
+pub struct Wordlist {
+    words: BTreeSet<String>,
+}
+
+impl Default for Wordlist {
+    fn default() -> Self {
+        let mut wordlist = Wordlist {
+            words: BTreeSet::new(),
+        };
+
+        wordlist.add_words(BUILTIN_WORDS);
+        wordlist
+    }
+}
+
+impl Wordlist {
+    // Adds the words in a wordlist file. Words are separated by whitespace, and anything
+    // after a '#' on a line is a comment
+    pub fn add_words(&mut self, text: &str) {
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+
+            self.words
+                .extend(line.split_whitespace().map(str::to_lowercase));
+        }
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        stems(word).any(|(stem, _)| self.words.contains(&stem))
+    }
+
+    // Finds a known word one edit away from a misspelled one, keeping its inflection
+    pub fn suggest(&self, word: &str) -> Option<String> {
+        stems(word).find_map(|(stem, (suffix, replacement))| {
+            let known = self
+                .words
+                .iter()
+                .find(|known| one_edit_apart(known, &stem))?;
+            let root = known.strip_suffix(replacement)?;
+
+            Some(format!("{}{}", root, suffix))
+        })
+    }
+}
+
+// The word itself followed by each stem it might have been inflected from, along with the
+// suffix and replacement that produced it
+fn stems(word: &str) -> impl Iterator<Item = (String, (&'static str, &'static str))> + '_ {
+    let inflected = SUFFIXES.iter().filter_map(move |&(suffix, replacement)| {
+        let stem = word.strip_suffix(suffix)?;
+
+        if stem.len() < 2 {
+            return None;
+        }
+
+        Some((format!("{}{}", stem, replacement), (suffix, replacement)))
+    });
+
+    // Consonants are often doubled before a suffix, as in 'stopped'
+    let undoubled = SUFFIXES.iter().filter_map(move |&(suffix, _)| {
+        let stem = word.strip_suffix(suffix)?.as_bytes();
+
+        match stem {
+            [.., a, b] if a == b && stem.len() > 2 => {
+                let stem = String::from_utf8(stem[..stem.len() - 1].to_vec()).ok()?;
+                Some((stem, (suffix, "")))
+            }
+            _ => None,
+        }
+    });
+
+    Some((word.to_owned(), ("", "")))
+        .into_iter()
+        .chain(inflected)
+        .chain(undoubled)
+}
+
+// Whether two words differ by a single inserted, removed, replaced or swapped letter
+fn one_edit_apart(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+
+    if a.len() == b.len() {
+        if prefix == a.len() {
+            return false;
+        }
+
+        // Either a single replaced letter, or two neighbouring letters swapped
+        let rest = prefix + 1;
+        a[rest..] == b[rest..]
+            || (rest < a.len()
+                && a[prefix] == b[rest]
+                && a[rest] == b[prefix]
+                && a[rest + 1..] == b[rest + 1..])
+    } else if a.len() + 1 == b.len() {
+        a[prefix..] == b[prefix + 1..]
+    } else if b.len() + 1 == a.len() {
+        a[prefix + 1..] == b[prefix..]
+    } else {
+        false
+    }
+}
+
+// Lints the string literals and comments in a source for likely typos: words missing from
+// the wordlist that are a single edit away from a word in it. Identifiers declared in the
+// source count as known words, so comments can mention them freely. Returns the warnings
+// found, or the errors met lexing the source
+pub fn check_spelling(
+    source: &str,
+    source_id: SourceId,
+    wordlist: &Wordlist,
+) -> Result<Vec<LulaError>, Vec<LulaError>> {
+    let tokens = Lexer::for_source(source.to_owned(), source_id).collect_tokens()?;
+
+    let mut wordlist = wordlist.clone();
+    let mut texts = Vec::new();
+    let mut trivia_start = 0;
+
+    for token in &tokens {
+        let span = token.span;
+
+        // Everything between tokens is whitespace or comments, so can be checked as it is
+        texts.push((trivia_start, source[trivia_start..span.start].to_owned()));
+        trivia_start = span.end;
+
+        match &token.kind {
+            TokenKind::Literal(Literal::Identifier(name)) => {
+                wordlist.add_words(&name.replace('_', " "));
+            }
+            TokenKind::Literal(Literal::String(..)) => {
+                let text = &source[span.start + 1..span.end - 1];
+                texts.push((span.start + 1, unescaped(text)));
+            }
+            // Strings broken up by interpolations have a brace or quote before each piece
+            // and '${' after it
+            TokenKind::Interpolation(..) => {
+                let text = &source[span.start + 1..span.end - 2];
+                texts.push((span.start + 1, unescaped(text)));
+            }
+            _ => {}
+        }
+    }
+
+    let lines = LineIndex::new(source, source_id);
+    let mut warnings = Vec::new();
+
+    for (offset, text) in texts {
+        for (start, word) in words(&text) {
+            let lowercase = word.to_lowercase();
+
+            if wordlist.contains(&lowercase) {
+                continue;
+            }
+
+            if let Some(suggestion) = wordlist.suggest(&lowercase) {
+                warnings.push(LulaError::spelling(
+                    Message::new("unknown-word").arg(word).arg(suggestion),
+                    lines.position(offset + start),
+                ));
+            }
+        }
+    }
+
+    Ok(warnings)
+}
+
+// Blanks out escape sequences in the contents of a string literal, so that the letter after
+// a backslash or the digits of a unicode escape aren't read as words, keeping byte offsets
+// intact
+fn unescaped(contents: &str) -> String {
+    let mut text = String::with_capacity(contents.len());
+    let mut chars = contents.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let mut blanked = 0;
+
+        if c == '\\' {
+            blanked += c.len_utf8();
+
+            if let Some(escaped) = chars.next() {
+                blanked += escaped.len_utf8();
+
+                if escaped == 'u' && chars.peek() == Some(&'{') {
+                    for c in chars.by_ref() {
+                        blanked += c.len_utf8();
+
+                        if c == '}' {
+                            break;
+                        }
+                    }
+                }
+            }
+        } else {
+            text.push(c);
+        }
+
+        text.extend(std::iter::repeat_n(' ', blanked));
+    }
+
+    text
+}
+
+// Splits text into the words worth checking, with their byte offsets. Runs of characters
+// containing digits, underscores or apostrophes are skipped, as are all-caps and camelCase
+// words, since these are usually names, acronyms or contractions rather than prose
+fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_' || c == '\'';
+    let mut start = None;
+    let mut runs = Vec::new();
+
+    for (offset, c) in text.char_indices().chain(Some((text.len(), ' '))) {
+        match (start, is_word_char(c)) {
+            (None, true) => start = Some(offset),
+            (Some(from), false) => {
+                runs.push((from, &text[from..offset]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    runs.into_iter().filter(|(_, word)| {
+        word.len() >= MIN_WORD_LENGTH
+            && word.chars().all(|c| c.is_ascii_alphabetic())
+            && !word.chars().skip(1).any(|c| c.is_ascii_uppercase())
+    })
+}
+
+// Converts byte offsets into the line and column positions diagnostics use
+struct LineIndex<'a> {
+    source: &'a str,
+    source_id: SourceId,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(source: &'a str, source_id: SourceId) -> Self {
+        let line_starts = Some(0)
+            .into_iter()
+            .chain(source.match_indices('\n').map(|(offset, _)| offset + 1))
+            .collect();
+
+        LineIndex {
+            source,
+            source_id,
+            line_starts,
+        }
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let column = self.source[self.line_starts[line]..offset].chars().count();
+
+        Position(line, column, self.source_id)
+    }
+}
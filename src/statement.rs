@@ -1,34 +1,302 @@
+use crate::environment::Environment;
+use crate::error::{LulaError, RuntimeErrorKind};
 use crate::expr::Expr;
+use crate::function::Function;
+use crate::messages::Message;
+use crate::number::Number;
+use crate::token::{Literal, Name, Position, Token, TokenKind};
+use crate::value::Value;
+use std::{iter, rc::Rc};
 
-#[derive(Debug)]
+#[derive(Debug, Hash)]
 pub enum Statement {
     Print(Expr),
-    VarDecl(String, Option<Expr>),
+    VarDecl(Name, Option<Expr>),
     Expr(Expr),
+    // Conditionals keep their 'if' and 'elif' keywords, so that a condition can be pointed
+    // out even when it has no tokens of its own to tell
+    If(
+        Token,
+        Expr,
+        Vec<Statement>,
+        Vec<(Token, Expr, Vec<Statement>)>,
+        Option<Vec<Statement>>,
+    ),
+    Block(Vec<Statement>),
+    // Loops keep their keyword, so that they can be pointed out while they run
+    Loop(Token, Vec<Statement>),
+    While(Token, Expr, Vec<Statement>),
+    For(Token, Token, Expr, Vec<Statement>),
+    Break,
+    Continue,
+    Func(Name, Vec<Name>, Rc<Vec<Statement>>),
+    Return(Option<Expr>),
+
+    // Stands in for a statement that failed to parse, keeping its tokens
+    Error(Vec<Token>),
+}
+
+// Tells enclosing statements how control should continue after a statement has run
+#[derive(Debug, Clone, PartialEq)]
+pub enum Signal {
+    Normal,
+    Break,
+    Continue,
+    Return(Value),
 }
 
 impl Statement {
-    pub fn interpret(&self) -> bool {
+    pub fn interpret_block(
+        block: &[Statement],
+        env: &mut Environment,
+    ) -> Result<Signal, LulaError> {
+        for statement in block {
+            // Stop running the block as soon as control leaves it
+            match statement.interpret(env)? {
+                Signal::Normal => {}
+                signal => return Ok(signal),
+            }
+        }
+
+        Ok(Signal::Normal)
+    }
+
+    // Runs a block in its own scope, so declarations inside it shadow outer ones and go out
+    // of scope at its closing brace
+    pub fn interpret_scoped(
+        block: &[Statement],
+        env: &mut Environment,
+    ) -> Result<Signal, LulaError> {
+        env.push_scope();
+        let signal = Statement::interpret_block(block, env);
+        env.pop_scope();
+
+        signal
+    }
+
+    // Declares a variable in the current scope, unless that would replace a constant
+    pub(crate) fn define_variable(
+        name: &str,
+        value: Value,
+        position: Position,
+        env: &mut Environment,
+    ) -> Result<(), LulaError> {
+        if env.is_constant(name) {
+            return Err(LulaError::runtime(
+                RuntimeErrorKind::Name,
+                Message::new("constant-redefinition").arg(name),
+                position,
+            ));
+        }
+
+        env.define(name.to_owned(), value);
+        Ok(())
+    }
+
+    // Where a statement starts, if it has any tokens to tell
+    pub(crate) fn position(&self) -> Option<Position> {
+        match self {
+            Statement::Print(expr) | Statement::Expr(expr) | Statement::Return(Some(expr)) => {
+                expr.position()
+            }
+
+            Statement::VarDecl(name, _) | Statement::Func(name, ..) => Some(name.position),
+            Statement::If(keyword, ..)
+            | Statement::Loop(keyword, _)
+            | Statement::While(keyword, ..)
+            | Statement::For(keyword, ..) => Some(keyword.position),
+            Statement::Block(block) => block.iter().find_map(Statement::position),
+            Statement::Error(tokens) => tokens.first().map(|tok| tok.position),
+
+            Statement::Break | Statement::Continue | Statement::Return(None) => None,
+        }
+    }
+
+    // Error nodes are only left in programs that failed to parse, which aren't meant to be
+    // run
+    pub(crate) fn unparsed(tokens: &[Token]) -> LulaError {
+        let position = tokens.first().map_or(Position(0, 0, 0), |tok| tok.position);
+        LulaError::internal(Message::new("unparsed-code"), position)
+    }
+
+    // Counts up from the start of a range to just before its end, in integers unless the
+    // range starts at a float
+    pub(crate) fn range_items(start: Number, end: Number) -> Box<dyn Iterator<Item = Value>> {
+        let steps = iter::successors(Some(start), |i| match *i {
+            Number::Int(i) => i.checked_add(1).map(Number::Int),
+            Number::Float(i) => Some(Number::Float(i + 1.0)),
+        });
+
+        Box::new(steps.take_while(move |i| *i < end).map(Value::from))
+    }
+
+    // Lists the values a for loop visits, reporting errors at its loop variable
+    pub(crate) fn iterate(
+        iterable: Value,
+        name: &Token,
+    ) -> Result<Box<dyn Iterator<Item = Value>>, LulaError> {
+        // Iterate over a snapshot so the body can safely change what it loops over
+        match iterable {
+            Value::List(items) => Ok(Box::new(items.borrow().clone().into_iter())),
+            Value::Map(entries) => Ok(Box::new(
+                Value::sorted_keys(&entries.borrow())
+                    .into_iter()
+                    .map(Value::String),
+            )),
+            val => Err(LulaError::runtime(
+                RuntimeErrorKind::Type,
+                Message::new("not-iterable").debug_arg(val),
+                name.position,
+            )),
+        }
+    }
+
+    pub fn interpret(&self, env: &mut Environment) -> Result<Signal, LulaError> {
+        env.steps().count_statement();
+
         match self {
             Statement::Print(expr) => {
-                match expr.evaluate() {
-                    Some(val) => println!("{}", val),
-                    None => return false,
-                };
+                let val = expr.evaluate(env)?;
+                println!("{}", val);
             }
 
             Statement::VarDecl(name, initializer) => {
-                let initializer = match initializer {
-                    Some(val) => Some(val.evaluate()),
-                    None => None,
+                // Variables declared without an initializer start out as nil
+                let value = match initializer {
+                    Some(expr) => expr.evaluate(env)?,
+                    None => Value::Nil,
+                };
+
+                Statement::define_variable(&name.identifier, value, name.position, env)?;
+            }
+
+            Statement::Expr(expr) => {
+                expr.evaluate(env)?;
+            }
+
+            Statement::If(keyword, condition, then_block, elif_blocks, else_block) => {
+                let branches = iter::once((keyword, condition, then_block)).chain(
+                    elif_blocks
+                        .iter()
+                        .map(|(keyword, cond, block)| (keyword, cond, block)),
+                );
+
+                // Run the first branch whose condition holds, falling back to the else block
+                for (keyword, cond, block) in branches {
+                    let value = cond.evaluate(env)?;
+
+                    if env
+                        .semantics()
+                        .test(&value, || cond.position_or(keyword.position))?
+                    {
+                        return Statement::interpret_scoped(block, env);
+                    }
+                }
+
+                if let Some(block) = else_block {
+                    return Statement::interpret_scoped(block, env);
+                }
+            }
+
+            Statement::Block(block) => return Statement::interpret_scoped(block, env),
+
+            Statement::Loop(keyword, body) => loop {
+                env.steps().count_iteration(keyword.position);
+
+                match Statement::interpret_scoped(body, env)? {
+                    Signal::Break => break,
+                    Signal::Normal | Signal::Continue => {}
+                    signal @ Signal::Return(..) => return Ok(signal),
+                }
+            },
+
+            Statement::While(keyword, condition, body) => loop {
+                let value = condition.evaluate(env)?;
+
+                if !env
+                    .semantics()
+                    .test(&value, || condition.position_or(keyword.position))?
+                {
+                    break;
+                }
+
+                env.steps().count_iteration(keyword.position);
+
+                match Statement::interpret_scoped(body, env)? {
+                    Signal::Break => break,
+                    Signal::Normal | Signal::Continue => {}
+                    signal @ Signal::Return(..) => return Ok(signal),
+                }
+            },
+
+            Statement::For(keyword, name, iterable, body) => {
+                let items = match iterable {
+                    Expr::Range(start, op, end) => {
+                        let (start, end) = Expr::evaluate_range(start, op, end, env)?;
+                        Statement::range_items(start, end)
+                    }
+
+                    _ => Statement::iterate(iterable.evaluate(env)?, name)?,
+                };
+
+                let name = match &name.kind {
+                    TokenKind::Literal(Literal::Identifier(val)) => val,
+                    _ => {
+                        return Err(LulaError::internal(
+                            Message::new("expected-identifier").debug_arg(&name.kind),
+                            name.position,
+                        ))
+                    }
+                };
+
+                for item in items {
+                    env.steps().count_iteration(keyword.position);
+
+                    // Give every iteration a fresh scope holding the loop variable
+                    env.push_scope();
+                    env.define(name.to_string(), item);
+                    let signal = Statement::interpret_block(body, env);
+                    env.pop_scope();
+
+                    match signal? {
+                        Signal::Break => break,
+                        Signal::Normal | Signal::Continue => {}
+                        signal @ Signal::Return(..) => return Ok(signal),
+                    }
+                }
+            }
+
+            Statement::Break => return Ok(Signal::Break),
+            Statement::Continue => return Ok(Signal::Continue),
+
+            Statement::Func(name, params, body) => {
+                let func = Function {
+                    name: name.identifier.clone(),
+                    params: Name::identifiers(params),
+                    body: Rc::clone(body),
+                    closure: env.capture(),
+                };
+
+                Statement::define_variable(
+                    &name.identifier,
+                    Value::Function(func),
+                    name.position,
+                    env,
+                )?;
+            }
+
+            Statement::Return(value) => {
+                let val = match value {
+                    Some(expr) => expr.evaluate(env)?,
+                    None => Value::Nil,
                 };
 
-                println!("Variable name: {}, initializer: {:?}", name, initializer);
+                return Ok(Signal::Return(val));
             }
 
-            _ => {}
+            Statement::Error(tokens) => return Err(Statement::unparsed(tokens)),
         }
 
-        true
+        Ok(Signal::Normal)
     }
 }
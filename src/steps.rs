@@ -0,0 +1,99 @@
+use std::{collections::HashMap, fmt};
+
+use crate::error::LulaError;
+use crate::messages::Message;
+use crate::token::Position;
+
+// Loop iterations a program runs before it is warned that it may be stuck in a loop that
+// never ends. Ordinary programs rarely come close, while an infinite loop gets there in a
+// few seconds
+pub const DEFAULT_STEP_WARNING: u64 = 10_000_000;
+
+// Receives warnings raised while a program runs, so that they can be shown straight away
+// rather than after the program ends, which it may never do
+pub type WarningHandler = Box<dyn FnMut(&LulaError)>;
+
+// Counts the iterations each loop in a program runs. Once the total passes a threshold, the
+// loop that has run the most is reported a single time, as the likeliest to be stuck. Also
+// counts the statements run, for reporting once the program ends
+pub struct StepCounter {
+    steps: u64,
+    statements: u64,
+    iterations: HashMap<Position, u64>,
+
+    // No warning is given if there is no threshold, or once one has been given
+    threshold: Option<u64>,
+    on_warning: Option<WarningHandler>,
+}
+
+impl Default for StepCounter {
+    fn default() -> Self {
+        StepCounter {
+            steps: 0,
+            statements: 0,
+            iterations: HashMap::new(),
+            threshold: Some(DEFAULT_STEP_WARNING),
+            on_warning: None,
+        }
+    }
+}
+
+impl fmt::Debug for StepCounter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StepCounter")
+            .field("steps", &self.steps)
+            .field("statements", &self.statements)
+            .field("threshold", &self.threshold)
+            .finish()
+    }
+}
+
+impl StepCounter {
+    pub fn set_threshold(&mut self, threshold: Option<u64>) {
+        self.threshold = threshold;
+    }
+
+    pub fn set_warning_handler(&mut self, handler: WarningHandler) {
+        self.on_warning = Some(handler);
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn statements(&self) -> u64 {
+        self.statements
+    }
+
+    pub fn count_statement(&mut self) {
+        self.statements += 1;
+    }
+
+    // Counts an iteration of the loop whose keyword is at a position
+    pub fn count_iteration(&mut self, position: Position) {
+        self.steps += 1;
+        *self.iterations.entry(position).or_insert(0) += 1;
+
+        if self
+            .threshold
+            .is_some_and(|threshold| self.steps > threshold)
+        {
+            self.threshold = None;
+            self.warn();
+        }
+    }
+
+    fn warn(&mut self) {
+        let busiest = self
+            .iterations
+            .iter()
+            .max_by_key(|(position, count)| (**count, std::cmp::Reverse(**position)));
+
+        if let (Some((position, count)), Some(on_warning)) = (busiest, &mut self.on_warning) {
+            let warning =
+                LulaError::loop_warning(Message::new("long-running-loop").arg(count), *position);
+
+            on_warning(&warning);
+        }
+    }
+}
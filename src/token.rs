@@ -1,15 +1,47 @@
+use crate::number::format_number;
 use core::fmt;
-use std::{collections::HashMap, fmt::Display};
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct Token {
     pub position: Position,
+    pub span: Span,
     pub kind: TokenKind,
 }
 
 impl Token {}
 
-#[derive(Debug, Clone, PartialEq)]
+// Tokens hash by kind alone, so that moving code around doesn't change the hash of the
+// syntax tree it's part of
+impl Hash for Token {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+    }
+}
+
+// The identifier introduced by a declaration, along with where it was declared. Like
+// tokens, names leave their position out of their hash
+#[derive(Debug, Clone)]
+pub struct Name {
+    pub identifier: String,
+    pub position: Position,
+}
+
+impl Name {
+    pub fn identifiers(names: &[Name]) -> Vec<String> {
+        names.iter().map(|name| name.identifier.clone()).collect()
+    }
+}
+
+impl Hash for Name {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.identifier.hash(state);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub enum TokenKind {
     // Single character tokens
     LeftParen,
@@ -24,6 +56,8 @@ pub enum TokenKind {
     Star,
     Slash,
     Percent,
+    Comma,
+    Colon,
 
     // Single and double character tokens
     Equal,
@@ -34,6 +68,8 @@ pub enum TokenKind {
     LessEqual,
     Greater,
     GreaterEqual,
+    Dot,
+    DotDot,
 
     // Literals
     Literal(Literal),
@@ -47,9 +83,13 @@ pub enum TokenKind {
     Or,
 
     Func,
+    Return,
     Let,
 
     Loop,
+    While,
+    For,
+    In,
     Break,
     Continue,
 
@@ -58,23 +98,94 @@ pub enum TokenKind {
     // Control tokens
     Newline,
     Eof,
+
+    // Compound assignment tokens. Kinds hash by their discriminant, so new kinds go last to
+    // keep the hashes of existing programs the same
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PercentEqual,
+
+    // The text of a string up to an interpolated expression, from its opening quote or the
+    // end of the previous interpolation. The rest of the string follows the expression, as
+    // another of these or as a string literal
+    Interpolation(Rc<str>),
+
+    Question,
+    StarStar,
+
+    // '???', a placeholder for an expression that hasn't been written yet
+    Placeholder,
+
+    // Bitwise operators
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    LessLess,
+    GreaterGreater,
 }
 
+// Identifiers and strings share their text with every other token for the same lexemme,
+// so tokens are cheap to clone
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
-    Identifier(String),
-    String(String),
-    Number(f64),
+    Identifier(Rc<str>),
+    String(Rc<str>),
+    Int(i64),
+    Float(f64),
     Bool(bool),
     Nil,
 }
 
+// Literals hash under fixed tags rather than their discriminants, so that program hashes
+// stay valid as variants are added. Numbers hash as they did when every number was a float,
+// except for integers a float can't hold exactly and whole floats, which used to be
+// indistinguishable from integers
+impl Hash for Literal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Literal::Identifier(val) => {
+                state.write_isize(0);
+                val.hash(state);
+            }
+            Literal::String(val) => {
+                state.write_isize(1);
+                val.hash(state);
+            }
+            Literal::Int(val) if (*val as f64) as i64 == *val && *val != i64::MAX => {
+                state.write_isize(2);
+                (*val as f64).to_bits().hash(state);
+            }
+            Literal::Int(val) => {
+                state.write_isize(5);
+                val.hash(state);
+            }
+            Literal::Float(val) if val.fract() == 0.0 => {
+                state.write_isize(6);
+                val.to_bits().hash(state);
+            }
+            Literal::Float(val) => {
+                state.write_isize(2);
+                val.to_bits().hash(state);
+            }
+            Literal::Bool(val) => {
+                state.write_isize(3);
+                val.hash(state);
+            }
+            Literal::Nil => state.write_isize(4),
+        }
+    }
+}
+
 impl Display for Literal {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let val = match self {
-            Literal::Identifier(val) => val.clone(),
-            Literal::String(val) => val.clone(),
-            Literal::Number(val) => val.to_string(),
+            Literal::Identifier(val) => val.to_string(),
+            Literal::String(val) => val.to_string(),
+            Literal::Int(val) => val.to_string(),
+            Literal::Float(val) => format_number(*val),
             Literal::Bool(val) => val.to_string(),
             Literal::Nil => String::from("nil"),
         };
@@ -83,36 +194,52 @@ impl Display for Literal {
     }
 }
 
-lazy_static! {
-    pub static ref KEYWORDS: HashMap<&'static str, TokenKind> = {
-        let mut map = HashMap::new();
+// Maps a lexemme to its keyword token kind, if it is one
+pub fn keyword(lexemme: &str) -> Option<TokenKind> {
+    let kind = match lexemme {
+        "true" => TokenKind::Literal(Literal::Bool(true)),
+        "false" => TokenKind::Literal(Literal::Bool(false)),
+        "nil" => TokenKind::Literal(Literal::Nil),
 
-        map.insert("true", TokenKind::Literal(Literal::Bool(true)));
-        map.insert("false", TokenKind::Literal(Literal::Bool(false)));
-        map.insert("nil", TokenKind::Literal(Literal::Nil));
+        "if" => TokenKind::If,
+        "elif" => TokenKind::Elif,
+        "else" => TokenKind::Else,
 
-        map.insert("if", TokenKind::If);
-        map.insert("elif", TokenKind::Elif);
-        map.insert("else", TokenKind::Else);
+        "and" => TokenKind::And,
+        "or" => TokenKind::Or,
 
-        map.insert("and", TokenKind::And);
-        map.insert("or", TokenKind::Or);
+        "func" => TokenKind::Func,
+        "return" => TokenKind::Return,
+        "let" => TokenKind::Let,
 
-        map.insert("func", TokenKind::Func);
-        map.insert("let", TokenKind::Let);
+        "loop" => TokenKind::Loop,
+        "while" => TokenKind::While,
+        "for" => TokenKind::For,
+        "in" => TokenKind::In,
+        "break" => TokenKind::Break,
+        "continue" => TokenKind::Continue,
 
-        map.insert("loop", TokenKind::Loop);
-        map.insert("break", TokenKind::Break);
-        map.insert("continue", TokenKind::Continue);
+        "print" => TokenKind::Print,
 
-        map.insert("print", TokenKind::Print);
-
-        map
+        _ => return None,
     };
+
+    Some(kind)
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct Position(pub usize, pub usize);
+// Index of the source file a position is in, for programs made up of several files
+pub type SourceId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position(pub usize, pub usize, pub SourceId);
+
+// Range of bytes in its source that a token was lexed from. Tokens made up by the parser
+// or optimizer rather than lexed have an empty span
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
 
 impl Display for Position {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -0,0 +1,301 @@
+use crate::function::Function;
+use crate::native::NativeFn;
+use crate::number::{format_number, Number};
+use crate::semantics::to_number;
+use crate::token::Literal;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Display},
+    rc::Rc,
+};
+
+// Lists and maps are shared, so changes made through one reference are seen through all
+// others
+pub type List = Rc<RefCell<Vec<Value>>>;
+pub type Map = Rc<RefCell<HashMap<String, Value>>>;
+
+// A value produced while running a program. Literal tokens are converted into values as
+// they are evaluated
+#[derive(Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bool(bool),
+    Function(Function),
+    NativeFn(NativeFn),
+    List(List),
+    Map(Map),
+    Nil,
+}
+
+impl Value {
+    // Identifiers name values rather than being values themselves, so they have no
+    // conversion
+    pub fn from_literal(lit: &Literal) -> Option<Value> {
+        let val = match lit {
+            Literal::Identifier(..) => return None,
+            Literal::String(val) => Value::String(val.to_string()),
+            Literal::Int(val) => Value::Int(*val),
+            Literal::Float(val) => Value::Float(*val),
+            Literal::Bool(val) => Value::Bool(*val),
+            Literal::Nil => Value::Nil,
+        };
+
+        Some(val)
+    }
+
+    // Turns a value back into the literal that evaluates to it, if there is one
+    pub fn to_literal(&self) -> Option<Literal> {
+        let lit = match self {
+            Value::String(val) => Literal::String(Rc::from(val.as_str())),
+            Value::Int(val) => Literal::Int(*val),
+            Value::Float(val) => Literal::Float(*val),
+            Value::Bool(val) => Literal::Bool(*val),
+            Value::Nil => Literal::Nil,
+            _ => return None,
+        };
+
+        Some(lit)
+    }
+
+    pub fn list(items: Vec<Value>) -> Value {
+        Value::List(Rc::new(RefCell::new(items)))
+    }
+
+    pub fn map(entries: HashMap<String, Value>) -> Value {
+        Value::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    // Maps have no order of their own, so their keys are sorted wherever order is visible
+    pub fn sorted_keys(entries: &HashMap<String, Value>) -> Vec<String> {
+        let mut keys: Vec<String> = entries.keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    // Identifies the list or map behind a value, if it is one, so that a container met again
+    // inside itself can be recognised
+    fn container_address(&self) -> Option<*const ()> {
+        match self {
+            Value::List(items) => Some(Rc::as_ptr(items) as *const ()),
+            Value::Map(entries) => Some(Rc::as_ptr(entries) as *const ()),
+            _ => None,
+        }
+    }
+
+    // Formats a value, quoting strings inside lists and maps so that their contents can't be
+    // mistaken for other items. Lists and maps can hold themselves, so one met again while
+    // it is still being written is shortened to '[...]' or '{...}'
+    fn format_within(&self, quoted: bool, open: &mut Vec<*const ()>) -> String {
+        if let Some(address) = self.container_address() {
+            if open.contains(&address) {
+                let repeated = match self {
+                    Value::List(..) => "[...]",
+                    _ => "{...}",
+                };
+
+                return repeated.to_owned();
+            }
+
+            open.push(address);
+        }
+
+        let text = match self {
+            Value::String(val) if quoted => format!("{:?}", val),
+            Value::String(val) => val.clone(),
+            Value::Int(val) => val.to_string(),
+            Value::Float(val) => format_number(*val),
+            Value::Bool(val) => val.to_string(),
+            Value::Function(func) => format!("<func {}>", func.name),
+            Value::NativeFn(native) => format!("<native func {}>", native.name),
+            Value::List(items) => {
+                let items: Vec<String> = items
+                    .borrow()
+                    .iter()
+                    .map(|item| item.format_within(true, open))
+                    .collect();
+
+                format!("[{}]", items.join(", "))
+            }
+            Value::Map(entries) => {
+                let entries = entries.borrow();
+                let entries: Vec<String> = Value::sorted_keys(&entries)
+                    .into_iter()
+                    .map(|key| format!("{:?}: {}", key, entries[&key].format_within(true, open)))
+                    .collect();
+
+                format!("{{{}}}", entries.join(", "))
+            }
+            Value::Nil => String::from("nil"),
+        };
+
+        if self.container_address().is_some() {
+            open.pop();
+        }
+
+        text
+    }
+
+    // Values can only be compared with values of the same type or with nil, though integers
+    // and floats can be compared with each other. Returns None for any other pair so the
+    // caller can report a type error
+    pub fn equals(&self, other: &Value) -> Option<bool> {
+        self.equals_within(other, &mut Vec::new())
+    }
+
+    // Containers can hold themselves, so a pair of them met again while it is still being
+    // compared is taken to be equal. Any difference is found by the comparison already
+    // underway
+    fn equals_within(
+        &self,
+        other: &Value,
+        comparing: &mut Vec<(*const (), *const ())>,
+    ) -> Option<bool> {
+        if let (Some(left), Some(right)) = (to_number(self), to_number(other)) {
+            return Some(left == right);
+        }
+
+        let pair = self.container_address().zip(other.container_address());
+
+        if let Some(pair) = pair {
+            if comparing.contains(&pair) {
+                return Some(true);
+            }
+
+            comparing.push(pair);
+        }
+
+        let equal = match (self, other) {
+            (Value::Bool(left), Value::Bool(right)) => Some(left == right),
+            (Value::String(left), Value::String(right)) => Some(left == right),
+
+            // Lists and maps are equal if all of their items are
+            (Value::List(left), Value::List(right)) => {
+                let (left, right) = (left.borrow(), right.borrow());
+
+                Some(
+                    left.len() == right.len()
+                        && left
+                            .iter()
+                            .zip(right.iter())
+                            .all(|(l, r)| l.equals_within(r, comparing) == Some(true)),
+                )
+            }
+            (Value::Map(left), Value::Map(right)) => {
+                let (left, right) = (left.borrow(), right.borrow());
+
+                Some(
+                    left.len() == right.len()
+                        && left.iter().all(|(key, l)| {
+                            right.get(key).and_then(|r| l.equals_within(r, comparing)) == Some(true)
+                        }),
+                )
+            }
+
+            // Anything can be checked for nil, and nil only equals itself
+            (Value::Nil, Value::Nil) => Some(true),
+            (Value::Nil, _) | (_, Value::Nil) => Some(false),
+
+            _ => None,
+        };
+
+        if pair.is_some() {
+            comparing.pop();
+        }
+
+        equal
+    }
+}
+
+impl From<Number> for Value {
+    fn from(number: Number) -> Self {
+        match number {
+            Number::Int(val) => Value::Int(val),
+            Number::Float(val) => Value::Float(val),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format_within(false, &mut Vec::new()))
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let open = RefCell::new(Vec::new());
+        DebugWithin {
+            value: self,
+            open: &open,
+        }
+        .fmt(f)
+    }
+}
+
+// Carries the lists and maps still being written through the formatter, which the Debug
+// trait has no room for, so that a container holding itself is shortened as in Display
+struct DebugWithin<'a> {
+    value: &'a Value,
+    open: &'a RefCell<Vec<*const ()>>,
+}
+
+impl<'a> DebugWithin<'a> {
+    fn nested(&self, value: &'a Value) -> DebugWithin<'a> {
+        DebugWithin {
+            value,
+            open: self.open,
+        }
+    }
+}
+
+impl fmt::Debug for DebugWithin<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let address = self.value.container_address();
+
+        if let Some(address) = address {
+            if self.open.borrow().contains(&address) {
+                return match self.value {
+                    Value::List(..) => write!(f, "List([...])"),
+                    _ => write!(f, "Map({{...}})"),
+                };
+            }
+
+            self.open.borrow_mut().push(address);
+        }
+
+        let result = match self.value {
+            Value::Int(val) => f.debug_tuple("Int").field(val).finish(),
+            Value::Float(val) => f.debug_tuple("Float").field(val).finish(),
+            Value::String(val) => f.debug_tuple("String").field(val).finish(),
+            Value::Bool(val) => f.debug_tuple("Bool").field(val).finish(),
+            Value::Function(func) => f.debug_tuple("Function").field(func).finish(),
+            Value::NativeFn(native) => f.debug_tuple("NativeFn").field(native).finish(),
+
+            // Print the items directly rather than the cell holding them
+            Value::List(items) => {
+                let items = items.borrow();
+                let items: Vec<DebugWithin> = items.iter().map(|item| self.nested(item)).collect();
+                f.debug_tuple("List").field(&items).finish()
+            }
+            Value::Map(entries) => {
+                let entries = entries.borrow();
+                let entries: BTreeMap<&String, DebugWithin> = entries
+                    .iter()
+                    .map(|(key, value)| (key, self.nested(value)))
+                    .collect();
+                f.debug_tuple("Map").field(&entries).finish()
+            }
+
+            Value::Nil => write!(f, "Nil"),
+        };
+
+        if address.is_some() {
+            self.open.borrow_mut().pop();
+        }
+
+        result
+    }
+}
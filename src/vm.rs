@@ -0,0 +1,420 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+
+use crate::compiler::{compile_function, Chunk, Op};
+use crate::environment::{Assignment, Environment, Scope};
+use crate::error::LulaError;
+use crate::expr::Expr;
+use crate::function::Function;
+use crate::messages::Message;
+use crate::statement::Statement;
+use crate::token::Position;
+use crate::value::Value;
+
+// Slots of the globals in a chunk's pool, filled in as each is first found. Slots belong to
+// the environment being run in, so are kept alongside a chunk rather than in it
+type GlobalSlots = Rc<[Cell<Option<usize>>]>;
+
+fn global_slots(chunk: &Chunk) -> GlobalSlots {
+    chunk.globals.iter().map(|_| Cell::new(None)).collect()
+}
+
+struct Frame {
+    chunk: Rc<Chunk>,
+    globals: GlobalSlots,
+    ip: usize,
+
+    // Heights of the value and iterator stacks when the frame was entered
+    stack_base: usize,
+    iterator_base: usize,
+
+    // Scope to return to once the frame is left; the program's own frame has none
+    caller_scope: Option<Rc<RefCell<Scope>>>,
+}
+
+// A function body along with the code it compiled to. The body is kept alive so that its
+// address, which compiled code is looked up by, can't be reused
+struct Compiled {
+    _body: Rc<Vec<Statement>>,
+    chunk: Rc<Chunk>,
+    globals: GlobalSlots,
+}
+
+// Stack machine running compiled chunks. Calls push frames instead of recursing, so deep
+// recursion can't overflow the native stack, and stops at the same call depth as in the
+// tree-walker
+struct Vm<'a> {
+    env: &'a mut Environment,
+    stack: Vec<Value>,
+    iterators: Vec<Box<dyn Iterator<Item = Value>>>,
+    frames: Vec<Frame>,
+
+    // Function bodies are compiled the first time they are called, separately for
+    // closures of the global scope, which can find globals by slot
+    functions: HashMap<(*const Vec<Statement>, bool), Compiled>,
+}
+
+// Runs a compiled program. On error, the environment is returned to the scope it was in
+// beforehand, as the tree-walker would leave it
+pub fn run(chunk: Rc<Chunk>, env: &mut Environment) -> Result<Value, LulaError> {
+    let scope = env.capture();
+
+    let mut vm = Vm {
+        env,
+        stack: Vec::new(),
+        iterators: Vec::new(),
+        frames: vec![Frame {
+            globals: global_slots(&chunk),
+            chunk,
+            ip: 0,
+            stack_base: 0,
+            iterator_base: 0,
+            caller_scope: None,
+        }],
+        functions: HashMap::new(),
+    };
+
+    let result = vm.execute();
+
+    if result.is_err() {
+        while let Some(frame) = vm.frames.pop() {
+            if let Some(caller_scope) = frame.caller_scope {
+                vm.env.exit_call(caller_scope);
+            }
+        }
+
+        vm.env.restore(scope);
+    }
+
+    result
+}
+
+impl<'a> Vm<'a> {
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("value stack underflow")
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack.last().expect("value stack underflow")
+    }
+
+    fn test(&self, condition: &Value, position: Position) -> Result<bool, LulaError> {
+        self.env.semantics().test(condition, || position)
+    }
+
+    fn pop_many(&mut self, count: usize) -> Vec<Value> {
+        self.stack.split_off(self.stack.len() - count)
+    }
+
+    fn compiled_body(&mut self, func: &Function) -> Result<(Rc<Chunk>, GlobalSlots), LulaError> {
+        let top_level = self.env.is_global(&func.closure);
+        let key = (Rc::as_ptr(&func.body), top_level);
+
+        if let Some(compiled) = self.functions.get(&key) {
+            return Ok((Rc::clone(&compiled.chunk), Rc::clone(&compiled.globals)));
+        }
+
+        let chunk = Rc::new(compile_function(&func.params, &func.body, top_level)?);
+        let globals = global_slots(&chunk);
+
+        self.functions.insert(
+            key,
+            Compiled {
+                _body: Rc::clone(&func.body),
+                chunk: Rc::clone(&chunk),
+                globals: Rc::clone(&globals),
+            },
+        );
+
+        Ok((chunk, globals))
+    }
+
+    // Finds the slot of a global in the running chunk's pool, which is only looked up by
+    // name the first time. Globals are never removed, so a slot once found stays valid
+    fn global_slot(&self, globals: &GlobalSlots, chunk: &Chunk, global: usize) -> Option<usize> {
+        if let Some(slot) = globals[global].get() {
+            return Some(slot);
+        }
+
+        let slot = self.env.global_slot(&chunk.globals[global])?;
+        globals[global].set(Some(slot));
+        Some(slot)
+    }
+
+    fn execute(&mut self) -> Result<Value, LulaError> {
+        // The running frame's chunk, instruction pointer and first stack slot are kept in
+        // locals, and only written back to the frame when another frame is entered
+        let mut chunk = Rc::clone(&self.frames[0].chunk);
+        let mut globals = Rc::clone(&self.frames[0].globals);
+        let mut ip = 0;
+        let mut base = 0;
+
+        loop {
+            let op = chunk.code[ip];
+            ip += 1;
+
+            match op {
+                Op::Constant(i) => self.stack.push(chunk.constants[i].clone()),
+                Op::Nil => self.stack.push(Value::Nil),
+                Op::Pop => {
+                    self.pop();
+                }
+
+                Op::GetVar(i) => {
+                    let val = Expr::evaluate_variable(&chunk.tokens[i], self.env)?;
+                    self.stack.push(val);
+                }
+                Op::SetVar(i) => {
+                    let val = self.pop();
+                    let val = Expr::assign_variable(&chunk.tokens[i], val, self.env)?;
+                    self.stack.push(val);
+                }
+                // Globals that haven't been declared, and constants, are left to the lookup
+                // by name to report
+                Op::GetGlobal(global, name) => {
+                    let val = match self.global_slot(&globals, &chunk, global) {
+                        Some(slot) => self.env.get_global(slot),
+                        None => Expr::evaluate_variable(&chunk.tokens[name], self.env)?,
+                    };
+                    self.stack.push(val);
+                }
+                Op::SetGlobal(global, name) => {
+                    let val = self.pop();
+                    let assigned = self
+                        .global_slot(&globals, &chunk, global)
+                        .map(|slot| self.env.assign_global(slot, val.clone()));
+
+                    let val = match assigned {
+                        Some(Assignment::Assigned) => val,
+                        _ => Expr::assign_variable(&chunk.tokens[name], val, self.env)?,
+                    };
+                    self.stack.push(val);
+                }
+                Op::DefineVar(i, position) => {
+                    let val = self.pop();
+                    let position = chunk.positions[position];
+                    Statement::define_variable(&chunk.names[i], val, position, self.env)?;
+                }
+                Op::PushScope => self.env.push_scope(),
+                Op::PopScope => self.env.pop_scope(),
+
+                Op::GetLocal(slot) => self.stack.push(self.stack[base + slot].clone()),
+                Op::SetLocal(slot) => self.stack[base + slot] = self.peek().clone(),
+                Op::PopLocals(count) => self.stack.truncate(self.stack.len() - count),
+
+                Op::Unary(i) => {
+                    let operand = self.pop();
+                    let val = Expr::apply_unary(&chunk.tokens[i], operand)?;
+                    self.stack.push(val);
+                }
+                Op::Binary(i) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let val = Expr::apply_binary(&chunk.tokens[i], left, right)?;
+                    self.stack.push(val);
+                }
+                Op::Power(i) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let val = Expr::apply_power(&chunk.tokens[i], left, right)?;
+                    self.stack.push(val);
+                }
+                Op::Print => println!("{}", self.pop()),
+                Op::Placeholder(i) => return Err(Expr::placeholder_error(&chunk.tokens[i])),
+                Op::Statement => self.env.steps().count_statement(),
+
+                Op::Jump(target) => ip = target,
+                Op::JumpIfFalse(target, position) => {
+                    let condition = self.pop();
+
+                    if !self.test(&condition, chunk.positions[position])? {
+                        ip = target;
+                    }
+                }
+                Op::AndJump(target, position) => {
+                    if self.test(self.peek(), chunk.positions[position])? {
+                        self.pop();
+                    } else {
+                        ip = target;
+                    }
+                }
+                Op::OrJump(target, position) => {
+                    if self.test(self.peek(), chunk.positions[position])? {
+                        ip = target;
+                    } else {
+                        self.pop();
+                    }
+                }
+
+                Op::CheckCall(paren, arg_count) => {
+                    let call_depth = self.env.call_depth();
+                    Expr::check_call(self.peek(), arg_count, &chunk.tokens[paren], call_depth)?;
+                }
+                Op::Call(paren, arg_count) => {
+                    let args = self.pop_many(arg_count);
+                    let paren = &chunk.tokens[paren];
+
+                    match self.pop() {
+                        Value::NativeFn(native) => {
+                            let val = native.call(&args, paren.position)?;
+                            self.stack.push(val);
+                        }
+
+                        Value::Function(func) => {
+                            let (body, body_globals) = self.compiled_body(&func)?;
+
+                            if let Some(frame) = self.frames.last_mut() {
+                                frame.ip = ip;
+                            }
+
+                            let caller_scope = self.env.enter_call(&func.closure);
+                            base = self.stack.len();
+
+                            if body.local_params {
+                                self.stack.extend(args);
+                            } else {
+                                for (param, arg) in func.params.iter().zip(args) {
+                                    self.env.define(param.clone(), arg);
+                                }
+                            }
+
+                            self.frames.push(Frame {
+                                chunk: Rc::clone(&body),
+                                globals: Rc::clone(&body_globals),
+                                ip: 0,
+                                stack_base: base,
+                                iterator_base: self.iterators.len(),
+                                caller_scope: Some(caller_scope),
+                            });
+
+                            chunk = body;
+                            globals = body_globals;
+                            ip = 0;
+                        }
+
+                        callee => {
+                            return Err(LulaError::internal(
+                                Message::new("value-not-callable").debug_arg(callee),
+                                paren.position,
+                            ))
+                        }
+                    }
+                }
+                Op::Method(name, paren, arg_count) => {
+                    let receiver = self.pop();
+                    let native = Expr::method_native(
+                        &receiver,
+                        &chunk.tokens[name],
+                        &chunk.tokens[paren],
+                        arg_count,
+                    )?;
+
+                    self.stack.push(Value::NativeFn(native));
+                    self.stack.push(receiver);
+                }
+                Op::Closure(i) => {
+                    let proto = &chunk.functions[i];
+
+                    self.stack.push(Value::Function(Function {
+                        name: proto.name.clone(),
+                        params: proto.params.clone(),
+                        body: Rc::clone(&proto.body),
+                        closure: self.env.capture(),
+                    }));
+                }
+                Op::Return => {
+                    let val = self.pop();
+                    let frame = self.frames.pop().expect("call stack underflow");
+
+                    let caller_scope = match frame.caller_scope {
+                        Some(caller_scope) => caller_scope,
+                        None => return Ok(val),
+                    };
+
+                    self.stack.truncate(frame.stack_base);
+                    self.iterators.truncate(frame.iterator_base);
+                    self.env.exit_call(caller_scope);
+
+                    let caller = self.frames.last().expect("call stack underflow");
+                    chunk = Rc::clone(&caller.chunk);
+                    globals = Rc::clone(&caller.globals);
+                    ip = caller.ip;
+                    base = caller.stack_base;
+
+                    self.stack.push(val);
+                }
+
+                Op::List(count) => {
+                    let items = self.pop_many(count);
+                    self.stack.push(Value::list(items));
+                }
+                Op::MapKey(colon) => {
+                    let key = self.pop();
+                    let key = Expr::map_key(key, &chunk.tokens[colon])?;
+                    self.stack.push(Value::String(key));
+                }
+                Op::Map(count) => {
+                    let mut entries = HashMap::new();
+                    let mut vals = self.pop_many(count * 2).into_iter();
+
+                    while let (Some(Value::String(key)), Some(val)) = (vals.next(), vals.next()) {
+                        entries.insert(key, val);
+                    }
+
+                    self.stack.push(Value::map(entries));
+                }
+                Op::Index(bracket) => {
+                    let index = self.pop();
+                    let target = self.pop();
+                    let val = Expr::index_value(&target, &chunk.tokens[bracket], index)?;
+                    self.stack.push(val);
+                }
+                Op::Slice(bracket, op) => {
+                    let end = self.pop();
+                    let start = self.pop();
+                    let target = self.pop();
+
+                    let (start, end) = Expr::range_bounds(start, end, &chunk.tokens[op])?;
+                    let val = Expr::slice_value(&target, &chunk.tokens[bracket], start, end)?;
+                    self.stack.push(val);
+                }
+                Op::SetIndex(bracket) => {
+                    let val = self.pop();
+                    let index = self.pop();
+                    let target = self.pop();
+
+                    Expr::assign_index(&target, &chunk.tokens[bracket], index, val.clone())?;
+                    self.stack.push(val);
+                }
+
+                Op::Iteration(keyword) => {
+                    let position = chunk.tokens[keyword].position;
+                    self.env.steps().count_iteration(position);
+                }
+
+                Op::Iterate(name) => {
+                    let iterable = self.pop();
+                    let items = Statement::iterate(iterable, &chunk.tokens[name])?;
+                    self.iterators.push(items);
+                }
+                Op::IterateRange(op) => {
+                    let end = self.pop();
+                    let start = self.pop();
+
+                    let (start, end) = Expr::range_bounds(start, end, &chunk.tokens[op])?;
+                    self.iterators.push(Statement::range_items(start, end));
+                }
+                Op::Next(target) => match self.iterators.last_mut().and_then(Iterator::next) {
+                    Some(item) => self.stack.push(item),
+                    None => ip = target,
+                },
+                Op::PopIterator => {
+                    self.iterators.pop();
+                }
+            }
+        }
+    }
+}
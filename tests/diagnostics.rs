@@ -0,0 +1,129 @@
+// Snapshot tests for rendered diagnostics.
+//
+// Every `.lla` file in `tests/diagnostics/` is run through the interpreter and
+// its stderr is compared against the `.stderr` file of the same name. Run with
+// `LULA_BLESS=1` to (re)write the snapshots after an intentional change.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const FIXTURE_DIR: &str = "tests/diagnostics";
+
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.peek() == Some(&'[') {
+            // Skip CSI sequence up to and including its final byte
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) && c != '[' {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+
+    stripped
+}
+
+fn render_diagnostics(fixture: &Path, flags: &[&str]) -> String {
+    // The summary line holds a timing, so it's left out
+    let output = Command::new(env!("CARGO_BIN_EXE_lula-lang"))
+        .arg("--quiet")
+        .args(flags)
+        .arg(fixture)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env_remove("LULA_LANG")
+        .output()
+        .expect("failed to run interpreter");
+
+    strip_ansi(&String::from_utf8_lossy(&output.stderr))
+}
+
+// A fixture without a snapshot is a failure rather than an empty expectation, so a new
+// fixture can't pass until it has been blessed
+fn read_snapshot(path: &Path) -> String {
+    fs::read_to_string(path).unwrap_or_else(|err| {
+        panic!(
+            "failed to read snapshot {} ({}), rerun with LULA_BLESS=1 to create it",
+            path.display(),
+            err
+        )
+    })
+}
+
+fn collect_fixtures() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(FIXTURE_DIR);
+
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(dir)
+        .expect("failed to read fixture directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some("lla".as_ref()))
+        .collect();
+
+    fixtures.sort();
+    fixtures
+}
+
+#[test]
+fn diagnostics_match_snapshots() {
+    let bless = env::var_os("LULA_BLESS").is_some();
+    let mut failures = Vec::new();
+
+    for fixture in collect_fixtures() {
+        let name = fixture.file_name().unwrap().to_string_lossy().into_owned();
+        let relative = Path::new(FIXTURE_DIR).join(&name);
+        let snapshot_path = fixture.with_extension("stderr");
+
+        let actual = render_diagnostics(&relative, &[]);
+
+        if bless {
+            fs::write(&snapshot_path, &actual).expect("failed to write snapshot");
+            continue;
+        }
+
+        let expected = read_snapshot(&snapshot_path);
+
+        if actual != expected {
+            failures.push(format!(
+                "{}\n--- expected ---\n{}--- actual ---\n{}",
+                name, expected, actual
+            ));
+        }
+    }
+
+    if !failures.is_empty() {
+        panic!(
+            "{} diagnostic snapshot(s) differ (rerun with LULA_BLESS=1 to update):\n\n{}",
+            failures.len(),
+            failures.join("\n")
+        );
+    }
+}
+
+#[test]
+fn vm_diagnostics_match_snapshots() {
+    // The bytecode VM must report errors exactly as the tree-walker does. While blessing,
+    // the snapshots are being rewritten, so the tree-walker's output is compared directly
+    let bless = env::var_os("LULA_BLESS").is_some();
+
+    for fixture in collect_fixtures() {
+        let name = fixture.file_name().unwrap().to_string_lossy().into_owned();
+        let relative = Path::new(FIXTURE_DIR).join(&name);
+
+        let expected = if bless {
+            render_diagnostics(&relative, &[])
+        } else {
+            read_snapshot(&fixture.with_extension("stderr"))
+        };
+        let actual = render_diagnostics(&relative, &["--backend=vm"]);
+
+        assert_eq!(actual, expected, "VM diagnostics differ for {}", name);
+    }
+}
@@ -0,0 +1,56 @@
+// Checks the syntax trees printed by '--dump-ast', in each of their formats.
+
+use lula::{
+    dump::{program_node, Node},
+    lexer::source_tokens,
+    Parser,
+};
+
+fn node(source: &str) -> Node {
+    let statements = Parser::new(source_tokens(vec![source.to_owned()]))
+        .collect_statements()
+        .unwrap();
+    program_node(&statements)
+}
+
+#[test]
+fn trees_indent_children_under_their_parents() {
+    let tree = node("let total = 1 + 2 * 3\nprint total\n").to_indented();
+
+    assert_eq!(
+        tree,
+        "program\n  let\n    total\n    Plus\n      1\n      Star\n        2\n        3\n  print\n    total\n"
+    );
+}
+
+#[test]
+fn sexps_leave_leaves_bare() {
+    let sexp = node(
+        "func greet(name) {\n    return \"hi \" + name\n}\nif ready {\n    greet(\"lula\")\n} else {\n    print nil\n}\n",
+    )
+    .to_sexp();
+
+    assert_eq!(
+        sexp,
+        "(program (func greet (params name) (body (return (Plus \"hi \" name)))) (if ready (then (call greet \"lula\")) (else (print nil))))"
+    );
+}
+
+#[test]
+fn json_nests_labelled_nodes() {
+    let json = node("xs[0] = -1\n").to_json();
+
+    assert_eq!(
+        json,
+        "{\"label\": \"program\", \"children\": [{\"label\": \"assign-index\", \"children\": [{\"label\": \"xs\", \"children\": []}, {\"label\": \"0\", \"children\": []}, {\"label\": \"Minus\", \"children\": [{\"label\": \"1\", \"children\": []}]}]}]}"
+    );
+}
+
+#[test]
+fn trees_are_dumped_as_parsed() {
+    // Constant folding would have turned this into a single literal
+    assert_eq!(
+        node("print 1 + 2\n").to_sexp(),
+        "(program (print (Plus 1 2)))"
+    );
+}
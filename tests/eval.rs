@@ -0,0 +1,528 @@
+// Checks the embedding API exposed through 'lula::eval'.
+
+use lula::{error::RuntimeErrorKind, eval, LulaError, Value};
+use std::fs;
+
+#[test]
+fn eval_returns_value_of_final_expression() {
+    assert_eq!(eval("1 + 2 * 3"), Ok(Value::Int(7)));
+    assert_eq!(eval("let x = 2\nx = x + 1\n"), Ok(Value::Int(3)));
+}
+
+#[test]
+fn eval_returns_nil_without_final_expression() {
+    assert_eq!(eval("let x = 1\n"), Ok(Value::Nil));
+    assert_eq!(eval(""), Ok(Value::Nil));
+}
+
+#[test]
+fn nil_marks_missing_values() {
+    assert_eq!(eval("let x\nx"), Ok(Value::Nil));
+    assert_eq!(eval("func f() {}\nf()"), Ok(Value::Nil));
+    assert_eq!(eval("nil == nil"), Ok(Value::Bool(true)));
+    assert_eq!(eval("1 == nil"), Ok(Value::Bool(false)));
+    assert_eq!(eval("nil != \"nil\""), Ok(Value::Bool(true)));
+}
+
+#[test]
+fn logical_operators_short_circuit() {
+    assert_eq!(eval("true and 2"), Ok(Value::Int(2)));
+    assert_eq!(eval("nil and 2"), Ok(Value::Nil));
+    assert_eq!(
+        eval("false or \"default\""),
+        Ok(Value::String(String::from("default")))
+    );
+    assert_eq!(eval("1 or 2"), Ok(Value::Int(1)));
+
+    // The right operand would be a name error if it were evaluated
+    assert_eq!(eval("false and missing"), Ok(Value::Bool(false)));
+    assert_eq!(eval("true or missing"), Ok(Value::Bool(true)));
+}
+
+#[test]
+fn powers_are_right_associative_and_bind_tighter_than_negation() {
+    assert_eq!(eval("2 ** 10"), Ok(Value::Int(1024)));
+    assert_eq!(eval("2 ** 3 ** 2"), Ok(Value::Int(512)));
+    assert_eq!(eval("-2 ** 2"), Ok(Value::Int(-4)));
+    assert_eq!(eval("(-2) ** 3"), Ok(Value::Int(-8)));
+    assert_eq!(eval("2 * 3 ** 2"), Ok(Value::Int(18)));
+
+    // Integer powers become floats when the exponent is negative or the result too large
+    assert_eq!(eval("2 ** -1"), Ok(Value::Float(0.5)));
+    assert_eq!(eval("4.0 ** 0.5"), Ok(Value::Float(2.0)));
+    assert_eq!(eval("10 ** 20"), Ok(Value::Float(1e20)));
+}
+
+#[test]
+fn bitwise_operators_apply_to_integers() {
+    assert_eq!(eval("12 & 10"), Ok(Value::Int(8)));
+    assert_eq!(eval("12 | 10"), Ok(Value::Int(14)));
+    assert_eq!(eval("12 ^ 10"), Ok(Value::Int(6)));
+    assert_eq!(eval("~5"), Ok(Value::Int(-6)));
+    assert_eq!(eval("-16 >> 2"), Ok(Value::Int(-4)));
+    assert_eq!(eval("1 << 64"), Ok(Value::Int(0)));
+    assert_eq!(eval("-1 >> 64"), Ok(Value::Int(-1)));
+
+    // Shifts bind looser than arithmetic, and '&', '^' and '|' looser still, but all of them
+    // tighter than comparisons
+    assert_eq!(eval("1 << 2 + 1"), Ok(Value::Int(8)));
+    assert_eq!(eval("1 | 2 ^ 3 & 6"), Ok(Value::Int(1)));
+    assert_eq!(eval("5 & 1 == 1"), Ok(Value::Bool(true)));
+
+    for source in ["1.0 & 1", "\"a\" | 1", "~2.5", "1 << -1"] {
+        assert!(eval(source).is_err(), "{}", source);
+    }
+}
+
+#[test]
+fn conditional_expressions_evaluate_one_value() {
+    assert_eq!(
+        eval("let n = 5\nn > 3 ? \"big\" : \"small\""),
+        Ok(Value::String(String::from("big")))
+    );
+    assert_eq!(
+        eval("let n = 0\nn < 0 ? -1 : n == 0 ? 0 : 1"),
+        Ok(Value::Int(0))
+    );
+
+    // The value that isn't chosen would be a name error if it were evaluated
+    assert_eq!(eval("let t = true\nt ? 1 : missing"), Ok(Value::Int(1)));
+    assert_eq!(eval("let f = nil\nf ? missing : 2"), Ok(Value::Int(2)));
+
+    // Conditionals bind looser than 'or' but tighter than assignment
+    assert_eq!(
+        eval("let x = 0\nx = false or true ? 1 : 2\nx"),
+        Ok(Value::Int(1))
+    );
+}
+
+#[test]
+fn placeholders_only_fail_when_reached() {
+    assert_eq!(
+        eval("func later() {\n    return ???\n}\nlet x = false ? ??? : 1\nx"),
+        Ok(Value::Int(1))
+    );
+
+    let errors = eval("let x = 1\nx + ???").unwrap_err();
+    assert!(matches!(
+        errors.as_slice(),
+        [LulaError::RuntimeError(RuntimeErrorKind::Unimplemented, ..)]
+    ));
+}
+
+#[test]
+fn lists_can_be_indexed_and_assigned() {
+    assert_eq!(eval("[1, 2, 3][1]"), Ok(Value::Int(2)));
+    assert_eq!(
+        eval("let xs = [1, 2]\nxs[0] = 5\nxs[0] + xs[1]"),
+        Ok(Value::Int(7))
+    );
+    assert_eq!(eval("[[1], [2, 3]][1][0]"), Ok(Value::Int(2)));
+    assert_eq!(eval("[\n    1,\n    2\n][1]"), Ok(Value::Int(2)));
+
+    // Lists are shared rather than copied
+    assert_eq!(
+        eval("let a = [1]\nlet b = a\nb[0] = 2\na[0]"),
+        Ok(Value::Int(2))
+    );
+}
+
+#[test]
+fn compound_assignment_updates_variables_and_elements() {
+    assert_eq!(
+        eval("let x = 2\nx += 3\nx *= 2\nx -= 1\nx"),
+        Ok(Value::Int(9))
+    );
+    assert_eq!(eval("let x = 7\nx /= 2\nx %= 2\nx"), Ok(Value::Int(1)));
+    assert_eq!(
+        eval("let s = \"a\"\ns += \"b\"\ns"),
+        Ok(Value::String(String::from("ab")))
+    );
+    assert_eq!(
+        eval("let xs = [1, 2]\nxs[1] += 5\nxs[1]"),
+        Ok(Value::Int(7))
+    );
+
+    // Like plain assignment, compound assignment is an expression that is right-associative
+    assert_eq!(
+        eval("let a = 1\nlet b = 2\na += b *= 3\na"),
+        Ok(Value::Int(7))
+    );
+}
+
+#[test]
+fn list_indices_are_checked() {
+    let errors = eval("let xs = [1, 2]\nxs[2]").unwrap_err();
+
+    assert!(matches!(
+        errors.as_slice(),
+        [LulaError::RuntimeError(RuntimeErrorKind::Index, ..)]
+    ));
+}
+
+#[test]
+fn maps_can_be_indexed_and_assigned() {
+    assert_eq!(eval("({ \"a\": 1, b: 2 })[\"b\"]"), Ok(Value::Int(2)));
+    assert_eq!(
+        eval("let m = {}\nm[\"k\"] = 3\nm[\"k\"]"),
+        Ok(Value::Int(3))
+    );
+    assert_eq!(eval("let m = { a: 1 }\nm[\"missing\"]"), Ok(Value::Nil));
+    assert_eq!(
+        eval("let m = {\n    a: 1,\n    b: 2,\n}\nm[\"a\"]"),
+        Ok(Value::Int(1))
+    );
+    assert_eq!(
+        eval("({ a: [1], b: 2 }) == { b: 2, a: [1] }"),
+        Ok(Value::Bool(true))
+    );
+}
+
+#[test]
+fn containers_holding_themselves_print_and_compare() {
+    let s = |text: &str| Ok(Value::String(String::from(text)));
+
+    // A container met again inside itself is shortened rather than written out forever
+    let list = "let a = [1, 2]\na[1] = a\n";
+    assert_eq!(eval(&format!("{}str(a)", list)), s("[1, [...]]"));
+    assert_eq!(
+        eval(&format!("{}str([a, a])", list)),
+        s("[[1, [...]], [1, [...]]]")
+    );
+    assert_eq!(
+        format!("{:?}", eval(&format!("{}a", list)).unwrap()),
+        "List([Int(1), List([...])])"
+    );
+
+    let map = "let m = { n: 1 }\nm[\"me\"] = m\n";
+    assert_eq!(
+        eval(&format!("{}str(m)", map)),
+        s("{\"me\": {...}, \"n\": 1}")
+    );
+    assert_eq!(
+        format!("{:?}", eval(&format!("{}m", map)).unwrap()),
+        "Map({\"me\": Map({...}), \"n\": Int(1)})"
+    );
+
+    // Comparisons end where they started, so only the items outside the cycle decide them
+    let lists = "let b = [1, 2]\nb[1] = b\nlet c = [2, 2]\nc[1] = c\n";
+    assert_eq!(
+        eval(&format!("{}{}[a == a, a == b, a == c]", list, lists)),
+        eval("[true, true, false]")
+    );
+
+    let maps = "let k = { n: 1 }\nk[\"me\"] = k\nlet l = { n: 2 }\nl[\"me\"] = l\n";
+    assert_eq!(
+        eval(&format!("{}{}[m == m, m == k, m == l]", map, maps)),
+        eval("[true, true, false]")
+    );
+}
+
+#[test]
+fn for_loops_iterate_over_lists_and_map_keys() {
+    let source = "let total = 0\nfor x in [1, 2, 3] { total = total + x }\ntotal";
+    assert_eq!(eval(source), Ok(Value::Int(6)));
+
+    let source = "let keys = \"\"\nfor k in { b: 1, a: 2 } { keys = keys + k }\nkeys";
+    assert_eq!(eval(source), Ok(Value::String(String::from("ab"))));
+}
+
+#[test]
+fn strings_interpolate_expressions() {
+    let s = |text: &str| Ok(Value::String(String::from(text)));
+
+    assert_eq!(
+        eval("let name = \"Ana\"\nlet age = 30\n\"hello ${name}, you are ${age + 1}\""),
+        s("hello Ana, you are 31")
+    );
+    assert_eq!(eval("\"${1.5}${[1, nil]}${true}\""), s("1.5[1, nil]true"));
+    assert_eq!(
+        eval("\"a ${\"b ${1 + 1}\"} ${({k: 3})[\"k\"]}\""),
+        s("a b 2 3")
+    );
+    assert_eq!(eval("\"\\${x} costs $5\""), s("${x} costs $5"));
+
+    // Values are converted by the builtin even if a variable shadows its name
+    assert_eq!(eval("let str = 1\n\"${2}\""), s("2"));
+}
+
+#[test]
+fn strings_and_lists_can_be_sliced() {
+    let s = |text: &str| Ok(Value::String(String::from(text)));
+
+    assert_eq!(eval("\"hello\"[1]"), s("e"));
+    assert_eq!(eval("\"hello\"[1..4]"), s("ell"));
+    assert_eq!(eval("\"héllo wörld\"[7]"), s("ö"));
+    assert_eq!(eval("\"héllo\"[0..2]"), s("hé"));
+    assert_eq!(eval("\"hello\"[2..2]"), s(""));
+    assert_eq!(eval("[1, 2, 3, 4][1..3] == [2, 3]"), Ok(Value::Bool(true)));
+}
+
+#[test]
+fn string_indices_are_checked() {
+    for source in [
+        "\"abc\"[3]",
+        "\"abc\"[-1]",
+        "\"abc\"[2..4]",
+        "\"abc\"[2..1]",
+    ] {
+        assert!(
+            matches!(
+                eval(source).unwrap_err().as_slice(),
+                [LulaError::RuntimeError(RuntimeErrorKind::Index, ..)]
+            ),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn native_functions_are_predefined() {
+    let s = |text: &str| Ok(Value::String(String::from(text)));
+
+    assert_eq!(
+        eval("len(\"héllo\") + len([1, 2]) + len({ a: 1 })"),
+        Ok(Value::Int(8))
+    );
+    assert_eq!(eval("type(1)"), s("int"));
+    assert_eq!(eval("type(1.0)"), s("float"));
+    assert_eq!(eval("type(len)"), s("function"));
+    assert_eq!(eval("type({})"), s("map"));
+    assert_eq!(eval("str(1.5) + str(nil)"), s("1.5nil"));
+    assert_eq!(eval("num(\" -12.5 \")"), Ok(Value::Float(-12.5)));
+    assert_eq!(eval("num(\"twelve\")"), Ok(Value::Nil));
+    assert_eq!(eval("num(\"--1\")"), Ok(Value::Nil));
+    assert_eq!(eval("num(str(-2.5e-300))"), Ok(Value::Float(-2.5e-300)));
+    assert_eq!(eval("num(\"0x1_f\")"), Ok(Value::Int(31)));
+    assert_eq!(eval("abs(-3)"), Ok(Value::Int(3)));
+    assert_eq!(eval("clock() > 0"), Ok(Value::Bool(true)));
+
+    // Natives are ordinary globals, so they can be shadowed
+    assert_eq!(eval("let len = 3\nlen"), Ok(Value::Int(3)));
+}
+
+#[test]
+fn native_functions_check_their_arguments() {
+    for source in ["len(1)", "len()", "abs(\"x\")", "num(true)"] {
+        assert!(
+            matches!(
+                eval(source).unwrap_err().as_slice(),
+                [LulaError::RuntimeError(RuntimeErrorKind::Type, ..)]
+            ),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn string_natives_work_as_functions_and_methods() {
+    let s = |text: &str| Ok(Value::String(String::from(text)));
+
+    assert_eq!(eval("upper(\"lula\")"), s("LULA"));
+    assert_eq!(eval("\"  Lula \".trim().lower()"), s("lula"));
+    assert_eq!(eval("\"a,b,c\".split(\",\").join(\"-\")"), s("a-b-c"));
+    assert_eq!(eval("len(\"abc\".split(\"\"))"), Ok(Value::Int(3)));
+    assert_eq!(eval("\"hello\".replace(\"l\", \"L\")"), s("heLLo"));
+    assert_eq!(eval("\"hello\".contains(\"ell\")"), Ok(Value::Bool(true)));
+    assert_eq!(
+        eval("starts_with(\"hello\", \"he\")"),
+        Ok(Value::Bool(true))
+    );
+    assert_eq!(eval("\"hello\".ends_with(\"he\")"), Ok(Value::Bool(false)));
+
+    // Methods find builtins even when a global shadows them
+    assert_eq!(eval("let upper = 1\n\"a\".upper()"), s("A"));
+}
+
+#[test]
+fn math_natives_and_constants() {
+    let n = |val: f64| Ok(Value::Float(val));
+
+    assert_eq!(eval("sqrt(16) + pow(2, 10)"), n(1028.0));
+
+    // Powers agree with the '**' operator, keeping integers where they can
+    assert_eq!(eval("pow(2, 3)"), Ok(Value::Int(8)));
+    assert_eq!(eval("pow(2, 3) == 2 ** 3"), Ok(Value::Bool(true)));
+    assert_eq!(eval("pow(2, -1)"), n(0.5));
+    assert_eq!(eval("pow(10, 20)"), n(1e20));
+    assert_eq!(eval("pow(4.0, 0.5)"), n(2.0));
+    assert_eq!(
+        eval("[floor(1.5), ceil(1.5), round(2.5), round(-2.5)] == [1, 2, 3, -3]"),
+        Ok(Value::Bool(true))
+    );
+    assert_eq!(eval("sin(0) + cos(0)"), n(1.0));
+    assert_eq!(eval("log(E)"), n(1.0));
+    assert_eq!(eval("min(3, -1.5) + max(3, -1)"), n(1.5));
+    assert_eq!(eval("floor(PI * 1000)"), Ok(Value::Int(3141)));
+}
+
+#[test]
+fn numbers_format_with_fixed_decimals() {
+    let s = |text: &str| Ok(Value::String(String::from(text)));
+
+    // Whole floats keep their '.0' everywhere they're turned into text, and integers have
+    // none, so decimals are only forced or dropped through 'to_fixed'
+    assert_eq!(
+        eval("str(3.0) + \" \" + str(3) + \" ${3.0}\""),
+        s("3.0 3 3.0")
+    );
+    assert_eq!(eval("to_fixed(3.0, 0)"), s("3"));
+    assert_eq!(eval("to_fixed(3, 2)"), s("3.00"));
+    assert_eq!(eval("to_fixed(3.14159, 2)"), s("3.14"));
+    assert_eq!(eval("to_fixed(-0.125, 2)"), s("-0.12"));
+    assert_eq!(
+        eval("to_fixed(9007199254740993, 1)"),
+        s("9007199254740993.0")
+    );
+    assert_eq!(eval("to_fixed(1.0 / 0, 2)"), s("inf"));
+    assert_eq!(eval("(2.5).to_fixed(1)"), s("2.5"));
+
+    for source in [
+        "to_fixed(1, -1)",
+        "to_fixed(1, 101)",
+        "to_fixed(1, 2.0)",
+        "to_fixed(\"1\", 2)",
+    ] {
+        let errors = eval(source).unwrap_err();
+        assert!(
+            matches!(
+                errors.as_slice(),
+                [LulaError::RuntimeError(RuntimeErrorKind::Type, ..)]
+            ),
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn integers_and_floats_are_separate_types() {
+    let int = |val: i64| Ok(Value::Int(val));
+    let float = |val: f64| Ok(Value::Float(val));
+
+    assert_eq!(eval("7 / 2"), int(3));
+    assert_eq!(eval("-7 / 2"), int(-3));
+    assert_eq!(eval("-7 % 3"), int(-1));
+    assert_eq!(eval("7.0 / 2"), float(3.5));
+    assert_eq!(eval("1 + 0.5"), float(1.5));
+    assert_eq!(eval("len([1, 2]) * 3"), int(6));
+
+    // Results that aren't integers are worked out with floats instead
+    assert_eq!(
+        eval("9223372036854775807 + 1"),
+        float(9223372036854775808.0)
+    );
+    assert_eq!(eval("1.0 / 0"), float(f64::INFINITY));
+
+    // Though a remainder always fits in an integer, even where the quotient doesn't
+    let min = "let m = -9223372036854775807 - 1\n";
+    assert_eq!(eval(&format!("{}m % -1", min)), int(0));
+    assert_eq!(
+        eval(&format!("{}m / -1", min)),
+        float(9223372036854775808.0)
+    );
+
+    // Except for division by zero, which has no integer result
+    for source in ["1 / 0", "let n = 0\n5 % n"] {
+        let errors = eval(source).unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [LulaError::RuntimeError(RuntimeErrorKind::Arithmetic, ..)]
+        ));
+    }
+
+    // Integers and floats compare by value
+    assert_eq!(eval("1 == 1.0"), Ok(Value::Bool(true)));
+    assert_eq!(eval("[1, 2] == [1.0, 2.0]"), Ok(Value::Bool(true)));
+    assert_eq!(
+        eval("9007199254740993 > 9007199254740992.0"),
+        Ok(Value::Bool(true))
+    );
+    assert_eq!(eval("2 < 2.5 and 3 > 2.5"), Ok(Value::Bool(true)));
+
+    // Floats always print as floats, and only integers can index
+    assert_eq!(
+        eval("str(2.0) + str(2)"),
+        Ok(Value::String(String::from("2.02")))
+    );
+    assert!(eval("[1, 2][1.0]").is_err());
+
+    // Very large and very small floats print with an exponent rather than every digit
+    assert_eq!(
+        eval("str(1e300) + \" \" + str(0.00001) + \" \" + str(-123456.5)"),
+        Ok(Value::String(String::from("1e300 1e-5 -123456.5")))
+    );
+
+    // Loop counters are integers when the range starts at one
+    let source = "let text = \"\"\nfor i in 0..3 { text = text + str(i) }\ntext";
+    assert_eq!(eval(source), Ok(Value::String(String::from("012"))));
+}
+
+#[test]
+fn file_natives_read_and_write_files() {
+    let path = std::env::temp_dir().join("lula-eval-file-natives.txt");
+    let path = path.to_str().unwrap();
+
+    let source = format!(
+        "write_file({0:?}, \"one\")\nappend_file({0:?}, \" two\")\nread_file({0:?})",
+        path
+    );
+    assert_eq!(eval(&source), Ok(Value::String(String::from("one two"))));
+
+    // Failures give nil rather than stopping the program
+    assert_eq!(eval("read_file(\"/does/not/exist.txt\")"), Ok(Value::Nil));
+    assert_eq!(
+        eval("write_file(\"/does/not/exist.txt\", \"\")"),
+        Ok(Value::Nil)
+    );
+
+    // The reason for the last failure is kept, and cleared by the next success
+    let reason = |path: &str| {
+        Ok(Value::String(
+            fs::read_to_string(path).unwrap_err().to_string(),
+        ))
+    };
+    let missing = "/does/not/exist.txt";
+    let directory = std::env::temp_dir();
+    let directory = directory.to_str().unwrap();
+
+    assert_eq!(eval("io_error()"), Ok(Value::Nil));
+    assert_eq!(
+        eval(&format!("read_file({:?})\nio_error()", missing)),
+        reason(missing)
+    );
+    assert_eq!(
+        eval(&format!("read_file({:?})\nio_error()", directory)),
+        reason(directory)
+    );
+    assert_ne!(reason(missing), reason(directory));
+    assert_eq!(
+        eval(&format!(
+            "read_file({:?})\nread_file({:?})\nio_error()",
+            missing, path
+        )),
+        Ok(Value::Nil)
+    );
+}
+
+#[test]
+fn eval_collects_syntax_errors() {
+    let errors = eval("let = 1\nlet = 2\n").unwrap_err();
+
+    assert_eq!(errors.len(), 2);
+    assert!(errors
+        .iter()
+        .all(|err| matches!(err, LulaError::ParseError(..))));
+}
+
+#[test]
+fn eval_reports_runtime_errors() {
+    let errors = eval("print missing\n").unwrap_err();
+
+    assert!(matches!(
+        errors.as_slice(),
+        [LulaError::RuntimeError(RuntimeErrorKind::Name, ..)]
+    ));
+}
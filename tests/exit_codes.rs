@@ -0,0 +1,195 @@
+// Checks that each class of failure exits with its own status code.
+
+use std::{env, fs, path::PathBuf, process::Command};
+
+fn exit_code(args: &[&str]) -> Option<i32> {
+    Command::new(env!("CARGO_BIN_EXE_lula-lang"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to run interpreter")
+        .status
+        .code()
+}
+
+fn write_temp_source(name: &str, source: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("lula-exit-codes-{}", name));
+    fs::write(&path, source).expect("failed to write temporary source file");
+    path
+}
+
+#[test]
+fn successful_run_exits_with_zero() {
+    let path = write_temp_source("ok.lla", "let x = 1\nx = x + 1\n");
+    let path = path.to_str().unwrap();
+
+    assert_eq!(exit_code(&[path]), Some(0));
+    assert_eq!(exit_code(&["--backend=vm", "--optimize", path]), Some(0));
+}
+
+#[test]
+fn script_arguments_are_passed_to_the_script() {
+    let path = write_temp_source(
+        "args.lla",
+        "if args == [\"a\", \"b c\"] {\n} else {\n    print missing\n}\n",
+    );
+    let path = path.to_str().unwrap();
+
+    assert_eq!(exit_code(&[path, "--", "a", "b c"]), Some(0));
+    assert_eq!(exit_code(&[path, "a", "b c"]), Some(0));
+    assert_eq!(exit_code(&[path, "a"]), Some(70));
+}
+
+#[test]
+fn several_files_run_as_one_program() {
+    let first = write_temp_source("first.lla", "let greeting = \"hi\"\n");
+    let second = write_temp_source("second.lla", "print greeting\n");
+    let broken = write_temp_source("broken.lla", "print greeting + 1\n");
+
+    let first = first.to_str().unwrap();
+    let broken = broken.to_str().unwrap();
+    assert_eq!(exit_code(&[first, second.to_str().unwrap()]), Some(0));
+
+    // Errors are reported against the file they occurred in
+    let output = Command::new(env!("CARGO_BIN_EXE_lula-lang"))
+        .args([first, broken])
+        .output()
+        .expect("failed to run interpreter");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(70));
+    assert!(stderr.contains(broken), "unexpected stderr: {}", stderr);
+}
+
+#[test]
+fn usage_errors_exit_with_64() {
+    assert_eq!(exit_code(&[]), Some(64));
+    assert_eq!(exit_code(&["Cargo.toml"]), Some(64));
+}
+
+#[test]
+fn syntax_errors_exit_with_65() {
+    assert_eq!(
+        exit_code(&["tests/diagnostics/unterminated_string.lla"]),
+        Some(65)
+    );
+    assert_eq!(
+        exit_code(&["tests/diagnostics/missing_identifier.lla"]),
+        Some(65)
+    );
+}
+
+#[test]
+fn runtime_errors_exit_with_70() {
+    assert_eq!(
+        exit_code(&["tests/diagnostics/binary_type_mismatch.lla"]),
+        Some(70)
+    );
+}
+
+#[test]
+fn io_errors_exit_with_74() {
+    assert_eq!(exit_code(&["tests/does_not_exist.lla"]), Some(74));
+}
+
+#[test]
+fn check_reports_syntax_errors_without_running() {
+    assert_eq!(
+        exit_code(&["check", "tests/diagnostics/binary_type_mismatch.lla"]),
+        Some(0)
+    );
+    assert_eq!(
+        exit_code(&["check", "tests/diagnostics/missing_identifier.lla"]),
+        Some(65)
+    );
+    assert_eq!(exit_code(&["check"]), Some(64));
+}
+
+#[test]
+fn spelling_warnings_do_not_fail_checks() {
+    let path = write_temp_source("typo.lla", "# Prints a mesage\nprint \"helo\"\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lula-lang"))
+        .args(["check", "--spell", path.to_str().unwrap()])
+        .output()
+        .expect("failed to run interpreter");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(
+        stderr.contains("Spelling warning[W0100]"),
+        "unexpected stderr: {}",
+        stderr
+    );
+    assert_eq!(exit_code(&["check", "--spelling", "x.lla"]), Some(64));
+}
+
+#[test]
+fn long_running_loops_warn_without_failing() {
+    let path = write_temp_source(
+        "busy.lla",
+        "let total = 0\nfor i in 0..100 {\n    total = total + i\n}\n",
+    );
+    let path = path.to_str().unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lula-lang"))
+        .args(["--warn-steps=50", path])
+        .output()
+        .expect("failed to run interpreter");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(
+        stderr.starts_with("Long-running loop warning[W0200]") && stderr.contains("line 2"),
+        "unexpected stderr: {}",
+        stderr
+    );
+
+    assert_eq!(exit_code(&["--warn-steps=lots", path]), Some(64));
+}
+
+#[test]
+fn commands_end_with_a_summary_unless_quiet() {
+    let path = write_temp_source("summary.lla", "for i in 0..2 {\n    print i\n}\n");
+    let path = path.to_str().unwrap();
+
+    let stderr = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_lula-lang"))
+            .args(args)
+            .output()
+            .expect("failed to run interpreter");
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+
+    let summary = stderr(&[path]);
+    assert!(
+        summary.starts_with("finished: 0 errors, 0 warnings, ran 3 statements in "),
+        "unexpected stderr: {}",
+        summary
+    );
+    assert_eq!(stderr(&["check", path]), "finished: 0 errors, 0 warnings\n");
+    assert_eq!(stderr(&["--quiet", path]), "");
+}
+
+#[test]
+fn strict_mode_rejects_conditions_that_are_not_booleans() {
+    let path = write_temp_source(
+        "strict.lla",
+        "let name = \"lula\"\nif name {\n    print name\n}\n",
+    );
+    let path = path.to_str().unwrap();
+
+    assert_eq!(exit_code(&[path]), Some(0));
+    assert_eq!(exit_code(&["--strict", path]), Some(70));
+}
+
+#[test]
+fn dumping_the_ast_skips_running() {
+    let path = write_temp_source("dump.lla", "print 1 / 0\n");
+    let path = path.to_str().unwrap();
+
+    assert_eq!(exit_code(&[path]), Some(70));
+    assert_eq!(exit_code(&["--dump-ast", path]), Some(0));
+    assert_eq!(exit_code(&["--dump-ast=json", path]), Some(0));
+    assert_eq!(exit_code(&["--dump-ast=xml", path]), Some(64));
+}
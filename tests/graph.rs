@@ -0,0 +1,74 @@
+// Checks the call and file dependency graphs built from resolved programs.
+
+use lula::{
+    graph::{call_graph, file_graph, Graph},
+    lexer::source_tokens,
+    resolver::resolve,
+    Parser,
+};
+
+fn graphs(sources: &[&str]) -> (Graph, Graph) {
+    let statements = Parser::new(source_tokens(
+        sources.iter().map(|s| s.to_string()).collect(),
+    ))
+    .collect_statements()
+    .unwrap();
+    let resolution = resolve(&statements);
+
+    let paths: Vec<String> = (0..sources.len()).map(|i| format!("{}.lla", i)).collect();
+    (
+        call_graph(&statements, &resolution),
+        file_graph(&resolution, &paths),
+    )
+}
+
+fn labels(graph: &Graph) -> Vec<&str> {
+    graph.nodes.iter().map(|node| node.label.as_str()).collect()
+}
+
+#[test]
+fn calls_link_named_functions() {
+    let (calls, _) = graphs(&[
+        "func a() {\n    return b() + len(\"x\")\n}\nlet b = func() {\n    let inner = func() {\n        return a()\n    }\n    return 1\n}\nprint a()\n",
+    ]);
+
+    assert_eq!(labels(&calls), ["<main>", "a", "b", "inner"]);
+
+    // Builtins such as 'len' are left out
+    assert_eq!(calls.edges, [(0, 1), (1, 2), (3, 1)]);
+}
+
+#[test]
+fn shadowed_functions_are_separate_nodes() {
+    let (calls, _) =
+        graphs(&["func f() {\n    func f() {\n        return 1\n    }\n    return f()\n}\nf()\n"]);
+
+    assert_eq!(labels(&calls), ["<main>", "f", "f"]);
+    assert_eq!(calls.edges, [(0, 1), (1, 2)]);
+}
+
+#[test]
+fn files_depend_on_declarations_they_use() {
+    let (_, files) = graphs(&[
+        "let shared = 1\nfunc helper() {\n    return shared\n}\n",
+        "print helper()\nlet own = 2\nprint own\n",
+        "print shared + helper()\n",
+    ]);
+
+    assert_eq!(labels(&files), ["0.lla", "1.lla", "2.lla"]);
+    assert_eq!(files.edges, [(1, 0), (2, 0)]);
+}
+
+#[test]
+fn graphs_render_as_dot_and_json() {
+    let (calls, _) = graphs(&["func f() {\n}\nf()\n"]);
+
+    assert_eq!(
+        calls.to_dot("calls"),
+        "digraph calls {\n    n0 [label=\"<main>\"];\n    n1 [label=\"f\"];\n    n0 -> n1;\n}\n"
+    );
+    assert_eq!(
+        calls.to_json(),
+        "{\"nodes\": [{\"id\": 0, \"label\": \"<main>\", \"line\": null}, {\"id\": 1, \"label\": \"f\", \"line\": 1}], \"edges\": [{\"from\": 0, \"to\": 1}]}\n"
+    );
+}
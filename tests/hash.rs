@@ -0,0 +1,33 @@
+// Checks that program hashes depend on structure alone and stay stable over time.
+
+use lula::{hash::hash_program, Lexer, Parser};
+
+fn hash(source: &str) -> u64 {
+    let statements = Parser::new(Lexer::new(source.to_owned()))
+        .collect_statements()
+        .unwrap();
+
+    hash_program(&statements)
+}
+
+#[test]
+fn layout_and_comments_do_not_change_hash() {
+    assert_eq!(
+        hash("let x = 1 # one\nprint x + 2\n"),
+        hash("# header\nlet   x = 1\n\nprint   x+2")
+    );
+}
+
+#[test]
+fn structural_changes_change_hash() {
+    assert_ne!(hash("print 1 + 2"), hash("print 1 + 3"));
+    assert_ne!(hash("print 1 + 2"), hash("print (1 + 2)"));
+    assert_ne!(hash("let x = 1"), hash("let y = 1"));
+    assert_ne!(hash("print 1"), hash("print 1.0"));
+}
+
+#[test]
+fn hashes_are_stable() {
+    // Stored hashes must stay valid, so this value should never change
+    assert_eq!(hash("print 1"), 0xfab7_91ae_b962_fa8c);
+}
@@ -0,0 +1,60 @@
+// Checks the natives that read standard input against a piped stdin.
+
+use std::{
+    env, fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+fn write_temp_source(name: &str, source: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("lula-io-{}", name));
+    fs::write(&path, source).expect("failed to write temporary source file");
+    path
+}
+
+fn run_with_stdin(args: &[&str], stdin: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lula-lang"))
+        .arg("--quiet")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run interpreter");
+
+    // Dropping stdin closes the pipe, so the script sees the end of input
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .expect("failed to write to interpreter");
+
+    let output = child.wait_with_output().expect("failed to run interpreter");
+    assert_eq!(output.status.code(), Some(0));
+
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn input_prompts_and_reads_lines_until_eof() {
+    let path = write_temp_source(
+        "input.lla",
+        "let first = input(\"name? \")\n\
+         let second = input(\"again? \")\n\
+         let third = input(\"more? \")\n\
+         print \"[\" + first + \"][\" + second + \"]\"\n\
+         print third == nil\n",
+    );
+    let path = path.to_str().unwrap();
+
+    // Both '\n' and '\r\n' line endings are stripped, and once input runs out the prompt
+    // is still written but nil is returned
+    let expected = "name? again? more? [Ada][Lovelace]\ntrue\n";
+
+    assert_eq!(run_with_stdin(&[path], "Ada\nLovelace\r\n"), expected);
+    assert_eq!(
+        run_with_stdin(&["--backend=vm", path], "Ada\nLovelace\r\n"),
+        expected
+    );
+}
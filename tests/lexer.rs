@@ -0,0 +1,306 @@
+// Checks the lexer: on demand tokens, spans, shared lexemmes and Unicode text.
+
+use std::rc::Rc;
+
+use lula::{
+    token::{Literal, Span, TokenKind},
+    Lexer, LulaError,
+};
+
+fn kinds(source: &str) -> Vec<Result<TokenKind, LulaError>> {
+    Lexer::new(source.to_owned())
+        .map(|result| result.map(|token| token.kind))
+        .collect()
+}
+
+#[test]
+fn tokens_are_lexed_on_demand() {
+    // Taking the first tokens doesn't reach the bad symbol later on
+    let first: Vec<_> = Lexer::new(String::from("print 1 $"))
+        .take(2)
+        .map(|result| result.map(|token| token.kind))
+        .collect();
+
+    assert_eq!(first.len(), 2);
+    assert!(first.iter().all(Result::is_ok));
+}
+
+#[test]
+fn lexing_carries_on_past_errors() {
+    let kinds = kinds("1 $ 2\n(");
+
+    assert!(kinds[1].is_err());
+    assert_eq!(kinds[2], Ok(TokenKind::Literal(Literal::Int(2))));
+    assert_eq!(kinds[3], Ok(TokenKind::Newline));
+
+    // Unmatched brackets are only known once the source ends, just before end-of-file
+    assert!(kinds[kinds.len() - 2].is_err());
+    assert_eq!(kinds.last(), Some(&Ok(TokenKind::Eof)));
+}
+
+#[test]
+fn large_sources_lex_in_linear_time() {
+    // Quadratic lexing would take minutes here
+    let source = "let total = total + 1.5 * \"x\".len()\n".repeat(50_000);
+    let tokens = Lexer::new(source).collect_tokens().unwrap();
+
+    assert_eq!(tokens.len(), 50_000 * 13 + 1);
+}
+
+#[test]
+fn tokens_span_the_bytes_they_were_lexed_from() {
+    let source = "let s = \"héllo\"  # ünïcode\nprint s";
+    let tokens = Lexer::new(source.to_owned()).collect_tokens().unwrap();
+
+    let texts: Vec<&str> = tokens
+        .iter()
+        .map(|token| &source[token.span.start..token.span.end])
+        .collect();
+    assert_eq!(
+        texts,
+        ["let", "s", "=", "\"héllo\"", "\n", "print", "s", ""]
+    );
+
+    let eof = tokens.last().unwrap();
+    assert_eq!(
+        eof.span,
+        Span {
+            start: source.len(),
+            end: source.len()
+        }
+    );
+}
+
+#[test]
+fn repeated_lexemmes_share_their_text() {
+    let tokens = Lexer::new(String::from("total = total + \"a\" + \"a\""))
+        .collect_tokens()
+        .unwrap();
+
+    let text = |index: usize| match &tokens[index].kind {
+        TokenKind::Literal(Literal::Identifier(text))
+        | TokenKind::Literal(Literal::String(text)) => Rc::clone(text),
+        kind => panic!("expected an identifier or string, found {:?}", kind),
+    };
+
+    assert!(Rc::ptr_eq(&text(0), &text(2)));
+    assert!(Rc::ptr_eq(&text(4), &text(6)));
+}
+
+#[test]
+fn identifiers_may_use_unicode_letters() {
+    let tokens = Lexer::new(String::from("let größe = naïve_名前2 + cafe\u{301}"))
+        .collect_tokens()
+        .unwrap();
+
+    let names: Vec<&str> = tokens
+        .iter()
+        .filter_map(|token| match &token.kind {
+            TokenKind::Literal(Literal::Identifier(name)) => Some(&**name),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(names, ["größe", "naïve_名前2", "cafe\u{301}"]);
+
+    // Columns count characters, however many bytes they take up
+    assert_eq!(tokens[2].position.1, 10);
+    assert_eq!(tokens[4].position.1, 22);
+}
+
+#[test]
+fn strings_may_contain_unicode_escapes() {
+    assert_eq!(
+        kinds(r#""caf\u{e9} \u{1F600}\u{0}""#)[0],
+        Ok(TokenKind::Literal(Literal::String("café 😀\0".into())))
+    );
+
+    for bad in [
+        r#""\u{d800}""#,
+        r#""\u{110000}""#,
+        r#""\ué""#,
+        r#""\u{}""#,
+        r#""\u{e9""#,
+    ] {
+        assert!(kinds(bad)[0].is_err(), "{} should not lex", bad);
+    }
+}
+
+#[test]
+fn block_comments_nest_and_end_statements_across_lines() {
+    let number = |n: i64| Ok(TokenKind::Literal(Literal::Int(n)));
+
+    assert_eq!(
+        kinds("1 #[ a #[ nested ]# comment ]# 2"),
+        [number(1), number(2), Ok(TokenKind::Eof)]
+    );
+
+    // A block comment spanning lines separates statements like the newline inside it,
+    // except at the end of the source
+    assert_eq!(
+        kinds("1 #[ a\ncomment ]# 2 #[\n]#"),
+        [
+            number(1),
+            Ok(TokenKind::Newline),
+            number(2),
+            Ok(TokenKind::Eof)
+        ]
+    );
+
+    let unterminated = kinds("1 #[ a #[ b ]#\n");
+    assert!(unterminated[1].is_err());
+}
+
+#[test]
+fn trailing_whitespace_and_comments_end_the_source() {
+    for source in ["print 1  ", "print 1 # done", "print 1 #[ done ]#  "] {
+        let kinds = kinds(source);
+        assert!(kinds.iter().all(Result::is_ok), "{:?}", source);
+        assert_eq!(kinds.last(), Some(&Ok(TokenKind::Eof)));
+    }
+}
+
+#[test]
+fn numbers_may_use_other_bases_separators_and_exponents() {
+    let number = |source: &str| match &kinds(source)[0] {
+        Ok(TokenKind::Literal(lit)) => lit.clone(),
+        kind => panic!("{:?} lexed as {:?}", source, kind),
+    };
+
+    assert_eq!(number("0xFF"), Literal::Int(255));
+    assert_eq!(number("0b1010"), Literal::Int(10));
+    assert_eq!(number("0o755"), Literal::Int(493));
+    assert_eq!(number("1_000_000"), Literal::Int(1_000_000));
+    assert_eq!(number("1.5e-3"), Literal::Float(0.0015));
+    assert_eq!(number("2E+2"), Literal::Float(200.0));
+    assert_eq!(number("1.0"), Literal::Float(1.0));
+
+    // Decimal whole numbers too large for an integer become floats
+    assert_eq!(number("9223372036854775807"), Literal::Int(i64::MAX));
+    assert_eq!(
+        number("9223372036854775808"),
+        Literal::Float(9223372036854775808.0)
+    );
+    assert_eq!(number("0x7fff_ffff_ffff_ffff"), Literal::Int(i64::MAX));
+
+    // An 'e' is a hexadecimal digit, so a following minus sign is subtraction
+    assert_eq!(
+        kinds("0x1e-3")[..3],
+        [
+            Ok(TokenKind::Literal(Literal::Int(30))),
+            Ok(TokenKind::Minus),
+            Ok(TokenKind::Literal(Literal::Int(3)))
+        ]
+    );
+}
+
+#[test]
+fn compound_assignment_operators_are_single_tokens() {
+    assert_eq!(
+        kinds("+= -= *= /= %= - =")[..7],
+        [
+            Ok(TokenKind::PlusEqual),
+            Ok(TokenKind::MinusEqual),
+            Ok(TokenKind::StarEqual),
+            Ok(TokenKind::SlashEqual),
+            Ok(TokenKind::PercentEqual),
+            Ok(TokenKind::Minus),
+            Ok(TokenKind::Equal),
+        ]
+    );
+
+    // Three question marks make a placeholder, which ends a statement like a value does
+    assert_eq!(
+        kinds("? ???\n")[..3],
+        [
+            Ok(TokenKind::Question),
+            Ok(TokenKind::Placeholder),
+            Ok(TokenKind::Newline),
+        ]
+    );
+
+    // A star followed by another is the power operator rather than two multiplications
+    assert_eq!(
+        kinds("** * *=")[..3],
+        [
+            Ok(TokenKind::StarStar),
+            Ok(TokenKind::Star),
+            Ok(TokenKind::StarEqual),
+        ]
+    );
+
+    assert_eq!(
+        kinds("& | ^ ~ << <= >> >=")[..8],
+        [
+            Ok(TokenKind::Ampersand),
+            Ok(TokenKind::Pipe),
+            Ok(TokenKind::Caret),
+            Ok(TokenKind::Tilde),
+            Ok(TokenKind::LessLess),
+            Ok(TokenKind::LessEqual),
+            Ok(TokenKind::GreaterGreater),
+            Ok(TokenKind::GreaterEqual),
+        ]
+    );
+}
+
+#[test]
+fn interpolated_strings_are_split_around_their_expressions() {
+    let text = |text: &str| Ok(TokenKind::Interpolation(Rc::from(text)));
+    let string = |text: &str| Ok(TokenKind::Literal(Literal::String(Rc::from(text))));
+
+    assert_eq!(
+        kinds("\"a ${ {k: 1} } b ${x}\" \"\\${x}\"")[..10],
+        [
+            text("a "),
+            Ok(TokenKind::LeftBrace),
+            Ok(TokenKind::Literal(Literal::Identifier(Rc::from("k")))),
+            Ok(TokenKind::Colon),
+            Ok(TokenKind::Literal(Literal::Int(1))),
+            Ok(TokenKind::RightBrace),
+            text(" b "),
+            Ok(TokenKind::Literal(Literal::Identifier(Rc::from("x")))),
+            string(""),
+            string("${x}"),
+        ]
+    );
+
+    // Strings can't span lines, even inside an interpolation
+    assert!(kinds("\"${1 +\n2}\"").iter().any(Result::is_err));
+}
+
+#[test]
+fn malformed_numbers_are_reported_whole() {
+    let message = |source: &str| match &kinds(source)[0] {
+        Err(err) => err.message(),
+        kind => panic!("{:?} lexed as {:?}", source, kind),
+    };
+
+    assert_eq!(message("0x"), "Expected hexadecimal digits after '0x'");
+    assert_eq!(
+        message("1e"),
+        "Expected digits after the exponent in number literal '1e'"
+    );
+    assert_eq!(
+        message("0b102"),
+        "Invalid digit '2' in binary number literal '0b102'"
+    );
+    assert_eq!(
+        message("1__000"),
+        "Underscores in number literal '1__000' must separate digits"
+    );
+    assert_eq!(message("1e400"), "Number literal '1e400' is too large");
+
+    // Hexadecimal, binary and octal literals must fit in an integer
+    assert_eq!(
+        message("0xffffffffffffffff"),
+        "Number literal '0xffffffffffffffff' is too large"
+    );
+    assert_eq!(
+        message(&format!("0b1{}", "0".repeat(63))),
+        format!("Number literal '0b1{}' is too large", "0".repeat(63))
+    );
+    assert_eq!(
+        message("0o1_000_000_000_000_000_000_000"),
+        "Number literal '0o1_000_000_000_000_000_000_000' is too large"
+    );
+}
@@ -0,0 +1,223 @@
+// Checks the diagnostic message catalogs and rendering messages in other languages.
+
+use std::{collections::HashSet, fs, path::Path, process::Command};
+
+use lula::{
+    eval,
+    messages::{fill, parse_catalog, Language},
+};
+
+fn placeholders(template: &str) -> HashSet<String> {
+    template
+        .split('{')
+        .skip(1)
+        .filter_map(|rest| {
+            let (inside, _) = rest.split_once('}')?;
+            inside.parse::<usize>().ok().map(|n| n.to_string())
+        })
+        .collect()
+}
+
+// Every string literal passed to 'Message::new' or naming a 'word.' key in the source
+fn keys_in_source(dir: &Path, keys: &mut Vec<String>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+
+        if path.is_dir() {
+            keys_in_source(&path, keys);
+        } else if path.extension() == Some("rs".as_ref()) {
+            let source = fs::read_to_string(&path).unwrap();
+
+            for chunk in source.split("Message::new(\"").skip(1) {
+                keys.push(chunk.split('"').next().unwrap().to_owned());
+            }
+
+            for chunk in source.split("\"word.").skip(1) {
+                keys.push(format!("word.{}", chunk.split('"').next().unwrap()));
+            }
+        }
+    }
+}
+
+#[test]
+fn translations_cover_every_english_message() {
+    let english = parse_catalog(Language::English.catalog());
+
+    for language in Language::ALL {
+        let catalog = parse_catalog(language.catalog());
+        let mut missing: Vec<&&str> = english
+            .keys()
+            .filter(|k| !catalog.contains_key(*k))
+            .collect();
+        missing.sort();
+        assert!(
+            missing.is_empty(),
+            "{:?} is missing {:?}",
+            language,
+            missing
+        );
+
+        for (key, template) in &catalog {
+            let english = english
+                .get(key)
+                .unwrap_or_else(|| panic!("unknown key {}", key));
+
+            assert_eq!(
+                placeholders(template),
+                placeholders(english),
+                "{:?} uses different arguments for {}",
+                language,
+                key
+            );
+        }
+    }
+}
+
+#[test]
+fn every_message_raised_is_in_the_catalog() {
+    let english = parse_catalog(Language::English.catalog());
+    let mut keys = Vec::new();
+    keys_in_source(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("src"),
+        &mut keys,
+    );
+
+    assert!(keys.len() > 40);
+
+    for key in keys {
+        let known = english.contains_key(key.as_str())
+            || english
+                .keys()
+                .any(|k| k.split_once('.').map(|(_, name)| name) == Some(&key));
+
+        assert!(known, "'{}' is missing from the English catalog", key);
+    }
+}
+
+#[test]
+fn placeholders_are_filled_and_other_braces_kept() {
+    assert_eq!(
+        fill(
+            "'\\u{{0}}' and {1} but not {x} or {",
+            &["e9".into(), "b".into()]
+        ),
+        "'\\u{e9}' and b but not {x} or {"
+    );
+}
+
+#[test]
+fn messages_render_in_any_language() {
+    let errors = eval("let xs = [1]\nprint xs[2]").unwrap_err();
+
+    assert_eq!(
+        errors[0].message_in(Language::English),
+        "Index 2 is out of bounds for list of length 1"
+    );
+    assert_eq!(
+        errors[0].message_in(Language::Spanish),
+        "El índice 2 está fuera de los límites de una lista de longitud 1"
+    );
+
+    assert_eq!(Language::from_code("es_MX.UTF-8"), Some(Language::Spanish));
+    assert_eq!(Language::from_code("fr"), None);
+}
+
+#[test]
+fn language_is_chosen_by_flag_or_environment() {
+    let run = |lang_var: Option<&str>, flags: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_lula-lang"));
+        command
+            .args(flags)
+            .arg("tests/diagnostics/undefined_variable.lla")
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .env_remove("LULA_LANG");
+
+        if let Some(code) = lang_var {
+            command.env("LULA_LANG", code);
+        }
+
+        let output = command.output().expect("failed to run interpreter");
+        (
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )
+    };
+
+    let (_, stderr) = run(Some("es"), &[]);
+    assert!(stderr.starts_with("Error de nombre[E0302]"), "{}", stderr);
+
+    // The flag wins over the environment
+    let (_, stderr) = run(Some("es"), &["--lang=en"]);
+    assert!(stderr.starts_with("Name error[E0302]"), "{}", stderr);
+
+    assert_eq!(run(None, &["--lang=xx"]).0, Some(64));
+}
+
+#[test]
+fn every_error_code_is_explained() {
+    let english = parse_catalog(Language::English.catalog());
+    let codes: HashSet<&str> = english
+        .keys()
+        .filter_map(|key| key.split_once('.'))
+        .map(|(code, _)| code)
+        .filter(|code| code.starts_with(['E', 'W']))
+        .collect();
+
+    for language in Language::ALL {
+        for code in &codes {
+            assert!(
+                language.explanation(code).is_some(),
+                "{:?} doesn't explain {}",
+                language,
+                code
+            );
+        }
+    }
+
+    // Messages with their own explanation use it, and others fall back to their code's
+    let english = Language::English;
+    assert_ne!(
+        english.explanation("E0302.undeclared-assignment"),
+        english.explanation("E0302")
+    );
+    assert_eq!(
+        english.explanation("E0302.undefined-variable"),
+        english.explanation("E0302")
+    );
+    assert_eq!(english.explanation("E9999"), None);
+}
+
+#[test]
+fn errors_are_explained_on_request() {
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_lula-lang"))
+            .args(args)
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .env_remove("LULA_LANG")
+            .output()
+            .expect("failed to run interpreter");
+        (
+            output.status.code(),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )
+    };
+
+    let (code, _, stderr) = run(&[
+        "--explain-errors",
+        "tests/diagnostics/undefined_variable.lla",
+    ]);
+    assert_eq!(code, Some(70));
+    assert!(stderr.contains("\n~ Wrong:\n"), "{}", stderr);
+
+    // Without the flag diagnostics stay short
+    let (_, _, stderr) = run(&["tests/diagnostics/undefined_variable.lla"]);
+    assert!(!stderr.contains("~ Wrong:"), "{}", stderr);
+
+    let (code, stdout, _) = run(&["explain", "E0302"]);
+    assert_eq!(code, Some(0));
+    assert!(stdout.contains("Wrong:"), "{}", stdout);
+
+    assert_eq!(run(&["explain", "X"]).0, Some(64));
+    assert_eq!(run(&["explain"]).0, Some(64));
+}
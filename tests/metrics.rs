@@ -0,0 +1,101 @@
+// Checks function measurements and duplicate block detection.
+
+use lula::{
+    metrics::{measure, Metrics, Thresholds},
+    token::Position,
+    Lexer, Parser,
+};
+
+fn metrics(source: &str) -> Metrics {
+    let statements = Parser::new(Lexer::new(source.to_owned()))
+        .collect_statements()
+        .unwrap();
+
+    measure(&statements, Thresholds::default().duplicate_statements)
+}
+
+const CLASSIFY: &str = "func classify(n) {\n    if n < 0 {\n        return \"negative\"\n    } elif n == 0 or n == 1 {\n        return \"small\"\n    }\n    for i in 0..n {\n        while i > 2 {\n            i = i - 1\n        }\n    }\n    let check = func(x) {\n        return x and true\n    }\n    return \"large\"\n}\n";
+
+#[test]
+fn functions_are_measured() {
+    let metrics = metrics(CLASSIFY);
+    let names: Vec<&str> = metrics.functions.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["classify", "check"]);
+
+    let classify = &metrics.functions[0];
+    assert_eq!(classify.position, Position(0, 5, 0));
+
+    // if, elif, or, for and while
+    assert_eq!(classify.complexity, 6);
+    assert_eq!(classify.statements, 8);
+    assert_eq!(classify.nesting, 2);
+
+    let check = &metrics.functions[1];
+    assert_eq!(
+        (check.complexity, check.statements, check.nesting),
+        (2, 1, 0)
+    );
+}
+
+#[test]
+fn anonymous_functions_count_towards_their_enclosing_function() {
+    let metrics = metrics("func outer() {\n    return [1].map(func(x) {\n        if x {\n            return 1\n        }\n    })\n}\n");
+
+    assert_eq!(metrics.functions.len(), 1);
+    assert_eq!(metrics.functions[0].complexity, 2);
+    assert_eq!(metrics.functions[0].nesting, 2);
+}
+
+#[test]
+fn warnings_report_measurements_over_thresholds() {
+    let thresholds = Thresholds {
+        complexity: 5,
+        nesting: 1,
+        ..Thresholds::default()
+    };
+    let warnings = metrics(CLASSIFY).warnings(&thresholds);
+
+    let messages: Vec<&str> = warnings.iter().map(|(_, m)| m.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Function 'classify' has complexity 6, above the limit of 5",
+            "Function 'classify' has nesting depth 2, above the limit of 1",
+        ]
+    );
+    assert!(metrics(CLASSIFY)
+        .warnings(&Thresholds::default())
+        .is_empty());
+}
+
+#[test]
+fn near_duplicate_blocks_are_found() {
+    let block = |name: &str| {
+        format!(
+            "if {0} > 5 {{\n        print {0}\n        print {0} * 2\n        {0} = {0} - 1\n        print \"{0}\"\n    }}",
+            name
+        )
+    };
+    let source = format!(
+        "func a(x) {{\n    {}\n}}\nfunc b(y) {{\n    {}\n}}\nfunc c(z) {{\n    print z\n}}\n",
+        block("x"),
+        block("y")
+    );
+
+    let metrics = metrics(&source);
+
+    // The function bodies are duplicates too, so the blocks nested in them aren't reported
+    assert_eq!(
+        metrics.duplicates,
+        [vec![Position(1, 4, 0), Position(9, 4, 0)]]
+    );
+}
+
+#[test]
+fn small_or_different_blocks_are_not_duplicates() {
+    let metrics = metrics(
+        "{\n    print 1\n}\n{\n    print 2\n}\n{\n    print 1\n    print 2\n    print 3\n    print 4\n}\n{\n    print a\n    print 2\n    print 3\n    print 4\n}\n",
+    );
+
+    assert!(metrics.duplicates.is_empty());
+}
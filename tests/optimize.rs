@@ -0,0 +1,93 @@
+// Checks that constant folding simplifies programs without changing what they do.
+
+use lula::{
+    expr::Expr,
+    optimize::optimize,
+    statement::Statement,
+    token::{Literal, Token, TokenKind},
+    Interpreter, Lexer, LulaError, Parser, Value,
+};
+
+fn parse(source: &str) -> Vec<Statement> {
+    Parser::new(Lexer::new(source.to_owned()))
+        .collect_statements()
+        .unwrap()
+}
+
+// The literal a single expression statement folds down to, if it folds completely
+fn folded(source: &str) -> Option<Literal> {
+    match optimize(parse(source)).pop() {
+        Some(Statement::Expr(Expr::Literal(Token {
+            kind: TokenKind::Literal(lit),
+            ..
+        }))) => Some(lit),
+        _ => None,
+    }
+}
+
+fn run(statements: &[Statement]) -> Result<Value, LulaError> {
+    Interpreter::new().run_statements(statements)
+}
+
+fn assert_unchanged_behaviour(source: &str) {
+    assert_eq!(
+        run(&optimize(parse(source))),
+        run(&parse(source)),
+        "folding changed the result of:\n{}",
+        source
+    );
+}
+
+#[test]
+fn constant_expressions_fold_to_literals() {
+    assert_eq!(folded("1 + 2 * 3"), Some(Literal::Int(7)));
+    assert_eq!(folded("\"a\" + \"b\""), Some(Literal::String("ab".into())));
+    assert_eq!(folded("-(2 - 5) >= 3"), Some(Literal::Bool(true)));
+    assert_eq!(folded("!(1 > 2)"), Some(Literal::Bool(true)));
+    assert_eq!(folded("-2 ** 3 ** 2"), Some(Literal::Int(-512)));
+}
+
+#[test]
+fn constant_conditions_pick_an_operand() {
+    assert_eq!(folded("false and missing"), Some(Literal::Bool(false)));
+    assert_eq!(folded("false or 2"), Some(Literal::Int(2)));
+    assert_eq!(folded("true and missing"), None);
+    assert_eq!(folded("1 > 2 ? missing : 3"), Some(Literal::Int(3)));
+    assert_eq!(folded("false ? 1 : 2 + 2"), Some(Literal::Int(4)));
+
+    // Other constants are left for the interpreter to test, as strict semantics reject them
+    assert_eq!(folded("nil or 2"), None);
+    assert_eq!(folded("0 ? 1 : 2"), None);
+}
+
+#[test]
+fn failing_and_variable_expressions_are_kept() {
+    assert_eq!(folded("1 + \"a\""), None);
+    assert_eq!(folded("let x = 1\nx + 1"), None);
+    assert_eq!(folded("[1 + 1]"), None);
+}
+
+#[test]
+fn function_bodies_are_folded() {
+    let statements = optimize(parse("func f() {\n    return 2 * 3\n}"));
+
+    match &statements[..] {
+        [Statement::Func(_, _, body)] => match &body[..] {
+            [Statement::Return(Some(Expr::Literal(..)))] => {}
+            body => panic!("expected a folded return, found {:?}", body),
+        },
+        statements => panic!("expected a single function, found {:?}", statements),
+    }
+}
+
+#[test]
+fn folding_preserves_behaviour() {
+    assert_unchanged_behaviour("1 + 2 * 3 - 4 / 2 % 3");
+    assert_unchanged_behaviour("(1 + 2) * (3 + 4) == 21 and \"x\" + \"y\"");
+    assert_unchanged_behaviour("1 + \"a\"");
+    assert_unchanged_behaviour("-\"a\"");
+    assert_unchanged_behaviour("let x = 2 + 3\nfunc f(n) {\n    return n * (1 + 1)\n}\nf(x)");
+    assert_unchanged_behaviour(
+        "let total = 0\nfor i in 0..(2 + 3) {\n    total = total + i\n}\ntotal",
+    );
+}
@@ -0,0 +1,77 @@
+// Checks that the parser recovers from errors, leaving error nodes in the syntax tree in
+// place of what failed to parse.
+
+use lula::{expr::Expr, statement::Statement, Lexer, LulaError, Parser};
+
+fn parse_partial(source: &str) -> (Vec<Statement>, Vec<LulaError>) {
+    Parser::new(Lexer::new(source.to_owned())).collect_partial()
+}
+
+#[test]
+fn statements_around_errors_are_kept() {
+    let source = "let a = 1\nlet b = * 2\nfunc f(x) {\n    x = = 1\n    return x\n}\nprint a\n";
+    let (statements, errors) = parse_partial(source);
+
+    // The bad value is reported along with the declaration it belongs to
+    assert_eq!(errors.len(), 3);
+
+    assert_eq!(statements.len(), 4);
+    assert!(matches!(&statements[0], Statement::VarDecl(a, Some(..)) if a.identifier == "a"));
+    assert!(matches!(
+        &statements[1],
+        Statement::VarDecl(b, Some(Expr::Error(tokens))) if b.identifier == "b" && tokens.len() == 2
+    ));
+    assert!(matches!(
+        &statements[2],
+        Statement::Func(f, _, body) if f.identifier == "f"
+            && matches!(body.as_slice(), [Statement::Error(..), Statement::Return(Some(..))])
+    ));
+    assert!(matches!(statements[3], Statement::Print(..)));
+
+    // Complete programs are still only produced without errors
+    assert_eq!(
+        Parser::new(Lexer::new(source.to_owned()))
+            .collect_statements()
+            .unwrap_err(),
+        errors
+    );
+}
+
+#[test]
+fn lexing_errors_replace_parsing_errors() {
+    // The parser pulls tokens as it goes, so meets the lexing error partway through, and
+    // still parses the rest. Only the lexing error is reported though, since the parsing
+    // error after it could have been caused by the symbol the lexer left out
+    let (statements, errors) = parse_partial(
+        "let a = 1 $ 2
+let b = = 2
+print a
+",
+    );
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].code(), "E0100");
+    assert_eq!(statements.len(), 3);
+}
+
+#[test]
+fn skipped_statements_end_with_their_block() {
+    // The for loop's body is skipped along with its header, but the statement after it is
+    // on a line of its own, so isn't
+    let (statements, errors) = parse_partial("for in xs {\n    print 1\n}\nprint 2\n");
+
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(
+        statements.as_slice(),
+        [Statement::Error(..), Statement::Print(..)]
+    ));
+
+    // Statements skipped inside a block don't run past its closing brace
+    let (statements, errors) = parse_partial("loop {\n    break +\n}\nprint 2");
+
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(
+        statements.as_slice(),
+        [Statement::Loop(_, body), Statement::Print(..)] if matches!(body.as_slice(), [Statement::Error(..)])
+    ));
+}
@@ -0,0 +1,46 @@
+// Checks that programs compiled ahead of time can be run again and again.
+
+use lula::{error::RuntimeErrorKind, interpreter::Backend, Interpreter, LulaError, Program, Value};
+
+#[test]
+fn programs_can_be_run_many_times() {
+    let program = Program::compile("counter += 1\ncounter * 10").unwrap();
+
+    for backend in [Backend::Tree, Backend::Vm] {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_backend(backend);
+        interpreter.define("counter", Value::Int(0));
+
+        // Globals outlive each run, so every run sees the last one's changes
+        for run in 1..=3 {
+            assert_eq!(
+                interpreter.run(&program),
+                Ok(Value::Int(run * 10)),
+                "{:?}",
+                backend
+            );
+        }
+    }
+}
+
+#[test]
+fn programs_report_errors_on_every_run() {
+    assert!(matches!(
+        Program::compile("let = 1").unwrap_err().as_slice(),
+        [LulaError::ParseError(..)]
+    ));
+
+    let program = Program::compile("missing + 1").unwrap();
+
+    for backend in [Backend::Tree, Backend::Vm] {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_backend(backend);
+
+        for _ in 0..2 {
+            assert!(matches!(
+                interpreter.run(&program),
+                Err(LulaError::RuntimeError(RuntimeErrorKind::Name, ..))
+            ));
+        }
+    }
+}
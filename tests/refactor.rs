@@ -0,0 +1,133 @@
+// Checks refactorings, which must rewrite only what they change and never change what a
+// program does.
+
+use lula::{
+    eval,
+    refactor::{extract_function, rename, RefactorError},
+};
+
+const SOURCE: &str = "let count = 0\nfunc bump(by) {\n    count = count + by  # keep\n    return count\n}\nfor i in 0..3 {\n    let count = i\n    print count\n}\nprint bump(len(\"ab\"))\n";
+
+fn rejected(result: Result<String, RefactorError>) -> bool {
+    matches!(result, Err(RefactorError::Rejected(..)))
+}
+
+#[test]
+fn rename_updates_every_reference() {
+    assert_eq!(
+        rename(SOURCE, 3, 5, "total"),
+        Ok(SOURCE
+            .replacen("let count", "let total", 1)
+            .replace("count = count + by", "total = total + by")
+            .replace("return count", "return total"))
+    );
+}
+
+#[test]
+fn rename_leaves_shadowing_variables_alone() {
+    assert_eq!(
+        rename(SOURCE, 8, 11, "n"),
+        Ok(SOURCE
+            .replace("let count = i", "let n = i")
+            .replace("print count", "print n"))
+    );
+}
+
+#[test]
+fn rename_reaches_into_interpolated_strings() {
+    let source = "let n = 1\nprint \"n is ${n + 1}\"\n";
+
+    assert_eq!(
+        rename(source, 1, 5, "count"),
+        Ok(source
+            .replace("let n", "let count")
+            .replace("${n", "${count"))
+    );
+}
+
+#[test]
+fn rename_finds_functions_declared_later() {
+    let source = "func a() {\n    return b()\n}\nfunc b() {\n    return 1\n}\n";
+
+    assert_eq!(rename(source, 4, 6, "c"), Ok(source.replace("b()", "c()")));
+}
+
+#[test]
+fn rename_rejects_conflicts() {
+    // 'count' would be captured by the parameter, and 'bump' would capture the builtin
+    assert!(rejected(rename(SOURCE, 1, 5, "by")));
+    assert!(rejected(rename(SOURCE, 2, 6, "len")));
+}
+
+#[test]
+fn rename_rejects_invalid_targets() {
+    assert!(rejected(rename(SOURCE, 1, 1, "x")));
+    assert!(rejected(rename(SOURCE, 10, 12, "size")));
+    assert!(rejected(rename(SOURCE, 1, 5, "while")));
+    assert!(rejected(rename(SOURCE, 1, 5, "2fast")));
+}
+
+const REPORT: &str = "let scale = 2\n\nfunc report(items) {\n    let total = 0\n    let count = 0\n    for item in items {\n        total = total + item * scale\n        count = count + 1\n    }\n    let mean = total / count\n    return [total, count, mean]\n}\n\nreport([1, 2, 3])\n";
+
+#[test]
+fn extract_passes_locals_and_returns_assignments() {
+    let extracted = extract_function(REPORT, 7, 8, "step").unwrap();
+
+    assert_eq!(
+        extracted,
+        "let scale = 2\n\nfunc step(total, item, count) {\n    total = total + item * scale\n    count = count + 1\n    return [total, count]\n}\n\nfunc report(items) {\n    let total = 0\n    let count = 0\n    for item in items {\n        let step_results = step(total, item, count)\n        total = step_results[0]\n        count = step_results[1]\n    }\n    let mean = total / count\n    return [total, count, mean]\n}\n\nreport([1, 2, 3])\n"
+    );
+    assert_eq!(eval(&extracted), eval(REPORT));
+}
+
+#[test]
+fn extract_returns_declarations_used_later() {
+    let extracted = extract_function(REPORT, 10, 10, "average").unwrap();
+
+    assert!(extracted.contains(
+        "func average(total, count) {\n    let mean = total / count\n    return mean\n}"
+    ));
+    assert!(extracted.contains("    let mean = average(total, count)\n"));
+    assert_eq!(eval(&extracted), eval(REPORT));
+}
+
+#[test]
+fn extract_leaves_globals_in_reach() {
+    let source = "let count = 0\nprint \"start\"\ncount = count + 1\ncount\n";
+    let extracted = extract_function(source, 2, 3, "bump").unwrap();
+
+    assert_eq!(
+        extracted,
+        "let count = 0\nfunc bump() {\n    print \"start\"\n    count = count + 1\n}\n\nbump()\ncount\n"
+    );
+    assert_eq!(eval(&extracted), eval(source));
+}
+
+#[test]
+fn extract_rejects_partial_statements() {
+    // Part of a loop, a block's closing brace, and a return out of the function
+    assert!(rejected(extract_function(REPORT, 6, 7, "f")));
+    assert!(rejected(extract_function(REPORT, 7, 9, "f")));
+    assert!(rejected(extract_function(REPORT, 10, 11, "f")));
+    assert!(rejected(extract_function(
+        "if true: print 1\nelse: print 2\n",
+        1,
+        1,
+        "f"
+    )));
+    assert!(rejected(extract_function(
+        "let xs = [1,\n    2]\n",
+        2,
+        2,
+        "f"
+    )));
+}
+
+#[test]
+fn extract_rejects_invalid_targets() {
+    assert!(rejected(extract_function(REPORT, 7, 8, "report")));
+    assert!(rejected(extract_function(REPORT, 7, 8, "item")));
+    assert!(rejected(extract_function(REPORT, 7, 8, "for")));
+    assert!(rejected(extract_function(REPORT, 2, 2, "f")));
+    assert!(rejected(extract_function(REPORT, 8, 30, "f")));
+}
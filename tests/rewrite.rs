@@ -0,0 +1,33 @@
+// Checks that source can be rebuilt from its tokens, with edits confined to the tokens
+// that were changed.
+
+use lula::{
+    rewrite::{resynthesize, source_tokens},
+    token::{Literal, TokenKind},
+};
+
+const SOURCE: &str = "# greet someone\nfunc greet(name) {\n\tprint \"hi \" +   name  # inline\n}\n\n#[ a block\n   comment ]#\ngreet(\"bob\\t\") #[ done ]#";
+
+#[test]
+fn untouched_tokens_reproduce_source() {
+    let tokens = source_tokens(SOURCE).unwrap();
+    assert_eq!(resynthesize(&tokens), SOURCE);
+}
+
+#[test]
+fn edits_only_change_edited_tokens() {
+    let mut tokens = source_tokens(SOURCE).unwrap();
+
+    for token in &mut tokens {
+        if token.token.kind == TokenKind::Literal(Literal::Identifier("name".into())) {
+            token.text = String::from("who");
+        }
+    }
+
+    assert_eq!(
+        resynthesize(&tokens),
+        SOURCE
+            .replace("(name)", "(who)")
+            .replace("+   name", "+   who")
+    );
+}
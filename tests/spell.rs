@@ -0,0 +1,90 @@
+// Checks the spelling lint over string literals and comments.
+
+use lula::{
+    spell::{check_spelling, Wordlist},
+    token::Position,
+};
+
+fn warnings(source: &str, wordlist: &Wordlist) -> Vec<(Position, String)> {
+    check_spelling(source, 0, wordlist)
+        .unwrap()
+        .iter()
+        .map(|warning| (warning.diagnostic().position, warning.message()))
+        .collect()
+}
+
+#[test]
+fn typos_in_strings_and_comments_are_flagged() {
+    let source = "# Prints a mesage\nprint \"Helo wrold\"\nlet recieve = 1\n";
+
+    assert_eq!(
+        warnings(source, &Wordlist::default()),
+        [
+            (
+                Position(0, 11, 0),
+                String::from("Unknown word 'mesage', perhaps 'message'")
+            ),
+            (
+                Position(1, 7, 0),
+                String::from("Unknown word 'Helo', perhaps 'hello'")
+            ),
+            (
+                Position(1, 12, 0),
+                String::from("Unknown word 'wrold', perhaps 'world'")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn interpolated_strings_are_checked_around_their_expressions() {
+    let source = "let recieve = 1\nprint \"Helo ${recieve} wrold\"\n";
+
+    assert_eq!(
+        warnings(source, &Wordlist::default()),
+        [
+            (
+                Position(1, 7, 0),
+                String::from("Unknown word 'Helo', perhaps 'hello'")
+            ),
+            (
+                Position(1, 23, 0),
+                String::from("Unknown word 'wrold', perhaps 'world'")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn inflected_words_are_known_and_keep_their_inflection_in_suggestions() {
+    let wordlist = Wordlist::default();
+
+    assert!(wordlist.contains("values"));
+    assert!(wordlist.contains("stopped"));
+    assert!(wordlist.contains("making"));
+    assert_eq!(wordlist.suggest("varibles").as_deref(), Some("variables"));
+    assert_eq!(wordlist.suggest("xyzzy"), None);
+}
+
+#[test]
+fn names_acronyms_and_escapes_are_skipped() {
+    let source = "let my_varible = 1\n# my_varible and varible are names\nprint \"HTTPS userNme don't \\tnode\"\n";
+
+    assert!(warnings(source, &Wordlist::default()).is_empty());
+}
+
+#[test]
+fn project_words_extend_the_wordlist() {
+    // Unknown words that aren't close to a known one aren't reported
+    let mut wordlist = Wordlist::default();
+    assert!(warnings("# frobnicat\n", &wordlist).is_empty());
+
+    wordlist.add_words("# Project words\nfrobnicate  lula\n");
+    assert_eq!(
+        warnings("# frobnicat\n", &wordlist),
+        [(
+            Position(0, 2, 0),
+            String::from("Unknown word 'frobnicat', perhaps 'frobnicate'")
+        )]
+    );
+}
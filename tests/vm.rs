@@ -0,0 +1,322 @@
+// Checks that the bytecode VM runs programs exactly as the tree-walking interpreter does.
+
+use std::{cell::RefCell, rc::Rc};
+
+use lula::{
+    error::RuntimeErrorKind, interpreter::Backend, semantics::Semantics, token::Position,
+    Interpreter, Lexer, LulaError, Parser, Value,
+};
+
+fn run(source: &str, backend: Backend) -> Result<Value, LulaError> {
+    let statements = Parser::new(Lexer::new(source.to_owned()))
+        .collect_statements()
+        .unwrap();
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_backend(backend);
+    interpreter.run_statements(&statements)
+}
+
+fn assert_backends_agree(source: &str) {
+    assert_eq!(
+        run(source, Backend::Vm),
+        run(source, Backend::Tree),
+        "backends disagree on:\n{}",
+        source
+    );
+}
+
+#[test]
+fn expressions_match() {
+    assert_backends_agree("1 + 2 * 3 - 4 / 2 % 3");
+    assert_backends_agree("let n = 3\n[-n ** 2, n ** -1, 2 ** n ** 2, n ** 40]");
+    assert_backends_agree("let n = 6\n[n & 3, n | 1, n ^ 5, ~n, n << 60, -n >> 1, n >> 99]");
+    assert_backends_agree("-(1 + 2) == -3 and !false");
+    assert_backends_agree("nil or false or \"fallback\"");
+    assert_backends_agree("1 and nil and missing");
+    assert_backends_agree("\"a\" + \"b\" != \"ab\"");
+    assert_backends_agree("[1, [2, \"x\"], {k: 3}]");
+    assert_backends_agree("({\"b\": 1, a: [2]})[\"a\"][0]");
+    assert_backends_agree("\"hello\"[1..4] + [1, 2, 3][0..2].join(\",\")");
+    assert_backends_agree("\"a,b\".split(\",\").len()");
+    assert_backends_agree("let n = 2\n\"${n} + ${n * 1.5} = ${n + n * 1.5}\"");
+    assert_backends_agree("let n = 2\n[n > 1 ? \"a\" : missing, n < 1 ? missing : n ? 3 : 4]");
+}
+
+#[test]
+fn control_flow_matches() {
+    assert_backends_agree(
+        "let total = 0\nfor i in 0..10 {\n    if i == 7 {\n        break\n    } elif i % 2 == 0 {\n        continue\n    } else {\n        let x = i\n        total = total + x\n    }\n}\ntotal",
+    );
+    assert_backends_agree(
+        "let n = 0\nlet hits = 0\nwhile n < 5 {\n    n = n + 1\n    for k in {b: 1, a: 2} {\n        if n == 3 {\n            break\n        }\n        hits = hits + 1\n    }\n}\n[n, hits]",
+    );
+    assert_backends_agree(
+        "let i = 0\nloop {\n    i = i + 1\n    {\n        if i > 3 {\n            break\n        }\n    }\n}\ni",
+    );
+    assert_backends_agree(
+        "let total = 1\nlet xs = [0, 10]\nfor i in 1..6 {\n    total *= i\n    xs[i % 2] += i\n}\ntotal -= 20\n[total, xs]",
+    );
+}
+
+#[test]
+fn functions_and_closures_match() {
+    assert_backends_agree("func fib(n) {\n    if n < 2 {\n        return n\n    }\n    return fib(n - 1) + fib(n - 2)\n}\nfib(15)");
+    assert_backends_agree(
+        "func counter() {\n    let count = 0\n    return func() {\n        count = count + 1\n        return count\n    }\n}\nlet c = counter()\nc()\nc()\nc()",
+    );
+    assert_backends_agree(
+        "func find(items, target) {\n    for item in items {\n        if item == target {\n            return true\n        }\n    }\n    return false\n}\nfind([1, 2, 3], 2) and !find([1], 5)",
+    );
+    assert_backends_agree("let xs = [1, 2]\nxs[0] = xs[1] = 5\nxs");
+}
+
+#[test]
+fn shadowing_matches() {
+    assert_backends_agree(
+        "let x = 1\nlet seen = [0, 0, 0, 0]\n{\n    seen[0] = x\n    let x = x + 1\n    {\n        let x = x * 10\n        seen[1] = x\n    }\n    seen[2] = x\n}\nseen[3] = x\nseen",
+    );
+    assert_backends_agree(
+        "func f(n) {\n    let total = 0\n    for i in 0..n {\n        let n = i * 2\n        if n > 6 {\n            break\n        }\n        total = total + n\n    }\n    return [total, n]\n}\nf(10)",
+    );
+    assert_backends_agree(
+        "let fs = [nil, nil, nil]\nfor i in 0..3 {\n    let j = i * i\n    fs[i] = func() {\n        return i + j\n    }\n}\n[fs[0](), fs[1](), fs[2]()]",
+    );
+    assert_backends_agree(
+        "func outer() {\n    func a() {\n        return b()\n    }\n    func b() {\n        return 2\n    }\n    return a()\n}\nouter()",
+    );
+}
+
+#[test]
+fn globals_match() {
+    assert_backends_agree(
+        "let count = 0\nfunc bump() {\n    count = count + 1\n}\nbump()\nbump()\ncount",
+    );
+    assert_backends_agree("let n = 1\nfunc f() {\n    return n + m\n}\nlet m = 2\nf()");
+    assert_backends_agree("func f() {\n    return later\n}\nf()");
+    assert_backends_agree("missing = 1");
+    assert_backends_agree(
+        "let x = 1\nfunc outer() {\n    let x = 2\n    func inner() {\n        return x\n    }\n    return inner()\n}\nouter() * 10 + x",
+    );
+    assert_backends_agree(
+        "let x = 1\n{\n    let x = 2\n    let get = func() {\n        return x\n    }\n    x = get() + 1\n}\nx",
+    );
+}
+
+#[test]
+fn errors_match() {
+    assert_backends_agree("1 + \"a\"");
+    assert_backends_agree("let s = \"a\"\ns ** 2");
+    assert_backends_agree("let n = 2.0\nn & 1");
+    assert_backends_agree("let n = -1\n1 << n");
+    assert_backends_agree("let n = 0\n[1.0 / n, 1 % n]");
+    assert_backends_agree("func f() {\n    return [1, ???]\n}\nf()");
+    assert_backends_agree("missing");
+    assert_backends_agree("undeclared = 1");
+    assert_backends_agree("let s = \"a\"\ns -= 1");
+    assert_backends_agree("[1][3]");
+    assert_backends_agree("\"abc\"[2..9]");
+    assert_backends_agree("({})[1]");
+    assert_backends_agree("for x in 5 {\n}");
+    assert_backends_agree("for x in 0..\"a\" {\n}");
+    assert_backends_agree("1.nope()");
+    assert_backends_agree("\"${missing}\"");
+    assert_backends_agree("len(1, 2)");
+    assert_backends_agree("let f = 3\nf(print_me)");
+}
+
+#[test]
+fn vm_recursion_does_not_use_native_stack() {
+    // Deep enough to overflow a test thread's stack if each call recursed natively
+    let result = run("func f() {\n    return f()\n}\nf()", Backend::Vm);
+
+    match result {
+        Err(LulaError::RuntimeError(kind, ..)) => assert_eq!(kind, RuntimeErrorKind::Recursion),
+        result => panic!("expected a recursion error, found {:?}", result),
+    }
+}
+
+#[test]
+fn interpreter_recovers_after_vm_errors() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_backend(Backend::Vm);
+
+    let source = "let x = 1\nfunc f() {\n    {\n        return missing\n    }\n}\nfor i in 0..3 {\n    f()\n}";
+    let statements = Parser::new(Lexer::new(source.to_owned()))
+        .collect_statements()
+        .unwrap();
+    assert!(interpreter.run_statements(&statements).is_err());
+
+    // Globals declared before the error are still visible from the global scope
+    let statements = Parser::new(Lexer::new(String::from("x + 1")))
+        .collect_statements()
+        .unwrap();
+    assert_eq!(interpreter.run_statements(&statements), Ok(Value::Int(2)));
+}
+
+#[test]
+fn busiest_loop_is_warned_about_once() {
+    let source = "let n = 0\nfor i in 0..5 {\n    while n < i * 10 {\n        n = n + 1\n    }\n}\nloop {\n    break\n}";
+    let statements = Parser::new(Lexer::new(source.to_owned()))
+        .collect_statements()
+        .unwrap();
+
+    for backend in [Backend::Tree, Backend::Vm] {
+        let warnings = Rc::new(RefCell::new(Vec::new()));
+        let handler_warnings = Rc::clone(&warnings);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_backend(backend);
+        interpreter.set_step_warning(Some(20));
+        interpreter.set_warning_handler(move |warning| {
+            handler_warnings.borrow_mut().push(warning.clone())
+        });
+        interpreter.run_statements(&statements).unwrap();
+
+        // Of the first 21 iterations, which pass the threshold, 18 are of the inner loop
+        let warnings = warnings.borrow();
+        assert_eq!(warnings.len(), 1, "{:?}", backend);
+        assert_eq!(warnings[0].code(), "W0200");
+        assert_eq!(warnings[0].diagnostic().position, Position(2, 4, 0));
+        assert_eq!(
+            warnings[0].message(),
+            "This loop has run 18 times and may never end"
+        );
+    }
+}
+
+#[test]
+fn backends_count_the_same_statements() {
+    let source = "func f(n) {\n    if n > 0 {\n        return f(n - 1)\n    }\n    return 0\n}\nlet t = 0\nfor i in 0..3 {\n    t += f(i)\n}\nt";
+    let statements = Parser::new(Lexer::new(source.to_owned()))
+        .collect_statements()
+        .unwrap();
+
+    for backend in [Backend::Tree, Backend::Vm] {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_backend(backend);
+        interpreter.run_statements(&statements).unwrap();
+
+        // The 7 statements at the top level, then an if and a return for each of the 6 calls
+        assert_eq!(interpreter.statements_run(), 19, "{:?}", backend);
+    }
+}
+
+#[test]
+fn strict_conditions_must_be_booleans() {
+    let run_strict = |source: &str, backend| {
+        let statements = Parser::new(Lexer::new(source.to_owned()))
+            .collect_statements()
+            .unwrap();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_backend(backend);
+        interpreter.set_semantics(Semantics::Strict);
+        interpreter.run_statements(&statements)
+    };
+
+    for backend in [Backend::Tree, Backend::Vm] {
+        assert_eq!(
+            run_strict("let n = 3\nn > 2 and n != 0 ? n : -n", backend),
+            Ok(Value::Int(3))
+        );
+
+        // Each kind of condition is reported at the start of its expression, or at its
+        // keyword when the expression has no tokens of its own
+        for (source, position) in [
+            ("let n = 3\nif n {\n}", Position(1, 3, 0)),
+            ("let n = 3\nif [] {\n}", Position(1, 0, 0)),
+            ("if false {\n} elif [] {\n}", Position(1, 2, 0)),
+            ("let n = 0\nwhile n {\n}", Position(1, 6, 0)),
+            ("let x = nil\nx ? 1 : 2", Position(1, 0, 0)),
+            ("let x = nil\nx or 1", Position(1, 0, 0)),
+        ] {
+            match run_strict(source, backend) {
+                Err(err) => {
+                    assert_eq!(err.code(), "E0301", "{}", source);
+                    assert_eq!(err.diagnostic().position, position, "{}", source);
+                }
+                result => panic!("expected a type error, found {:?} for:\n{}", result, source),
+            }
+        }
+    }
+}
+
+#[test]
+fn let_fallbacks_replace_only_nil() {
+    let run_with = |source: &str, backend, semantics| {
+        let statements = Parser::new(Lexer::new(source.to_owned()))
+            .collect_statements()
+            .unwrap();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_backend(backend);
+        interpreter.set_semantics(semantics);
+        interpreter.run_statements(&statements)
+    };
+
+    for backend in [Backend::Tree, Backend::Vm] {
+        for semantics in [Semantics::Lenient, Semantics::Strict] {
+            // False is a value like any other, so only nil is replaced
+            assert_eq!(
+                run_with(
+                    "let a = false else 1\nlet b = num(\"abc\") else 2\n\"${a} ${b}\"",
+                    backend,
+                    semantics
+                ),
+                Ok(Value::String(String::from("false 2")))
+            );
+            assert_eq!(
+                run_with(
+                    "func f(v) {\n    let x = v or return -1\n    return x\n}\n\"${f(false)} ${f(nil)}\"",
+                    backend,
+                    semantics
+                ),
+                Ok(Value::String(String::from("false -1")))
+            );
+        }
+    }
+}
+
+#[test]
+fn programs_cannot_change_constants() {
+    let run_frozen = |source: &str, backend| {
+        let statements = Parser::new(Lexer::new(source.to_owned()))
+            .collect_statements()
+            .unwrap();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_backend(backend);
+        interpreter.define_constant("version", Value::Int(2));
+        interpreter.define("host_name", Value::String(String::from("game")));
+        interpreter.freeze();
+        interpreter.run_statements(&statements)
+    };
+
+    for backend in [Backend::Tree, Backend::Vm] {
+        // Constants can be read and shadowed, and new globals declared alongside them
+        assert_eq!(
+            run_frozen(
+                "let total = version\nfunc f() {\n    let version = 3\n    return version\n}\ntotal + f()",
+                backend
+            ),
+            Ok(Value::Int(5))
+        );
+
+        for (source, position) in [
+            ("version = 3", Position(0, 0, 0)),
+            ("let x = 1\nversion += x", Position(1, 0, 0)),
+            ("func f() {\n    version = 1\n}\nf()", Position(1, 4, 0)),
+            ("let host_name = nil", Position(0, 4, 0)),
+            ("func len(x) {\n}", Position(0, 5, 0)),
+        ] {
+            match run_frozen(source, backend) {
+                Err(err) => {
+                    assert_eq!(err.code(), "E0302", "{}", source);
+                    assert_eq!(err.diagnostic().position, position, "{}", source);
+                }
+                result => panic!("expected a name error, found {:?} for:\n{}", result, source),
+            }
+        }
+    }
+}